@@ -3,7 +3,7 @@
 extern crate source_map_mappings;
 extern crate test;
 
-static FIXTURE: &'static [u8] = include_bytes!("./part-of-scala-js-source-map");
+static FIXTURE: &[u8] = include_bytes!("./part-of-scala-js-source-map");
 
 #[bench]
 fn bench_parse_part_of_scala_js_source_map(b: &mut test::Bencher) {
@@ -25,6 +25,15 @@ case "$JOB" in
         cargo build --release --target wasm32-unknown-unknown --features profiling
         test -f target/wasm32-unknown-unknown/release/source_map_mappings_wasm_api.wasm
         ;;
+    "wasi")
+        rustup target add wasm32-wasip1
+        cd source-map-mappings-wasm-api/
+
+        # Without the `mapping-callback` feature there are no JS-specific
+        # imports, and the `cdylib` is built as a WASI reactor.
+        cargo build --release --target wasm32-wasip1 --no-default-features
+        test -f target/wasm32-wasip1/release/source_map_mappings_wasm_api.wasm
+        ;;
     *)
         echo "Unknown \$JOB = '$JOB'"
         exit 1
@@ -10,14 +10,43 @@
 //! 3. Initialize the mappings string by copying the JS `String`'s data into it.
 //!
 //! 4. Parse the mappings with `parse_mappings`. Handle errors, if any.
+//! Alternatively, to parse in bounded slices of work, create a parse state with
+//! `create_parse_state`, call `parse_step` until it returns `true`, and then
+//! get the result from `finish_parse`.
 //!
 //! 5. Query the resulting `Mappings` structure as needed with
 //! `by_generated_location`, `by_original_location`, `compute_column_spans`,
-//! `original_location_for`, `generated_location_for`, and
-//! `all_generated_locations_for` as needed.
+//! `original_location_for`, `nearest_named_mapping`, `generated_location_for`,
+//! `all_generated_locations_for`, and `all_generated_locations_for_with_bias`
+//! as needed.
 //!
 //! 6. When finished with `Mappings` structure, dispose of it with
 //! `free_mappings`.
+//!
+//! ## Building mappings
+//!
+//! To go the other way and generate a mappings string, create a builder with
+//! `create_builder`, add each mapping with `builder_add_mapping`, and encode
+//! them with `builder_finish`. Read the encoded string out of linear memory
+//! with the help of `encoded_mappings_len`, and then dispose of it with
+//! `free_encoded_mappings`.
+//!
+//! ## Without `mapping_callback`
+//!
+//! Hosts that can't supply a `mapping_callback` import, such as WASI runtimes,
+//! can instead have query results written into a buffer in linear memory:
+//!
+//! * Build with `--no-default-features` to remove the `mapping_callback`
+//! import entirely, in which case results are always buffered. Building for a
+//! `wasm32-wasi` target then produces a WASI reactor module.
+//!
+//! * Or, with the default `mapping-callback` feature, call
+//! `set_mapping_buffer_enabled(true)` to buffer results instead of invoking
+//! the callback.
+//!
+//! After each query, `mapping_buffer` and `mapping_buffer_len` describe the
+//! resulting mappings. Each mapping is `MAPPING_RECORD_LEN` consecutive `u32`s,
+//! in the same order as `mapping_callback`'s parameters.
 
 // NB: every exported function must be `#[no_mangle]` and `pub extern "C"`.
 
@@ -25,7 +54,8 @@
 
 extern crate source_map_mappings;
 
-use source_map_mappings::{Bias, Error, Mapping, Mappings};
+use source_map_mappings::{Bias, Error, ErrorDetail, Mapping, Mappings, MappingsBuilder, MappingsParser,
+                          OriginalLocation, OriginalTieBreak, ParseOptions, Recovery};
 use std::mem;
 use std::ptr;
 use std::process;
@@ -121,6 +151,7 @@ mod observer {
 use observer::Observer;
 
 static mut LAST_ERROR: Option<Error> = None;
+static mut LAST_ERROR_DETAIL: Option<ErrorDetail> = None;
 
 /// Get the last error's error code, or 0 if there was none.
 ///
@@ -135,6 +166,41 @@ pub extern "C" fn get_last_error() -> u32 {
     }
 }
 
+/// Get the byte offset within the mappings string of the segment that the last
+/// parse failed on, or 0 if the last error wasn't from parsing.
+#[no_mangle]
+pub extern "C" fn get_last_error_offset() -> usize {
+    unsafe { LAST_ERROR_DETAIL.map_or(0, |detail| detail.offset) }
+}
+
+/// Get the generated line of the segment that the last parse failed on, or 0
+/// if the last error wasn't from parsing.
+#[no_mangle]
+pub extern "C" fn get_last_error_generated_line() -> u32 {
+    unsafe { LAST_ERROR_DETAIL.map_or(0, |detail| detail.generated_line) }
+}
+
+/// Get the index of the segment that the last parse failed on, or 0 if the
+/// last error wasn't from parsing.
+#[no_mangle]
+pub extern "C" fn get_last_error_segment_index() -> usize {
+    unsafe { LAST_ERROR_DETAIL.map_or(0, |detail| detail.segment_index) }
+}
+
+/// Get the segment field that the last parse failed to decode, or 0 if there
+/// was none.
+///
+/// See `source_map_mappings::SegmentField` for the field code definitions.
+#[no_mangle]
+pub extern "C" fn get_last_error_field() -> u32 {
+    unsafe {
+        match LAST_ERROR_DETAIL.and_then(|detail| detail.field) {
+            None => 0,
+            Some(field) => field as u32,
+        }
+    }
+}
+
 #[inline]
 fn assert_pointer_is_word_aligned(p: *mut u8) {
     debug_assert_eq!(p as usize & (mem::size_of::<usize>() - 1), 0);
@@ -171,12 +237,71 @@ pub extern "C" fn allocate_mappings(size: usize) -> *mut u8 {
     ptr
 }
 
+/// A mappings string that was allocated with `allocate_mappings`, and is
+/// deallocated when dropped.
+struct MappingsString {
+    capacity_ptr: *mut usize,
+    capacity: usize,
+    size: usize,
+}
+
+impl MappingsString {
+    /// Take ownership of a mappings string returned by `allocate_mappings`.
+    unsafe fn from_raw(mappings: *mut u8) -> MappingsString {
+        assert_pointer_is_word_aligned(mappings);
+        let mappings = mappings as *mut usize;
+
+        // Unstuff the data we put just before the pointer to the mappings
+        // string.
+        let capacity_ptr = mappings.wrapping_offset(-2);
+        debug_assert!(!capacity_ptr.is_null());
+        let capacity = *capacity_ptr;
+
+        let size_ptr = mappings.wrapping_offset(-1);
+        debug_assert!(!size_ptr.is_null());
+        let size = *size_ptr;
+
+        MappingsString {
+            capacity_ptr,
+            capacity,
+            size,
+        }
+    }
+
+    /// Get the mappings string's bytes.
+    ///
+    /// The bytes live on the heap, and don't move when `self` does, so the
+    /// slice stays valid until `self` is dropped.
+    unsafe fn as_bytes(&self) -> &'static [u8] {
+        let mappings = self.capacity_ptr.wrapping_offset(2);
+        slice::from_raw_parts(mappings as *const u8, self.size)
+    }
+}
+
+impl Drop for MappingsString {
+    fn drop(&mut self) {
+        // Deallocate the mappings string and its two prefix words.
+        let size_in_usizes = (self.size + mem::size_of::<usize>() - 1) / mem::size_of::<usize>();
+        unsafe {
+            Vec::<usize>::from_raw_parts(self.capacity_ptr, size_in_usizes + 2, self.capacity);
+        }
+    }
+}
+
+/// Return the result of parsing, saving any errors on the side for later
+/// inspection by JS if required.
 #[inline]
-fn constrain<'a, T>(_scope: &'a (), reference: &'a T) -> &'a T
-where
-    T: ?Sized,
-{
-    reference
+fn parse_result(result: Result<Mappings<Observer>, ErrorDetail>) -> *mut Mappings<Observer> {
+    match result {
+        Ok(mappings) => Box::into_raw(Box::new(mappings)),
+        Err(detail) => {
+            unsafe {
+                LAST_ERROR = Some(detail.error);
+                LAST_ERROR_DETAIL = Some(detail);
+            }
+            ptr::null_mut()
+        }
+    }
 }
 
 /// Parse the given initialized mappings string into a `Mappings` structure.
@@ -193,53 +318,234 @@ where
 /// input mappings string and must not use it again.
 #[no_mangle]
 pub extern "C" fn parse_mappings(mappings: *mut u8) -> *mut Mappings<Observer> {
-    assert_pointer_is_word_aligned(mappings);
-    let mappings = mappings as *mut usize;
-
-    // Unstuff the data we put just before the pointer to the mappings
-    // string.
-    let capacity_ptr = mappings.wrapping_offset(-2);
-    debug_assert!(!capacity_ptr.is_null());
-    let capacity = unsafe { *capacity_ptr };
-
-    let size_ptr = mappings.wrapping_offset(-1);
-    debug_assert!(!size_ptr.is_null());
-    let size = unsafe { *size_ptr };
-
-    // Construct the input slice from the pointer and parse the mappings.
-    let result = unsafe {
-        let input = slice::from_raw_parts(mappings as *const u8, size);
-        let this_scope = ();
-        let input = constrain(&this_scope, input);
-        source_map_mappings::parse_mappings(input)
+    let input = unsafe { MappingsString::from_raw(mappings) };
+    let result = MappingsParser::with_options(unsafe { input.as_bytes() }, &parse_options()).finish_detailed();
+    drop(input);
+    parse_result(result)
+}
+
+static mut PARSE_RECOVERY: u32 = 0;
+static mut PARSE_GENERATED_ONLY: bool = false;
+static mut PARSE_SKIP_NAMES: bool = false;
+static mut PARSE_STRICT: bool = false;
+static mut PARSE_ASSUME_SORTED: bool = false;
+static mut PARSE_MAX_MAPPINGS: u32 = 0;
+
+/// The options that `parse_mappings` and `create_parse_state` parse with, as
+/// configured by the `set_parse_*` functions.
+fn parse_options() -> ParseOptions {
+    let recovery = match unsafe { PARSE_RECOVERY } {
+        1 => Some(Recovery::SkipLine),
+        2 => Some(Recovery::SkipSegment),
+        _ => None,
     };
+    ParseOptions {
+        recovery,
+        generated_only: unsafe { PARSE_GENERATED_ONLY },
+        skip_names: unsafe { PARSE_SKIP_NAMES },
+        strict: unsafe { PARSE_STRICT },
+        assume_sorted: unsafe { PARSE_ASSUME_SORTED },
+        max_mappings: match unsafe { PARSE_MAX_MAPPINGS } {
+            0 => None,
+            max => Some(max as usize),
+        },
+        ..Default::default()
+    }
+}
+
+/// Choose how subsequent parses recover from malformed segments: `0` (the
+/// default) to fail, `1` to skip the rest of the malformed segment's generated
+/// line, or `2` to skip only the malformed segment.
+#[no_mangle]
+pub extern "C" fn set_parse_recovery(recovery: u32) {
+    unsafe {
+        PARSE_RECOVERY = recovery;
+    }
+}
 
-    // Deallocate the mappings string and its two prefix words.
-    let size_in_usizes = (size + mem::size_of::<usize>() - 1) / mem::size_of::<usize>();
+/// Choose whether subsequent parses only keep each mapping's generated
+/// location (`true`), or also its original location information (`false`, the
+/// default).
+#[no_mangle]
+pub extern "C" fn set_parse_generated_only(generated_only: bool) {
     unsafe {
-        Vec::<usize>::from_raw_parts(capacity_ptr, size_in_usizes + 2, capacity);
+        PARSE_GENERATED_ONLY = generated_only;
     }
+}
 
-    // Return the result, saving any errors on the side for later inspection by
-    // JS if required.
-    match result {
-        Ok(mappings) => Box::into_raw(Box::new(mappings)),
-        Err(e) => {
-            unsafe {
-                LAST_ERROR = Some(e);
-            }
-            ptr::null_mut()
-        }
+/// Choose whether subsequent parses discard each mapping's name (`true`), or
+/// keep it (`false`, the default).
+#[no_mangle]
+pub extern "C" fn set_parse_skip_names(skip_names: bool) {
+    unsafe {
+        PARSE_SKIP_NAMES = skip_names;
     }
 }
 
+/// Choose whether subsequent parses reject segments with a number of fields
+/// other than 1, 4, or 5 (`true`), or not (`false`, the default).
+#[no_mangle]
+pub extern "C" fn set_parse_strict(strict: bool) {
+    unsafe {
+        PARSE_STRICT = strict;
+    }
+}
+
+/// Choose whether subsequent parses trust that each generated line's segments
+/// are already sorted (`true`), or sort them (`false`, the default).
+#[no_mangle]
+pub extern "C" fn set_parse_assume_sorted(assume_sorted: bool) {
+    unsafe {
+        PARSE_ASSUME_SORTED = assume_sorted;
+    }
+}
+
+/// Limit subsequent parses to at most `max_mappings` mappings, or `0` (the
+/// default) for no limit.
+///
+/// Parsing a mappings string with more mappings than this fails with
+/// `Error::TooManyMappings`.
+#[no_mangle]
+pub extern "C" fn set_parse_max_mappings(max_mappings: u32) {
+    unsafe {
+        PARSE_MAX_MAPPINGS = max_mappings;
+    }
+}
+
+/// The state of a time-sliced parse of a mappings string.
+///
+/// Created with `create_parse_state`, driven with `parse_step`, and consumed by
+/// `finish_parse`.
+pub struct ParseState {
+    // NB: `parser` borrows from `input`, so it must be declared, and therefore
+    // dropped, first.
+    parser: MappingsParser<'static, Observer>,
+    input: MappingsString,
+}
+
+/// Begin a time-sliced parse of the given initialized mappings string.
+///
+/// The caller gives up ownership of the input mappings string and must not use
+/// it again. The caller takes ownership of the result, and must pass it to
+/// `finish_parse` when finished.
+#[no_mangle]
+pub extern "C" fn create_parse_state(mappings: *mut u8) -> *mut ParseState {
+    let input = unsafe { MappingsString::from_raw(mappings) };
+    let parser = MappingsParser::with_options(unsafe { input.as_bytes() }, &parse_options());
+    Box::into_raw(Box::new(ParseState { parser, input }))
+}
+
+/// Parse up to `max_segments` more segments of the mappings string.
+///
+/// Returns `true` once there is no more parsing to do, either because the whole
+/// string was parsed or because an error occurred, and `false` otherwise. In
+/// either case, call `finish_parse` to get the result.
+#[no_mangle]
+pub extern "C" fn parse_step(state: *mut ParseState, max_segments: u32) -> bool {
+    let state = unsafe { state.as_mut().unwrap() };
+    state.parser.parse_step(max_segments as usize).unwrap_or(true)
+}
+
+/// Finish a time-sliced parse, parsing anything that remains of the mappings
+/// string.
+///
+/// Returns `NULL` on failure, or a pointer to the parsed `Mappings` structure on
+/// success, exactly like `parse_mappings`. In both cases, the caller gives up
+/// ownership of the parse state and must not use it again.
+#[no_mangle]
+pub extern "C" fn finish_parse(state: *mut ParseState) -> *mut Mappings<Observer> {
+    let state = unsafe { Box::from_raw(state) };
+    let ParseState { parser, input } = *state;
+    let result = parser.finish_detailed();
+    drop(input);
+    parse_result(result)
+}
+
 /// Destroy the given `Mappings` structure.
 ///
 /// The caller gives up ownership of the mappings and must not use them again.
 #[no_mangle]
 pub extern "C" fn free_mappings(mappings: *mut Mappings<Observer>) {
     unsafe {
-        Box::from_raw(mappings);
+        drop(Box::from_raw(mappings));
+    }
+}
+
+/// Create a builder for constructing mappings one at a time, and then encoding
+/// them into a mappings string.
+///
+/// The caller takes ownership of the result, and must pass it to
+/// `builder_finish` when finished.
+#[no_mangle]
+pub extern "C" fn create_builder() -> *mut MappingsBuilder<Observer> {
+    Box::into_raw(Box::new(MappingsBuilder::new()))
+}
+
+/// Add a mapping to the given builder.
+///
+/// The parameters are the same as `mapping_callback`'s, except that there is no
+/// `last_generated_column`.
+#[no_mangle]
+pub extern "C" fn builder_add_mapping(
+    builder: *mut MappingsBuilder<Observer>,
+    generated_line: u32,
+    generated_column: u32,
+    has_original: bool,
+    source: u32,
+    original_line: u32,
+    original_column: u32,
+    has_name: bool,
+    name: u32,
+) {
+    let builder = unsafe { builder.as_mut().unwrap() };
+    let original = if has_original {
+        Some(OriginalLocation {
+            source,
+            original_line,
+            original_column,
+            name: if has_name { Some(name) } else { None },
+        })
+    } else {
+        None
+    };
+    builder.add_mapping(generated_line, generated_column, original);
+}
+
+/// Finish building mappings, and encode them into a mappings string.
+///
+/// Returns a pointer to the encoded mappings string, whose length in bytes is
+/// given by `encoded_mappings_len`. The caller gives up ownership of the
+/// builder and must not use it again. The caller takes ownership of the result,
+/// and must call `free_encoded_mappings` to destroy it when finished.
+#[no_mangle]
+pub extern "C" fn builder_finish(builder: *mut MappingsBuilder<Observer>) -> *mut u8 {
+    let builder = unsafe { Box::from_raw(builder) };
+    let encoded = builder.finish().serialize();
+
+    let ptr = allocate_mappings(encoded.len());
+    unsafe {
+        ptr::copy_nonoverlapping(encoded.as_ptr(), ptr, encoded.len());
+    }
+    ptr
+}
+
+/// Get the length, in bytes, of an encoded mappings string returned by
+/// `builder_finish`.
+#[no_mangle]
+pub extern "C" fn encoded_mappings_len(encoded: *mut u8) -> usize {
+    let encoded = unsafe { MappingsString::from_raw(encoded) };
+    let len = unsafe { encoded.as_bytes().len() };
+    mem::forget(encoded);
+    len
+}
+
+/// Destroy an encoded mappings string returned by `builder_finish`.
+///
+/// The caller gives up ownership of the string and must not use it again.
+#[no_mangle]
+pub extern "C" fn free_encoded_mappings(encoded: *mut u8) {
+    unsafe {
+        drop(MappingsString::from_raw(encoded));
     }
 }
 
@@ -251,6 +557,7 @@ unsafe fn mappings_mut<'a>(
     mappings.as_mut().unwrap()
 }
 
+#[cfg(feature = "mapping-callback")]
 extern "C" {
     fn mapping_callback(
         // These two parameters are always valid.
@@ -275,6 +582,116 @@ extern "C" {
     );
 }
 
+/// The number of `u32`s that make up each mapping in the mapping buffer.
+pub const MAPPING_RECORD_LEN: usize = 10;
+
+static mut MAPPING_BUFFER: Vec<u32> = Vec::new();
+
+#[cfg(feature = "mapping-callback")]
+static mut MAPPING_BUFFER_ENABLED: bool = false;
+
+#[cfg(feature = "mapping-callback")]
+#[inline]
+fn mapping_buffer_enabled() -> bool {
+    unsafe { MAPPING_BUFFER_ENABLED }
+}
+
+#[cfg(not(feature = "mapping-callback"))]
+#[inline]
+fn mapping_buffer_enabled() -> bool {
+    true
+}
+
+#[inline]
+unsafe fn mapping_buffer_mut() -> &'static mut Vec<u32> {
+    &mut *ptr::addr_of_mut!(MAPPING_BUFFER)
+}
+
+/// Choose whether query results are written into the mapping buffer (`true`)
+/// or passed to `mapping_callback` (`false`, the default).
+///
+/// When built without the `mapping-callback` feature, results are always
+/// written into the mapping buffer, and this function does nothing.
+#[no_mangle]
+pub extern "C" fn set_mapping_buffer_enabled(enabled: bool) {
+    #[cfg(feature = "mapping-callback")]
+    unsafe {
+        MAPPING_BUFFER_ENABLED = enabled;
+    }
+    let _ = enabled;
+}
+
+/// Get a pointer to the mappings that resulted from the last query, when the
+/// mapping buffer is in use.
+///
+/// The pointer is invalidated by the next query.
+#[no_mangle]
+pub extern "C" fn mapping_buffer() -> *const u32 {
+    unsafe { mapping_buffer_mut().as_ptr() }
+}
+
+/// Get the number of mappings that resulted from the last query, when the
+/// mapping buffer is in use.
+#[no_mangle]
+pub extern "C" fn mapping_buffer_len() -> usize {
+    unsafe { mapping_buffer_mut().len() / MAPPING_RECORD_LEN }
+}
+
+/// Prepare to report the results of a new query.
+#[inline]
+fn begin_results() {
+    unsafe {
+        mapping_buffer_mut().clear();
+    }
+}
+
+#[cfg(feature = "mapping-callback")]
+#[inline]
+#[allow(clippy::too_many_arguments)]
+unsafe fn call_mapping_callback(
+    generated_line: u32,
+    generated_column: u32,
+    has_last_generated_column: bool,
+    last_generated_column: u32,
+    has_original: bool,
+    source: u32,
+    original_line: u32,
+    original_column: u32,
+    has_name: bool,
+    name: u32,
+) {
+    mapping_callback(
+        generated_line,
+        generated_column,
+        has_last_generated_column,
+        last_generated_column,
+        has_original,
+        source,
+        original_line,
+        original_column,
+        has_name,
+        name,
+    );
+}
+
+#[cfg(not(feature = "mapping-callback"))]
+#[inline]
+#[allow(clippy::too_many_arguments)]
+unsafe fn call_mapping_callback(
+    _generated_line: u32,
+    _generated_column: u32,
+    _has_last_generated_column: bool,
+    _last_generated_column: u32,
+    _has_original: bool,
+    _source: u32,
+    _original_line: u32,
+    _original_column: u32,
+    _has_name: bool,
+    _name: u32,
+) {
+    unreachable!("results are always buffered without the `mapping-callback` feature")
+}
+
 #[inline]
 unsafe fn invoke_mapping_callback(mapping: &Mapping) {
     let generated_line = mapping.generated_line;
@@ -307,7 +724,23 @@ unsafe fn invoke_mapping_callback(mapping: &Mapping) {
             (false, 0, 0, 0, false, 0)
         };
 
-    mapping_callback(
+    if mapping_buffer_enabled() {
+        mapping_buffer_mut().extend_from_slice(&[
+            generated_line,
+            generated_column,
+            has_last_generated_column as u32,
+            last_generated_column,
+            has_original as u32,
+            source,
+            original_line,
+            original_column,
+            has_name as u32,
+            name,
+        ]);
+        return;
+    }
+
+    call_mapping_callback(
         generated_line,
         generated_column,
         has_last_generated_column,
@@ -325,6 +758,7 @@ unsafe fn invoke_mapping_callback(mapping: &Mapping) {
 /// structure, in order of generated location.
 #[no_mangle]
 pub extern "C" fn by_generated_location(mappings: *mut Mappings<Observer>) {
+    begin_results();
     let this_scope = ();
     let mappings = unsafe { mappings_mut(&this_scope, mappings) };
 
@@ -345,11 +779,48 @@ pub extern "C" fn compute_column_spans(mappings: *mut Mappings<Observer>) {
     mappings.compute_column_spans();
 }
 
+/// Release any spare capacity held by the given mappings.
+#[no_mangle]
+pub extern "C" fn shrink_mappings_to_fit(mappings: *mut Mappings<Observer>) {
+    let this_scope = ();
+    let mappings = unsafe { mappings_mut(&this_scope, mappings) };
+
+    mappings.shrink_to_fit();
+}
+
+/// Get the number of mappings in the given mappings.
+#[no_mangle]
+pub extern "C" fn mappings_len(mappings: *mut Mappings<Observer>) -> usize {
+    let this_scope = ();
+    let mappings = unsafe { mappings_mut(&this_scope, mappings) };
+
+    mappings.len()
+}
+
+/// Get the number of generated lines known to the given mappings.
+#[no_mangle]
+pub extern "C" fn mappings_generated_line_count(mappings: *mut Mappings<Observer>) -> u32 {
+    let this_scope = ();
+    let mappings = unsafe { mappings_mut(&this_scope, mappings) };
+
+    mappings.generated_line_count()
+}
+
+/// Does any of the given mappings have an associated name?
+#[no_mangle]
+pub extern "C" fn mappings_have_names(mappings: *mut Mappings<Observer>) -> bool {
+    let this_scope = ();
+    let mappings = unsafe { mappings_mut(&this_scope, mappings) };
+
+    mappings.has_names()
+}
+
 /// Invoke the `mapping_callback` on each mapping in the given `Mappings`
 /// structure that has original location information, in order of original
 /// location.
 #[no_mangle]
 pub extern "C" fn by_original_location(mappings: *mut Mappings<Observer>) {
+    begin_results();
     let this_scope = ();
     let mappings = unsafe { mappings_mut(&this_scope, mappings) };
 
@@ -358,18 +829,46 @@ pub extern "C" fn by_original_location(mappings: *mut Mappings<Observer>) {
     });
 }
 
+/// Choose how mappings at the same original location are ordered:
+/// `OriginalTieBreak::NameThenGenerated = 1` (the default) or
+/// `OriginalTieBreak::Generated = 2`.
+#[no_mangle]
+pub extern "C" fn set_original_tie_break(mappings: *mut Mappings<Observer>, tie_break: u32) {
+    let this_scope = ();
+    let mappings = unsafe { mappings_mut(&this_scope, mappings) };
+
+    let tie_break = match tie_break {
+        1 => OriginalTieBreak::NameThenGenerated,
+        2 => OriginalTieBreak::Generated,
+        otherwise => if cfg!(debug_assertions) {
+            panic!(
+                "Invalid `OriginalTieBreak = {}`; must be \
+                 `OriginalTieBreak::NameThenGenerated = {}` or `OriginalTieBreak::Generated = {}`",
+                otherwise,
+                OriginalTieBreak::NameThenGenerated as u32,
+                OriginalTieBreak::Generated as u32,
+            )
+        } else {
+            process::abort()
+        },
+    };
+    mappings.set_original_tie_break(tie_break);
+}
+
 #[inline]
 fn u32_to_bias(bias: u32) -> Bias {
     match bias {
         1 => Bias::GreatestLowerBound,
         2 => Bias::LeastUpperBound,
+        3 => Bias::ClosestMatch,
         otherwise => if cfg!(debug_assertions) {
             panic!(
-                "Invalid `Bias = {}`; must be `Bias::GreatestLowerBound = {}` or \
-                 `Bias::LeastUpperBound = {}`",
+                "Invalid `Bias = {}`; must be `Bias::GreatestLowerBound = {}`, \
+                 `Bias::LeastUpperBound = {}`, or `Bias::ClosestMatch = {}`",
                 otherwise,
                 Bias::GreatestLowerBound as u32,
                 Bias::LeastUpperBound as u32,
+                Bias::ClosestMatch as u32,
             )
         } else {
             process::abort()
@@ -388,6 +887,7 @@ pub extern "C" fn original_location_for(
     generated_column: u32,
     bias: u32,
 ) {
+    begin_results();
     let this_scope = ();
     let mappings = unsafe { mappings_mut(&this_scope, mappings) };
     let bias = u32_to_bias(bias);
@@ -399,6 +899,30 @@ pub extern "C" fn original_location_for(
     }
 }
 
+/// Find the closest mapping with an associated name, starting at the given
+/// generated location and searching in the direction given by `bias`.
+///
+/// If a mapping is found, the `mapping_callback` is invoked with it
+/// once. Otherwise, the `mapping_callback` is not invoked at all.
+#[no_mangle]
+pub extern "C" fn nearest_named_mapping(
+    mappings: *mut Mappings<Observer>,
+    generated_line: u32,
+    generated_column: u32,
+    bias: u32,
+) {
+    begin_results();
+    let this_scope = ();
+    let mappings = unsafe { mappings_mut(&this_scope, mappings) };
+    let bias = u32_to_bias(bias);
+
+    if let Some(m) = mappings.nearest_named_mapping(generated_line, generated_column, bias) {
+        unsafe {
+            invoke_mapping_callback(m);
+        }
+    }
+}
+
 /// Find the mapping for the given original location, if any exists.
 ///
 /// If a mapping is found, the `mapping_callback` is invoked with it
@@ -411,6 +935,7 @@ pub extern "C" fn generated_location_for(
     original_column: u32,
     bias: u32,
 ) {
+    begin_results();
     let this_scope = ();
     let mappings = unsafe { mappings_mut(&this_scope, mappings) };
     let bias = u32_to_bias(bias);
@@ -439,6 +964,7 @@ pub extern "C" fn all_generated_locations_for(
     has_original_column: bool,
     original_column: u32,
 ) {
+    begin_results();
     let this_scope = ();
     let mappings = unsafe { mappings_mut(&this_scope, mappings) };
 
@@ -454,3 +980,37 @@ pub extern "C" fn all_generated_locations_for(
         }
     }
 }
+
+/// Like `all_generated_locations_for`, but slide to the nearest original
+/// location with any mappings in the direction given by `bias`.
+///
+/// If `has_original_column` is `false`, the nearest original line with any
+/// mappings is found and the `mapping_callback` is invoked on all of its
+/// mappings. Otherwise, the nearest original column on the given original line
+/// is found and the `mapping_callback` is invoked on all of its mappings.
+#[no_mangle]
+pub extern "C" fn all_generated_locations_for_with_bias(
+    mappings: *mut Mappings<Observer>,
+    source: u32,
+    original_line: u32,
+    has_original_column: bool,
+    original_column: u32,
+    bias: u32,
+) {
+    begin_results();
+    let this_scope = ();
+    let mappings = unsafe { mappings_mut(&this_scope, mappings) };
+    let bias = u32_to_bias(bias);
+
+    let original_column = if has_original_column {
+        Some(original_column)
+    } else {
+        None
+    };
+
+    for m in mappings.all_generated_locations_for_with_bias(source, original_line, original_column, bias) {
+        unsafe {
+            invoke_mapping_callback(m);
+        }
+    }
+}
@@ -0,0 +1,64 @@
+//! Building a `Mappings`' indexes on a background thread.
+//!
+//! Computing column spans and sorting mappings by original location happens
+//! lazily, on the first query that needs them, which can be slow for large
+//! source maps. Native consumers can instead build them ahead of time on
+//! another thread with `Mappings::build_indexes_in_background`.
+
+use super::{Mappings, Observer};
+use std::thread;
+
+/// A handle to a `Mappings` whose indexes are being built on a background
+/// thread.
+///
+/// Returned by `Mappings::build_indexes_in_background`.
+#[derive(Debug)]
+pub struct BuildingIndexes<O = ()>
+where
+    O: Observer,
+{
+    thread: thread::JoinHandle<Mappings<O>>,
+}
+
+impl<O> BuildingIndexes<O>
+where
+    O: 'static + Observer + Send,
+    O::SortByOriginalLocation: Send,
+{
+    /// Has the background thread finished building the indexes?
+    #[inline]
+    pub fn is_finished(&self) -> bool {
+        self.thread.is_finished()
+    }
+
+    /// Wait for the background thread to finish building the indexes, and get
+    /// the mappings back.
+    ///
+    /// If building the indexes panicked, the panic is propagated.
+    pub fn join(self) -> Mappings<O> {
+        match self.thread.join() {
+            Ok(mappings) => mappings,
+            Err(panic) => ::std::panic::resume_unwind(panic),
+        }
+    }
+}
+
+impl<O> Mappings<O>
+where
+    O: 'static + Observer + Send,
+    O::SortByOriginalLocation: Send,
+{
+    /// Spawn a thread that builds these mappings' indexes, as
+    /// `Mappings::build_indexes` does, and return a handle to join it.
+    ///
+    /// To use a thread pool instead, move the mappings into a task that calls
+    /// `Mappings::build_indexes`.
+    pub fn build_indexes_in_background(mut self) -> BuildingIndexes<O> {
+        BuildingIndexes {
+            thread: thread::spawn(move || {
+                self.build_indexes();
+                self
+            }),
+        }
+    }
+}
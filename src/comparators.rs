@@ -7,7 +7,7 @@ use std::fmt;
 /// A function that can compare two `T`s.
 pub trait ComparatorFunction<T>: fmt::Debug {
     /// Compare the given values.
-    fn compare(&T, &T) -> Ordering;
+    fn compare(a: &T, b: &T) -> Ordering;
 }
 
 impl<T, F> ComparatorFunction<Option<T>> for F
@@ -20,7 +20,7 @@ where
             (&None, &None) => Ordering::Equal,
             (&Some(_), &None) => Ordering::Less,
             (&None, &Some(_)) => Ordering::Greater,
-            (&Some(ref a), &Some(ref b)) => F::compare(a, b),
+            (Some(a), Some(b)) => F::compare(a, b),
         }
     }
 }
@@ -46,6 +46,19 @@ macro_rules! compare {
     }
 }
 
+/// Sort mappings by their generated location, breaking ties by their original
+/// locations.
+#[derive(Debug)]
+pub struct ByGeneratedLocation;
+
+impl ComparatorFunction<Mapping> for ByGeneratedLocation {
+    #[inline]
+    fn compare(a: &Mapping, b: &Mapping) -> Ordering {
+        compare!(a.generated_line, b.generated_line);
+        ByGeneratedTail::compare(a, b)
+    }
+}
+
 /// Sort mappings by their generated location, but don't compare generated
 /// lines. This is useful for when we know that all mappings being sorted have
 /// the same generated line number.
@@ -119,3 +132,34 @@ impl ComparatorFunction<OriginalLocation> for ByOriginalLocationSameSource {
         a.name.cmp(&b.name)
     }
 }
+
+/// Assuming mappings are in the same original source, sort mappings by their
+/// original lines and columns, ignoring their names, breaking ties by their
+/// generated locations.
+#[derive(Debug)]
+pub struct ByOriginalLocationSameSourceIgnoringNames;
+
+impl ComparatorFunction<Mapping> for ByOriginalLocationSameSourceIgnoringNames {
+    #[inline]
+    fn compare(a: &Mapping, b: &Mapping) -> Ordering {
+        let c = ByOriginalLocationSameSourceIgnoringNames::compare(&a.original, &b.original);
+        match c {
+            Ordering::Less | Ordering::Greater => c,
+            Ordering::Equal => {
+                compare!(a.generated_line, b.generated_line);
+                compare!(a.generated_column, b.generated_column);
+                Ordering::Equal
+            }
+        }
+    }
+}
+
+impl ComparatorFunction<OriginalLocation> for ByOriginalLocationSameSourceIgnoringNames {
+    #[inline]
+    fn compare(a: &OriginalLocation, b: &OriginalLocation) -> Ordering {
+        debug_assert_eq!(a.source, b.source);
+        compare!(a.original_line, b.original_line);
+        compare!(a.original_column, b.original_column);
+        Ordering::Equal
+    }
+}
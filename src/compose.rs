@@ -0,0 +1,111 @@
+//! Composing two source maps into one.
+//!
+//! When code goes through several passes, such as TypeScript to ES5 to
+//! minified, each pass produces its own source map. Consumers want a single
+//! map from the final generated code all the way back to the original sources,
+//! which `Mappings::compose` produces.
+
+use super::{comparators, sort, Bias, Mapping, Mappings, Observer, OriginalLocation};
+
+/// Tables that translate the source and name indices of the two maps being
+/// composed into those of the composed map.
+///
+/// Each table is indexed by a source or name index of one of the two maps, and
+/// gives the corresponding index in the composed map's `"sources"` or
+/// `"names"`. Indices that are missing from a table are treated as unknown.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ComposeRemapping<'a> {
+    /// The index of the outer map's source whose generated code the inner map
+    /// describes.
+    pub source: u32,
+
+    /// The composed indices of the outer map's sources, for mappings into
+    /// sources other than `source`, which are kept as they are.
+    pub outer_sources: &'a [u32],
+
+    /// The composed indices of the outer map's names.
+    pub outer_names: &'a [u32],
+
+    /// The composed indices of the inner map's sources.
+    pub inner_sources: &'a [u32],
+
+    /// The composed indices of the inner map's names.
+    pub inner_names: &'a [u32],
+}
+
+#[inline]
+fn remap(table: &[u32], index: u32) -> Option<u32> {
+    table.get(index as usize).cloned()
+}
+
+impl<O: Observer> Mappings<O> {
+    /// Compose these mappings, which map generated code C back to some code B,
+    /// with `inner` mappings that map B back to original code A, producing
+    /// mappings from C back to A.
+    ///
+    /// Each of these mappings into `remapping.source` is looked up in `inner`
+    /// at its original location, on the same generated line of B. If found,
+    /// the composed mapping takes `inner`'s original location, and its name if
+    /// it has one, or else keeps this mapping's name. If not found, the
+    /// composed mapping only has a generated location. Mappings into other
+    /// sources keep their original locations.
+    pub fn compose<O2: Observer>(&self, inner: &Mappings<O2>, remapping: &ComposeRemapping) -> Mappings<O> {
+        let mut composed: Vec<Mapping> = self.by_generated
+            .iter()
+            .map(|m| Mapping {
+                generated_line: m.generated_line,
+                generated_column: m.generated_column,
+                last_generated_column: None,
+                original: m.original
+                    .as_ref()
+                    .and_then(|original| compose_original(original, inner, remapping)),
+            })
+            .collect();
+
+        // The generated locations are unchanged, but mappings at the same
+        // generated location may now be ordered differently.
+        {
+            let _observer = O::SortByGeneratedLocation::default();
+            sort::by_comparator::<comparators::ByGeneratedLocation, _>(&mut composed);
+        }
+
+        Mappings::from_sorted(composed)
+    }
+}
+
+fn compose_original<O2: Observer>(
+    outer: &OriginalLocation,
+    inner: &Mappings<O2>,
+    remapping: &ComposeRemapping,
+) -> Option<OriginalLocation> {
+    let outer_name = outer.name.and_then(|name| remap(remapping.outer_names, name));
+
+    if outer.source != remapping.source {
+        return Some(OriginalLocation {
+            source: remap(remapping.outer_sources, outer.source)?,
+            original_line: outer.original_line,
+            original_column: outer.original_column,
+            name: outer_name,
+        });
+    }
+
+    let found = inner.original_location_for(
+        outer.original_line,
+        outer.original_column,
+        Bias::GreatestLowerBound,
+    )?;
+    if found.generated_line != outer.original_line {
+        return None;
+    }
+
+    let original = found.original.as_ref()?;
+    Some(OriginalLocation {
+        source: remap(remapping.inner_sources, original.source)?,
+        original_line: original.original_line,
+        original_column: original.original_column,
+        name: original
+            .name
+            .and_then(|name| remap(remapping.inner_names, name))
+            .or(outer_name),
+    })
+}
@@ -0,0 +1,389 @@
+//! Measuring how much of the generated code is covered by mappings.
+//!
+//! Source map quality is often judged by how much of the generated code can be
+//! attributed back to some original source. These metrics answer that question
+//! directly from a parsed `Mappings` structure, and `Mappings::gaps` reports
+//! exactly which generated code is unattributed. `Mappings::name_usage` breaks
+//! the attributed code down by name, to find which symbols dominate a bundle,
+//! and `Mappings::generated_ranges_for_original_range` translates a range of
+//! original source into the generated code it became. `Mappings::coverage_stats`
+//! summarizes how each source contributes to the generated file.
+
+use super::{original_location, ByOriginalSource, ByOriginalSourceIter, Mapping, Mappings, Observer};
+use std::cmp;
+use std::iter::Peekable;
+use std::ops::Range;
+use std::slice;
+
+/// How many of the generated file's columns are covered by mappings with
+/// original location information.
+///
+/// Returned by `Mappings::column_coverage`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ColumnCoverage {
+    /// The total number of columns in the generated file.
+    pub total_columns: u64,
+
+    /// The number of generated columns covered by a mapping with original
+    /// location information.
+    pub covered_columns: u64,
+
+    /// The number of generated columns covered by a mapping into each source,
+    /// indexed by source.
+    pub covered_columns_by_source: Vec<u64>,
+}
+
+impl ColumnCoverage {
+    /// The fraction of generated columns that are covered by a mapping with
+    /// original location information, or `1.0` if the generated file is empty.
+    pub fn fraction(&self) -> f64 {
+        if self.total_columns == 0 {
+            1.0
+        } else {
+            self.covered_columns as f64 / self.total_columns as f64
+        }
+    }
+
+    /// The fraction of generated columns that are covered by a mapping into the
+    /// given source, or `0.0` if the generated file is empty.
+    pub fn source_fraction(&self, source: u32) -> f64 {
+        let covered = self.covered_columns_by_source
+            .get(source as usize)
+            .cloned()
+            .unwrap_or(0);
+        if self.total_columns == 0 {
+            0.0
+        } else {
+            covered as f64 / self.total_columns as f64
+        }
+    }
+}
+
+/// A range of generated columns within a single generated line.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct GeneratedRange {
+    /// The generated line.
+    pub generated_line: u32,
+
+    /// The first generated column in the range.
+    pub generated_column: u32,
+
+    /// The generated column at which the range ends (exclusive), or `None` if
+    /// it spans until the end of the generated line.
+    pub last_generated_column: Option<u32>,
+}
+
+/// How a single name is used by the mappings.
+///
+/// Returned by `Mappings::name_usage`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct NameUsage {
+    /// The number of mappings that reference this name.
+    pub mappings: usize,
+
+    /// The generated ranges spanned by the mappings that reference this name,
+    /// ordered by generated location.
+    pub generated_ranges: Vec<GeneratedRange>,
+}
+
+/// Per-source statistics about the mappings.
+///
+/// Returned by `Mappings::coverage_stats`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CoverageStats {
+    /// The number of generated lines known to the mappings: one more than the
+    /// last generated line with any mapping, saturating at `u32::MAX`.
+    pub total_generated_lines: u32,
+
+    /// The statistics for each source, indexed by source.
+    pub sources: Vec<SourceCoverage>,
+}
+
+impl CoverageStats {
+    /// The fraction of generated lines that have at least one mapping into the
+    /// given source, or `0.0` if there are no generated lines.
+    pub fn generated_line_fraction(&self, source: u32) -> f64 {
+        let lines = self.sources
+            .get(source as usize)
+            .map_or(0, |s| s.generated_lines);
+        if self.total_generated_lines == 0 {
+            0.0
+        } else {
+            lines as f64 / self.total_generated_lines as f64
+        }
+    }
+}
+
+/// Statistics about the mappings into a single source.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SourceCoverage {
+    /// The number of mappings into this source.
+    pub mappings: usize,
+
+    /// The original lines referenced by mappings into this source, as sorted,
+    /// non-overlapping, non-adjacent ranges.
+    ///
+    /// Ranges end at `u32::MAX` at the latest, so original line `u32::MAX`
+    /// itself is left out of them.
+    pub original_lines: Vec<Range<u32>>,
+
+    /// The number of generated lines with at least one mapping into this
+    /// source.
+    pub generated_lines: u32,
+}
+
+impl<O: Observer> Mappings<O> {
+    /// Compute how many of the generated file's columns are covered by mappings
+    /// that have original location information, overall and per source.
+    ///
+    /// The `line_lengths` are the lengths, in columns, of each of the generated
+    /// file's lines. Each mapping covers the columns from its generated column
+    /// up to the next mapping on the same line, or to the end of the line if it
+    /// is the last. Mappings that lie outside of the given line lengths do not
+    /// cover anything.
+    pub fn column_coverage(&self, line_lengths: &[u32]) -> ColumnCoverage {
+        let mut coverage = ColumnCoverage {
+            total_columns: line_lengths.iter().map(|&len| len as u64).sum(),
+            ..Default::default()
+        };
+
+        let mut by_generated = self.by_generated.iter().peekable();
+        while let Some(m) = by_generated.next() {
+            let line_length = match line_lengths.get(m.generated_line as usize) {
+                Some(&len) => len,
+                None => break,
+            };
+
+            let source = match m.original {
+                Some(ref original) => original.source as usize,
+                None => continue,
+            };
+
+            let end = match by_generated.peek() {
+                Some(next) if next.generated_line == m.generated_line => {
+                    cmp::min(next.generated_column, line_length)
+                }
+                _ => line_length,
+            };
+            let covered = end.saturating_sub(m.generated_column) as u64;
+
+            coverage.covered_columns += covered;
+            if coverage.covered_columns_by_source.len() <= source {
+                coverage.covered_columns_by_source.resize(source + 1, 0);
+            }
+            coverage.covered_columns_by_source[source] += covered;
+        }
+
+        coverage
+    }
+
+    /// Compute statistics about the mappings into each source, indexed by
+    /// source.
+    pub fn coverage_stats(&self) -> CoverageStats {
+        let mut sources: Vec<SourceCoverage> = vec![];
+        let mut original_lines: Vec<Vec<u32>> = vec![];
+        let mut last_generated_lines: Vec<Option<u32>> = vec![];
+
+        for m in &self.by_generated {
+            let original = match m.original {
+                Some(ref original) => original,
+                None => continue,
+            };
+
+            let source = original.source as usize;
+            if sources.len() <= source {
+                sources.resize(source + 1, Default::default());
+                original_lines.resize(source + 1, vec![]);
+                last_generated_lines.resize(source + 1, None);
+            }
+
+            sources[source].mappings += 1;
+            original_lines[source].push(original.original_line);
+            if last_generated_lines[source] != Some(m.generated_line) {
+                last_generated_lines[source] = Some(m.generated_line);
+                sources[source].generated_lines += 1;
+            }
+        }
+
+        for (stats, mut lines) in sources.iter_mut().zip(original_lines) {
+            lines.sort_unstable();
+            for line in lines {
+                match stats.original_lines.last_mut() {
+                    Some(ref mut range) if line <= range.end => {
+                        range.end = cmp::max(range.end, line.saturating_add(1));
+                    }
+                    _ => stats.original_lines.push(line..line.saturating_add(1)),
+                }
+            }
+        }
+
+        CoverageStats {
+            total_generated_lines: self.by_generated
+                .last()
+                .map_or(0, |m| m.generated_line.saturating_add(1)),
+            sources,
+        }
+    }
+
+    /// Compute how each name is used by the mappings, indexed by name.
+    ///
+    /// This computes column spans, if they haven't been computed already.
+    pub fn name_usage(&mut self) -> Vec<NameUsage> {
+        self.compute_column_spans();
+
+        let mut usage: Vec<NameUsage> = vec![];
+        for m in &self.by_generated {
+            let name = match m.original.as_ref().and_then(|o| o.name) {
+                Some(name) => name as usize,
+                None => continue,
+            };
+
+            if usage.len() <= name {
+                usage.resize(name + 1, Default::default());
+            }
+            usage[name].mappings += 1;
+            usage[name].generated_ranges.push(GeneratedRange {
+                generated_line: m.generated_line,
+                generated_column: m.generated_column,
+                last_generated_column: m.last_generated_column,
+            });
+        }
+
+        usage
+    }
+
+    /// Iterate over the generated ranges spanned by the mappings into the given
+    /// source whose original locations are within the `[original_start,
+    /// original_end)` range, ordered by original location.
+    ///
+    /// Both ends of the range are `(original_line, original_column)` pairs.
+    /// This computes column spans, if they haven't been computed already.
+    pub fn generated_ranges_for_original_range(
+        &mut self,
+        source: u32,
+        original_start: (u32, u32),
+        original_end: (u32, u32),
+    ) -> GeneratedRangesForOriginalRange<'_> {
+        ranges_within(self.by_original_source(source), original_start, original_end)
+    }
+
+    /// Iterate over the generated ranges that are not covered by any mapping's
+    /// generated column span, in order.
+    ///
+    /// This computes column spans, if they haven't been computed already.
+    ///
+    /// Generated lines that come after the last mapping are unknown to the
+    /// mappings, and so are not reported.
+    pub fn gaps(&mut self) -> Gaps<'_> {
+        self.compute_column_spans();
+        Gaps {
+            mappings: self.by_generated.iter().peekable(),
+            generated_line: 0,
+            covered_until: if self.by_generated.is_empty() {
+                None
+            } else {
+                Some(0)
+            },
+        }
+    }
+}
+
+/// Get the generated ranges of a source's mappings whose original locations
+/// are within the `[original_start, original_end)` range.
+pub(crate) fn ranges_within(
+    by_original: ByOriginalSource<'_>,
+    original_start: (u32, u32),
+    original_end: (u32, u32),
+) -> GeneratedRangesForOriginalRange<'_> {
+    let start = by_original.partition_point(|m| original_location(m) < original_start);
+    let end = start + by_original
+        .slice(start..by_original.len())
+        .partition_point(|m| original_location(m) < original_end);
+
+    GeneratedRangesForOriginalRange {
+        mappings: by_original.slice(start..end).iter(),
+    }
+}
+
+/// An iterator returned by `Mappings::generated_ranges_for_original_range`.
+#[derive(Debug)]
+pub struct GeneratedRangesForOriginalRange<'a> {
+    mappings: ByOriginalSourceIter<'a>,
+}
+
+impl<'a> Iterator for GeneratedRangesForOriginalRange<'a> {
+    type Item = GeneratedRange;
+
+    #[inline]
+    fn next(&mut self) -> Option<GeneratedRange> {
+        self.mappings.next().map(|m| GeneratedRange {
+            generated_line: m.generated_line,
+            generated_column: m.generated_column,
+            last_generated_column: m.last_generated_column,
+        })
+    }
+}
+
+/// An iterator returned by `Mappings::gaps`.
+#[derive(Debug)]
+pub struct Gaps<'a> {
+    mappings: Peekable<slice::Iter<'a, Mapping>>,
+    generated_line: u32,
+
+    // The column on `generated_line` from which nothing is known to be covered
+    // yet, or `None` if the rest of the line is covered.
+    covered_until: Option<u32>,
+}
+
+impl<'a> Iterator for Gaps<'a> {
+    type Item = GeneratedRange;
+
+    fn next(&mut self) -> Option<GeneratedRange> {
+        loop {
+            let m = match self.mappings.peek() {
+                Some(m) => *m,
+                None => {
+                    // A mapping with an explicit end column leaves the rest of
+                    // the last line uncovered.
+                    let column = self.covered_until.take()?;
+                    return Some(GeneratedRange {
+                        generated_line: self.generated_line,
+                        generated_column: column,
+                        last_generated_column: None,
+                    });
+                }
+            };
+
+            if m.generated_line > self.generated_line {
+                let gap = self.covered_until.map(|column| GeneratedRange {
+                    generated_line: self.generated_line,
+                    generated_column: column,
+                    last_generated_column: None,
+                });
+                self.generated_line += 1;
+                self.covered_until = Some(0);
+                match gap {
+                    Some(gap) => return Some(gap),
+                    None => continue,
+                }
+            }
+
+            if let Some(column) = self.covered_until {
+                if m.generated_column > column {
+                    self.covered_until = Some(m.generated_column);
+                    return Some(GeneratedRange {
+                        generated_line: self.generated_line,
+                        generated_column: column,
+                        last_generated_column: Some(m.generated_column),
+                    });
+                }
+            }
+
+            self.mappings.next();
+            self.covered_until = match (self.covered_until, m.last_generated_column) {
+                (Some(column), Some(last)) => Some(cmp::max(column, last)),
+                _ => None,
+            };
+        }
+    }
+}
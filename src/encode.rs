@@ -0,0 +1,192 @@
+//! Encoding mappings back into a source map's `"mappings"` string.
+//!
+//! This is the mirror image of parsing: each mapping becomes a segment of
+//! Base64 VLQ fields that are relative to the previous segment's, with `,`
+//! separating segments and `;` separating generated lines.
+//!
+//! `Mappings::serialize` and `Mappings::write_to` encode a whole parsed
+//! `Mappings` structure, while a `MappingsEncoder` streams mappings out one at
+//! a time, without ever holding more than a single generated line's worth of
+//! them in memory.
+
+use super::{comparators, Mapping, Mappings, Observer};
+use comparators::ComparatorFunction;
+use std::fmt;
+use std::io;
+use std::mem;
+use std::str;
+use vlq;
+
+/// A streaming encoder that writes mappings, one at a time, as a source map's
+/// `"mappings"` string.
+///
+/// Mappings must be added in generated line order, but may be in any order
+/// within a generated line: each line's mappings are buffered, and then sorted
+/// and written once a mapping on a later line is added or the encoder is
+/// finished.
+#[derive(Debug)]
+pub struct MappingsEncoder<W> {
+    output: W,
+
+    // The mappings added for the current generated line, not yet written.
+    line_buffer: Vec<Mapping>,
+
+    // The generated line of the last mapping written, and whether any mapping
+    // has been written on it yet.
+    generated_line: u32,
+    line_has_segments: bool,
+
+    // The previous values that each segment's fields are relative to.
+    generated_column: u32,
+    source: u32,
+    original_line: u32,
+    original_column: u32,
+    name: u32,
+}
+
+impl<W: io::Write> MappingsEncoder<W> {
+    /// Construct a new encoder that writes to the given output.
+    pub fn new(output: W) -> MappingsEncoder<W> {
+        MappingsEncoder {
+            output,
+            line_buffer: vec![],
+            generated_line: 0,
+            line_has_segments: false,
+            generated_column: 0,
+            source: 0,
+            original_line: 0,
+            original_column: 0,
+            name: 0,
+        }
+    }
+
+    /// Add the next mapping to encode.
+    ///
+    /// Returns an error of kind `InvalidInput` if the mapping is on an earlier
+    /// generated line than a previously added mapping, or any error from
+    /// writing the previous generated line's mappings.
+    pub fn add_mapping(&mut self, mapping: &Mapping) -> io::Result<()> {
+        let buffered_line = self.line_buffer.first().map(|m| m.generated_line);
+        match buffered_line {
+            Some(line) if mapping.generated_line < line => {
+                return Err(out_of_order());
+            }
+            Some(line) if mapping.generated_line > line => {
+                self.flush_line()?;
+            }
+            None if mapping.generated_line < self.generated_line => {
+                return Err(out_of_order());
+            }
+            _ => {}
+        }
+
+        self.line_buffer.push(mapping.clone());
+        Ok(())
+    }
+
+    /// Write any buffered mappings and return the output.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.flush_line()?;
+        Ok(self.output)
+    }
+
+    fn flush_line(&mut self) -> io::Result<()> {
+        let mut line = mem::take(&mut self.line_buffer);
+        line.sort_by(comparators::ByGeneratedTail::compare);
+        for mapping in &line {
+            self.write_segment(mapping)?;
+        }
+
+        // Reuse the allocation for the next line.
+        line.clear();
+        self.line_buffer = line;
+        Ok(())
+    }
+
+    /// Write the given mapping's segment, which must not come before the
+    /// previously written mapping in generated order.
+    fn write_segment(&mut self, mapping: &Mapping) -> io::Result<()> {
+        debug_assert!(mapping.generated_line >= self.generated_line);
+
+        while self.generated_line < mapping.generated_line {
+            self.output.write_all(b";")?;
+            self.generated_line += 1;
+            self.generated_column = 0;
+            self.line_has_segments = false;
+        }
+
+        if self.line_has_segments {
+            self.output.write_all(b",")?;
+        }
+        self.line_has_segments = true;
+
+        write_relative_vlq(&mut self.output, &mut self.generated_column, mapping.generated_column)?;
+
+        if let Some(ref original) = mapping.original {
+            write_relative_vlq(&mut self.output, &mut self.source, original.source)?;
+            write_relative_vlq(&mut self.output, &mut self.original_line, original.original_line)?;
+            write_relative_vlq(&mut self.output, &mut self.original_column, original.original_column)?;
+            if let Some(name) = original.name {
+                write_relative_vlq(&mut self.output, &mut self.name, name)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn out_of_order() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "mappings must be added in generated line order",
+    )
+}
+
+#[inline]
+fn write_relative_vlq<W: io::Write>(output: &mut W, previous: &mut u32, value: u32) -> io::Result<()> {
+    vlq::encode(value as i64 - *previous as i64, output)?;
+    *previous = value;
+    Ok(())
+}
+
+impl<O: Observer> Mappings<O> {
+    /// Encode these mappings into a source map's `"mappings"` string, writing
+    /// it to the given output.
+    pub fn write_to<W: io::Write>(&self, output: W) -> io::Result<()> {
+        let mut encoder = MappingsEncoder::new(output);
+        for mapping in &self.by_generated {
+            encoder.write_segment(mapping)?;
+        }
+        Ok(())
+    }
+
+    /// Encode these mappings into a source map's `"mappings"` string.
+    ///
+    /// This is the same as formatting them with `Display`.
+    pub fn serialize(&self) -> String {
+        self.to_string()
+    }
+}
+
+/// Formats the mappings as an encoded `"mappings"` string.
+impl<O: Observer> fmt::Display for Mappings<O> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write_to(FormatterWriter(f)).map_err(|_| fmt::Error)
+    }
+}
+
+/// Adapts a `fmt::Formatter` into an `io::Write` for the encoder, which only
+/// ever writes ASCII.
+struct FormatterWriter<'a, 'b: 'a>(&'a mut fmt::Formatter<'b>);
+
+impl<'a, 'b> io::Write for FormatterWriter<'a, 'b> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let s = str::from_utf8(buf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        self.0.write_str(s).map_err(io::Error::other)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,165 @@
+//! Querying mappings from many threads at once.
+//!
+//! `Mappings` builds its indexes lazily, so its queries by original location
+//! take `&mut self`. `Mappings::freeze` builds every index up front, and the
+//! resulting `FrozenMappings` answers all the same queries through `&self`, so
+//! that it can be shared between threads, for example behind an `Arc`.
+
+use super::{all_generated_locations_in, unwrap, AllGeneratedLocationsFor, Bias, ByOriginalSource,
+            GeneratedLocationsForName, Mapping, Mappings, Observer, SortedByOriginalLocation};
+use coverage::{self, GeneratedRange, GeneratedRangesForOriginalRange};
+use position::OriginalPosition;
+use std::fmt;
+use std::ops;
+
+/// A `Mappings` with its column spans computed and every index built, which
+/// can no longer be modified.
+///
+/// Returned by `Mappings::freeze`. It dereferences to the frozen `Mappings`,
+/// for queries that already take `&self`.
+pub struct FrozenMappings<O = ()>
+where
+    O: Observer,
+{
+    mappings: Mappings<O>,
+}
+
+impl<O> fmt::Debug for FrozenMappings<O>
+where
+    O: Observer,
+    Mappings<O>: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FrozenMappings")
+            .field("mappings", &self.mappings)
+            .finish()
+    }
+}
+
+impl<O: Observer> Mappings<O> {
+    /// Compute column spans, build the fully sorted index of mappings by
+    /// original location and the index of mappings by name, and freeze these
+    /// mappings so that every query takes `&self`.
+    pub fn freeze(mut self) -> FrozenMappings<O> {
+        self.build_indexes();
+        self.build_name_index();
+        FrozenMappings { mappings: self }
+    }
+}
+
+impl<O: Observer> FrozenMappings<O> {
+    /// Get the set of mappings that have original location information for the
+    /// given source and ordered by original location.
+    #[inline]
+    pub fn by_original_source(&self, source: u32) -> ByOriginalSource<'_> {
+        self.mappings.sorted_source(source)
+    }
+
+    /// Iterate over all mappings that contain original location information,
+    /// sorted by their original location information.
+    #[inline]
+    pub fn by_original_location(&self) -> SortedByOriginalLocation<'_, O::SortByOriginalLocation> {
+        unwrap(self.mappings.try_by_original_location())
+    }
+
+    /// Get the mapping closest to the given original location, if any exists,
+    /// as `Mappings::generated_location_for` does.
+    pub fn generated_location_for(
+        &self,
+        source: u32,
+        original_line: u32,
+        original_column: u32,
+        bias: Bias,
+    ) -> Option<&Mapping> {
+        let _observer = O::GeneratedLocationFor::default();
+        self.mappings
+            .sorted_generated_location_for(source, original_line, original_column, bias)
+    }
+
+    /// Get the mapping closest to the given generated location, if any exists,
+    /// along with the range of generated columns it spans, as
+    /// `Mappings::original_location_and_span_for` does.
+    pub fn original_location_and_span_for(
+        &self,
+        generated_line: u32,
+        generated_column: u32,
+        bias: Bias,
+    ) -> Option<(&Mapping, GeneratedRange)> {
+        self.mappings
+            .original_location_for(generated_line, generated_column, bias)
+            .map(|m| (m, m.generated_range()))
+    }
+
+    /// Get the mapping closest to the given original position, if any exists,
+    /// as `Mappings::generated_location_at` does.
+    #[inline]
+    pub fn generated_location_at(&self, position: OriginalPosition, bias: Bias) -> Option<&Mapping> {
+        self.generated_location_for(position.source, position.line, position.column, bias)
+    }
+
+    /// Get all mappings at the given original location, as
+    /// `Mappings::all_generated_locations_for` does.
+    pub fn all_generated_locations_for(
+        &self,
+        source: u32,
+        original_line: u32,
+        original_column: Option<u32>,
+    ) -> AllGeneratedLocationsFor<'_> {
+        self.all_generated_locations_for_with_bias(
+            source,
+            original_line,
+            original_column,
+            Bias::LeastUpperBound,
+        )
+    }
+
+    /// Get all mappings at the original location found by sliding from the
+    /// given one in the direction given by `bias`, as
+    /// `Mappings::all_generated_locations_for_with_bias` does.
+    pub fn all_generated_locations_for_with_bias(
+        &self,
+        source: u32,
+        original_line: u32,
+        original_column: Option<u32>,
+        bias: Bias,
+    ) -> AllGeneratedLocationsFor<'_> {
+        let _observer = O::AllGeneratedLocationsFor::default();
+        all_generated_locations_in(self.by_original_source(source), original_line, original_column, bias)
+    }
+
+    /// Iterate over the mappings with the given name, ordered by generated
+    /// location.
+    #[inline]
+    pub fn generated_locations_for_name(&self, name: u32) -> GeneratedLocationsForName<'_> {
+        self.mappings.indexed_generated_locations_for_name(name)
+    }
+
+    /// Iterate over the generated ranges spanned by the mappings into the given
+    /// source whose original locations are within the `[original_start,
+    /// original_end)` range, as `Mappings::generated_ranges_for_original_range`
+    /// does.
+    pub fn generated_ranges_for_original_range(
+        &self,
+        source: u32,
+        original_start: (u32, u32),
+        original_end: (u32, u32),
+    ) -> GeneratedRangesForOriginalRange<'_> {
+        coverage::ranges_within(self.by_original_source(source), original_start, original_end)
+    }
+
+    /// Unfreeze the mappings, keeping their indexes until they are next
+    /// modified.
+    #[inline]
+    pub fn into_inner(self) -> Mappings<O> {
+        self.mappings
+    }
+}
+
+impl<O: Observer> ops::Deref for FrozenMappings<O> {
+    type Target = Mappings<O>;
+
+    #[inline]
+    fn deref(&self) -> &Mappings<O> {
+        &self.mappings
+    }
+}
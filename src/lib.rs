@@ -45,29 +45,46 @@ dual licensed as above, without any additional terms or conditions.
 #![deny(missing_debug_implementations)]
 #![deny(missing_docs)]
 
-extern crate rand;
+#[cfg(feature = "rayon")]
+extern crate rayon;
 extern crate vlq;
 
+#[cfg(not(target_arch = "wasm32"))]
+pub mod background;
 pub mod comparators;
+pub mod compose;
+pub mod coverage;
+pub mod encode;
+pub mod frozen;
+mod line_index;
+#[cfg(feature = "rayon")]
+mod parallel;
+mod payload;
+pub mod position;
+pub mod query_cache;
+mod scan;
+pub mod sections;
+mod sort;
+mod string_tables;
+pub mod validation;
+mod vlq_decode;
 
 use comparators::ComparatorFunction;
+use coverage::GeneratedRange;
 use std::cmp;
 use std::marker::PhantomData;
 use std::mem;
+use std::ops;
 use std::slice;
-use std::u32;
+use std::vec;
 
-/// Errors that can occur during parsing.
-#[derive(Copy, Clone, Debug)]
+/// Errors that can occur during parsing, or while constructing mappings.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 #[repr(u32)]
 pub enum Error {
-    // NB: 0 is reserved for OK.
-    /// The mappings contained a negative line, column, source index, or name
-    /// index.
-    UnexpectedNegativeNumber = 1,
-
-    /// The mappings contained a number larger than `u32::MAX`.
-    UnexpectedlyBigNumber = 2,
+    // NB: 0 is reserved for OK, and 1 and 2 are retired: they were reported
+    // for a negative or too big number in any field, before those errors said
+    // which field it was.
 
     /// Reached EOF while in the middle of parsing a VLQ.
     VlqUnexpectedEof = 3,
@@ -78,6 +95,223 @@ pub enum Error {
     /// VLQ encountered a number that, when decoded, would not fit in
     /// an i64.
     VlqOverflow = 5,
+
+    /// A mapping's `last_generated_column` was before its `generated_column`.
+    InvalidColumnSpan = 6,
+
+    /// A segment had a number of fields other than the 1, 4, or 5 that the
+    /// source map specification allows. Only reported by strict parsing.
+    InvalidSegmentLength = 7,
+
+    /// The mappings contained more mappings than the parse options' limit.
+    TooManyMappings = 8,
+
+    /// The mappings contained a negative generated column.
+    UnexpectedNegativeGeneratedColumn = 9,
+
+    /// The mappings contained a negative source index.
+    UnexpectedNegativeSource = 10,
+
+    /// The mappings contained a negative original line.
+    UnexpectedNegativeOriginalLine = 11,
+
+    /// The mappings contained a negative original column.
+    UnexpectedNegativeOriginalColumn = 12,
+
+    /// The mappings contained a negative name index.
+    UnexpectedNegativeName = 13,
+
+    /// The mappings contained a generated column larger than `u32::MAX`.
+    UnexpectedlyBigGeneratedColumn = 14,
+
+    /// The mappings contained a source index larger than `u32::MAX`.
+    UnexpectedlyBigSource = 15,
+
+    /// The mappings contained an original line larger than `u32::MAX`.
+    UnexpectedlyBigOriginalLine = 16,
+
+    /// The mappings contained an original column larger than `u32::MAX`.
+    UnexpectedlyBigOriginalColumn = 17,
+
+    /// The mappings contained a name index larger than `u32::MAX`.
+    UnexpectedlyBigName = 18,
+
+    /// The mappings contained a generated line larger than `u32::MAX`.
+    UnexpectedlyBigGeneratedLine = 19,
+}
+
+/// A field of a segment within a `"mappings"` string.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[repr(u32)]
+pub enum SegmentField {
+    /// The generated column, which every segment has.
+    GeneratedColumn = 1,
+
+    /// The source index.
+    Source = 2,
+
+    /// The original line.
+    OriginalLine = 3,
+
+    /// The original column.
+    OriginalColumn = 4,
+
+    /// The name index.
+    Name = 5,
+}
+
+impl SegmentField {
+    /// The error for when this field's running value goes negative.
+    #[inline]
+    fn negative(self) -> Error {
+        match self {
+            SegmentField::GeneratedColumn => Error::UnexpectedNegativeGeneratedColumn,
+            SegmentField::Source => Error::UnexpectedNegativeSource,
+            SegmentField::OriginalLine => Error::UnexpectedNegativeOriginalLine,
+            SegmentField::OriginalColumn => Error::UnexpectedNegativeOriginalColumn,
+            SegmentField::Name => Error::UnexpectedNegativeName,
+        }
+    }
+
+    /// The error for when this field's running value grows past `u32::MAX`.
+    #[inline]
+    fn too_big(self) -> Error {
+        match self {
+            SegmentField::GeneratedColumn => Error::UnexpectedlyBigGeneratedColumn,
+            SegmentField::Source => Error::UnexpectedlyBigSource,
+            SegmentField::OriginalLine => Error::UnexpectedlyBigOriginalLine,
+            SegmentField::OriginalColumn => Error::UnexpectedlyBigOriginalColumn,
+            SegmentField::Name => Error::UnexpectedlyBigName,
+        }
+    }
+}
+
+/// Where in a `"mappings"` string parsing failed, along with the error.
+///
+/// Returned by `MappingsParser::finish_detailed` and
+/// `MappingsParser::error_detail`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ErrorDetail {
+    /// The error that stopped parsing.
+    pub error: Error,
+
+    /// The byte offset within the `"mappings"` string at which the failing
+    /// segment begins.
+    pub offset: usize,
+
+    /// The generated line of the failing segment.
+    pub generated_line: u32,
+
+    /// The index of the failing segment, counting every segment from zero in
+    /// the order they appear, including any malformed segments that were
+    /// skipped.
+    pub segment_index: usize,
+
+    /// The field that was being decoded, or `None` if the error wasn't in
+    /// decoding any single field.
+    pub field: Option<SegmentField>,
+}
+
+impl From<ErrorDetail> for Error {
+    #[inline]
+    fn from(detail: ErrorDetail) -> Error {
+        detail.error
+    }
+}
+
+/// How lenient parsing recovers from a malformed segment.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Recovery {
+    /// Skip the rest of the malformed segment's generated line.
+    ///
+    /// Since each generated line's columns start over from zero, the generated
+    /// locations of every mapping after the skipped bytes are still exact.
+    SkipLine,
+
+    /// Skip only the malformed segment itself, up to the next `,` or `;`.
+    ///
+    /// This keeps as many mappings as possible, but the generated columns of
+    /// the following mappings on the same generated line may be off.
+    SkipSegment,
+}
+
+impl Default for Recovery {
+    #[inline]
+    fn default() -> Recovery {
+        Recovery::SkipLine
+    }
+}
+
+/// Options for `parse_mappings_with_options` and
+/// `MappingsParser::with_options`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// How to recover from malformed segments, or `None` to fail parsing
+    /// instead.
+    ///
+    /// Lenient parsing never fails. What was skipped can be inspected with
+    /// `MappingsParser::issues` before finishing, or is returned directly by
+    /// `parse_mappings_with_recovery`.
+    pub recovery: Option<Recovery>,
+
+    /// Only keep each mapping's generated location, decoding but discarding
+    /// its original location information.
+    ///
+    /// This is all that analyses of the generated code's spans need, and the
+    /// resulting mappings never have to build an index by original location.
+    pub generated_only: bool,
+
+    /// Decode but discard each mapping's name index.
+    ///
+    /// Symbolicating minified code often doesn't need names, and mappings
+    /// without them sort by original location faster.
+    pub skip_names: bool,
+
+    /// Reject segments with a number of fields other than 1, 4, or 5, with
+    /// `Error::InvalidSegmentLength`, rather than misinterpreting them.
+    pub strict: bool,
+
+    /// Trust that the segments within each generated line are already sorted
+    /// by generated column, as most toolchains emit them, and skip sorting
+    /// them.
+    ///
+    /// If they aren't actually sorted, queries will give wrong answers. Debug
+    /// builds assert that they are.
+    pub assume_sorted: bool,
+
+    /// The maximum number of mappings to parse, or `None` for no limit.
+    ///
+    /// Parsing a string with more mappings than this fails with
+    /// `Error::TooManyMappings`, even when recovering from malformed segments.
+    /// This protects against maliciously huge source maps, and also bounds the
+    /// memory allocated up front for the parsed mappings.
+    pub max_mappings: Option<usize>,
+
+    /// Record the index within the `"mappings"` string of the segment that
+    /// each mapping was parsed from, for `Mappings::segment_index`.
+    ///
+    /// Segments are counted from zero in the order they appear, including any
+    /// malformed segments that were skipped.
+    pub record_segment_indices: bool,
+
+    /// Record the range of bytes within the `"mappings"` string of the segment
+    /// that each mapping was parsed from, for `Mappings::segment_range`.
+    ///
+    /// This costs another 8 bytes per mapping.
+    pub record_segment_ranges: bool,
+}
+
+/// A malformed part of a `"mappings"` string that lenient parsing skipped.
+///
+/// Returned by `parse_mappings_lenient` and
+/// `parse_mappings_with_recovery`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseIssue {
+    /// The error that parsing the skipped bytes ran into.
+    pub error: Error,
+
+    /// The range of bytes within the `"mappings"` string that was skipped.
+    pub skipped: ops::Range<usize>,
 }
 
 impl From<vlq::Error> for Error {
@@ -93,7 +327,7 @@ impl From<vlq::Error> for Error {
 
 /// When doing fuzzy searching, whether to slide the next larger or next smaller
 /// mapping from the queried location.
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 #[repr(u32)]
 pub enum Bias {
     // XXX: make sure these values always match `mozilla/source-map`'s
@@ -103,6 +337,123 @@ pub enum Bias {
 
     /// Slide to the next larger mapping.
     LeastUpperBound = 2,
+
+    /// Slide to whichever of the next smaller and next larger mappings is
+    /// nearer, preferring the next smaller mapping when they are equally near.
+    ///
+    /// A mapping on the queried line is always nearer than one on another
+    /// line. This has no `mozilla/source-map` equivalent.
+    ClosestMatch = 3,
+}
+
+/// Of two candidate locations on either side of `target`, pick the nearer one,
+/// preferring `lower` when they are equally near.
+///
+/// Each candidate is some item along with its `(line, column)` location.
+/// Locations on the target's line are nearer than those on any other line.
+#[inline]
+fn nearer<T>(target: (u32, u32), lower: Option<(T, (u32, u32))>, upper: Option<(T, (u32, u32))>) -> Option<T> {
+    let distance = |(line, column): (u32, u32)| {
+        let lines = (line as i64 - target.0 as i64).abs();
+        let columns = if lines == 0 {
+            (column as i64 - target.1 as i64).abs()
+        } else {
+            0
+        };
+        (lines, columns)
+    };
+
+    match (lower, upper) {
+        (Some(lower), Some(upper)) => if distance(upper.1) < distance(lower.1) {
+            Some(upper.0)
+        } else {
+            Some(lower.0)
+        },
+        (lower, upper) => lower.or(upper).map(|(item, _)| item),
+    }
+}
+
+/// Is `m` an exact duplicate of `prev`, ignoring their column spans?
+///
+/// Since mappings are sorted by generated location and then by original
+/// location, exact duplicates are always adjacent.
+#[inline]
+fn is_exact_duplicate(m: &Mapping, prev: &Mapping) -> bool {
+    generated_location(m) == generated_location(prev) && m.original == prev.original
+}
+
+/// The `(line, column)` generated location of a mapping.
+#[inline]
+fn generated_location(m: &Mapping) -> (u32, u32) {
+    (m.generated_line, m.generated_column)
+}
+
+/// The `(line, column)` original location of a mapping, which must have one.
+#[inline]
+fn original_location(m: &Mapping) -> (u32, u32) {
+    let original = unwrap(m.original.as_ref());
+    (original.original_line, original.original_column)
+}
+
+/// Get all of a source's mappings at the original location found by sliding
+/// from the given one in the direction given by `bias`.
+///
+/// See `Mappings::all_generated_locations_for_with_bias`.
+fn all_generated_locations_in(
+    by_original: ByOriginalSource<'_>,
+    original_line: u32,
+    original_column: Option<u32>,
+    bias: Bias,
+) -> AllGeneratedLocationsFor<'_> {
+    // Without a column, slide to the nearest line by treating the query as
+    // being before every column of its line when sliding forwards, and after
+    // every column of its line when sliding backwards.
+    let lower = || {
+        let query = (original_line, original_column.unwrap_or(u32::MAX));
+        let idx = by_original.partition_point(|m| original_location(m) <= query);
+        idx.checked_sub(1).and_then(|idx| by_original.get(idx))
+    };
+    let upper = || {
+        let query = (original_line, original_column.unwrap_or(0));
+        let idx = by_original.partition_point(|m| original_location(m) < query);
+        by_original.get(idx)
+    };
+    let found = match bias {
+        Bias::GreatestLowerBound => lower(),
+        Bias::LeastUpperBound => upper(),
+        Bias::ClosestMatch => nearer(
+            (original_line, original_column.unwrap_or(0)),
+            lower().map(|m| (m, original_location(m))),
+            upper().map(|m| (m, original_location(m))),
+        ),
+    };
+
+    let mappings = match found.map(original_location) {
+        // Sliding with a column never leaves the given line.
+        Some((found_line, _)) if original_column.is_some() && found_line != original_line => {
+            by_original.slice(0..0)
+        }
+        Some((found_line, found_column)) => {
+            // Fuzzy line matching only happens when we don't have a column.
+            let original_column = original_column.map(|_| found_column);
+            let in_run = |m: &Mapping| {
+                let (line, column) = original_location(m);
+                line == found_line && original_column.is_none_or(|c| column == c)
+            };
+
+            let first = (found_line, original_column.unwrap_or(0));
+            let start = by_original.partition_point(|m| original_location(m) < first);
+            let end = start + by_original
+                .slice(start..by_original.len())
+                .partition_point(in_run);
+            by_original.slice(start..end)
+        }
+        None => by_original.slice(0..0),
+    };
+
+    AllGeneratedLocationsFor {
+        mappings: mappings.iter(),
+    }
 }
 
 impl Default for Bias {
@@ -112,6 +463,88 @@ impl Default for Bias {
     }
 }
 
+/// When merging two sets of mappings, how to resolve conflicts between mappings
+/// from each set that are at the same generated location.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MergePolicy {
+    /// Keep the mappings from both sets.
+    KeepBoth,
+
+    /// Keep only the mappings from the set being merged into.
+    PreferSelf,
+
+    /// Keep only the mappings from the set being merged in.
+    PreferOther,
+}
+
+impl Default for MergePolicy {
+    #[inline]
+    fn default() -> MergePolicy {
+        MergePolicy::KeepBoth
+    }
+}
+
+/// How mappings at the same original location are ordered when iterating by
+/// original location.
+///
+/// Set with `Mappings::set_original_tie_break`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[repr(u32)]
+pub enum OriginalTieBreak {
+    /// Order by name, and then by generated location. This matches
+    /// `mozilla/source-map`.
+    NameThenGenerated = 1,
+
+    /// Ignore names, and order only by generated location, so that the mapping
+    /// with the smallest generated location always comes first.
+    Generated = 2,
+}
+
+impl Default for OriginalTieBreak {
+    #[inline]
+    fn default() -> OriginalTieBreak {
+        OriginalTieBreak::NameThenGenerated
+    }
+}
+
+/// Options for `Mappings::original_location_for_with_options`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct QueryOptions {
+    /// Which way to slide when there is no mapping exactly at the queried
+    /// location.
+    pub bias: Bias,
+
+    /// Only return a mapping if the queried location falls within its
+    /// `[generated_column, last_generated_column)` span, rather than
+    /// attributing unmapped gaps to the nearest mapping.
+    ///
+    /// Column spans should have been computed with
+    /// `Mappings::compute_column_spans` first; otherwise every mapping is
+    /// considered to span to the end of its generated line.
+    pub within_span: bool,
+
+    /// Only return a mapping on the queried generated line, rather than
+    /// sliding onto a neighboring line.
+    pub same_line_only: bool,
+
+    /// Slide past mappings that have no original location information, in
+    /// the direction given by `bias`, until one that does is found.
+    pub require_original: bool,
+
+    /// Only return a mapping exactly at the queried location, without
+    /// sliding in the direction given by `bias` at all.
+    pub exact: bool,
+}
+
+/// Where a previous `Mappings::original_location_for_with_hint` query ended up,
+/// so that the next query can start searching from there.
+///
+/// A default hint starts searching from the first mapping.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct QueryHint {
+    idx: usize,
+}
+
 /// A trait for defining a set of RAII types that can observe the start and end
 /// of various operations and queries we perform in their constructors and
 /// destructors.
@@ -154,54 +587,44 @@ impl Observer for () {
     type AllGeneratedLocationsFor = ();
 }
 
+/// The positions in `by_generated` of one original source's mappings, which
+/// are sorted by original location the first time they're needed.
 #[derive(Debug)]
-enum LazilySorted<T, F, O> {
-    Sorted(Vec<T>, PhantomData<F>, PhantomData<O>),
-    Unsorted(Vec<T>),
+struct SourceBucket<O> {
+    indices: Vec<u32>,
+    sorted: bool,
+    _observer: PhantomData<O>,
 }
 
-impl<T, F, O> LazilySorted<T, F, O>
-where
-    F: comparators::ComparatorFunction<T>,
-    O: Default,
-{
-    #[inline]
-    fn sort(&mut self) -> &[T] {
-        let me = mem::replace(self, LazilySorted::Unsorted(vec![]));
-        let items = match me {
-            LazilySorted::Sorted(items, ..) => items,
-            LazilySorted::Unsorted(mut items) => {
-                let _observer = O::default();
-                items.sort_unstable_by(F::compare);
-                items
-            }
-        };
-        mem::replace(self, LazilySorted::Sorted(items, PhantomData, PhantomData));
-        unwrap(self.sorted())
-    }
-
-    #[inline]
-    fn unsorted(&mut self) -> Option<&mut Vec<T>> {
-        match *self {
-            LazilySorted::Unsorted(ref mut items) => Some(items),
-            LazilySorted::Sorted(..) => None,
-        }
-    }
-
+impl<O: Default> SourceBucket<O> {
     #[inline]
-    fn sorted(&self) -> Option<&[T]> {
-        match *self {
-            LazilySorted::Sorted(ref items, ..) => Some(items),
-            LazilySorted::Unsorted(_) => None,
+    fn new(indices: Vec<u32>) -> SourceBucket<O> {
+        SourceBucket {
+            indices,
+            sorted: false,
+            _observer: PhantomData,
         }
     }
 
     #[inline]
-    fn is_empty(&self) -> bool {
-        match *self {
-            LazilySorted::Sorted(ref items, ..) |
-            LazilySorted::Unsorted(ref items) => items.is_empty()
+    fn sort(&mut self, by_generated: &[Mapping], tie_break: OriginalTieBreak) -> &[u32] {
+        if !self.sorted {
+            let _observer = O::default();
+            let indices = &mut self.indices;
+            match tie_break {
+                OriginalTieBreak::NameThenGenerated => {
+                    sort::indices_by_comparator::<comparators::ByOriginalLocationSameSource, _>(indices, by_generated)
+                }
+                OriginalTieBreak::Generated => {
+                    sort::indices_by_comparator::<comparators::ByOriginalLocationSameSourceIgnoringNames, _>(
+                        indices,
+                        by_generated,
+                    )
+                }
+            }
+            self.sorted = true;
         }
+        &self.indices
     }
 }
 
@@ -215,14 +638,54 @@ where
 {
     by_generated: Vec<Mapping>,
     computed_column_spans: bool,
-    observer: O,
-
-    // The `by_original` field maps source index to mappings within that
-    // original source. This lets us essentially do bucket sort on a per-source
-    // basis, and also enables lazily sorting different source's mappings.
-    by_original: Option<Vec<LazilySorted<Mapping, comparators::ByOriginalLocationSameSource, O::SortByOriginalLocation>>>,
+    _observer: O,
+
+    // Speeds up searching `by_generated` by generated location, especially
+    // within generated lines that have very many mappings. Must be rebuilt, or
+    // patched up for edits within a single line, whenever mappings are added
+    // to, removed from, or moved within `by_generated`.
+    line_index: line_index::GeneratedLineIndex,
+
+    // A user-provided `Vec<T>` of per-mapping data, indexed by position in
+    // `by_generated`, and edited in step with it.
+    payload: Option<Box<dyn payload::PayloadColumn>>,
+
+    // The source map's `"sources"` and `"names"`, if they were attached.
+    string_tables: Option<string_tables::StringTables>,
+
+    // The `by_original` field maps source index to the positions in
+    // `by_generated` of the mappings within that original source. This lets us
+    // essentially do bucket sort on a per-source basis, and also enables lazily
+    // sorting different source's mappings.
+    by_original: Option<Vec<SourceBucket<O::SortByOriginalLocation>>>,
+
+    // The `by_name` field maps name index to the positions in `by_generated` of
+    // the mappings with that name. Built on demand by
+    // `generated_locations_for_name`.
+    by_name: Option<Vec<Vec<usize>>>,
+
+    // How `by_original` orders mappings at the same original location.
+    original_tie_break: OriginalTieBreak,
+
+    // The index of the segment that each mapping was parsed from, indexed by
+    // position in `by_generated` and edited in step with it, if parsing
+    // recorded them. Mappings added since are at `NO_SEGMENT_INDEX`.
+    segment_indices: Option<Vec<usize>>,
+
+    // The start and end of the segment that each mapping was parsed from,
+    // indexed by position in `by_generated` and edited in step with it, if
+    // parsing recorded them. Mappings added since are at `NO_SEGMENT_RANGE`.
+    segment_ranges: Option<Vec<(u32, u32)>>,
 }
 
+/// The recorded segment index of a mapping that was not parsed from any
+/// segment.
+const NO_SEGMENT_INDEX: usize = usize::MAX;
+
+/// The recorded segment range of a mapping that was not parsed from any
+/// segment.
+const NO_SEGMENT_RANGE: (u32, u32) = (u32::MAX, u32::MAX);
+
 #[cfg(debug_assertions)]
 fn unwrap<T>(o: Option<T>) -> T {
     o.unwrap()
@@ -240,11 +703,113 @@ fn unwrap<T>(o: Option<T>) -> T {
 
 impl<O: Observer> Mappings<O> {
     /// Get the full set of mappings, ordered by generated location.
+    ///
+    /// Mappings that compare equal keep the order in which they were given,
+    /// just as with `mozilla/source-map`.
     #[inline]
     pub fn by_generated_location(&self) -> &[Mapping] {
         &self.by_generated
     }
 
+    /// Get the number of mappings.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.by_generated.len()
+    }
+
+    /// Are there no mappings?
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.by_generated.is_empty()
+    }
+
+    /// Get the number of generated lines known to the mappings: one more than
+    /// the last generated line with any mapping, or zero if there are none.
+    #[inline]
+    pub fn generated_line_count(&self) -> u32 {
+        self.by_generated.last().map_or(0, |m| m.generated_line + 1)
+    }
+
+    /// Does any mapping have an associated name?
+    ///
+    /// This stops at the first named mapping, but may have to look at every
+    /// mapping to find that there are none.
+    pub fn has_names(&self) -> bool {
+        match self.by_name {
+            Some(ref by_name) => by_name.iter().any(|indices| !indices.is_empty()),
+            None => self.by_generated
+                .iter()
+                .any(|m| m.original.as_ref().is_some_and(|o| o.name.is_some())),
+        }
+    }
+
+    /// Get the index within the `"mappings"` string of the segment that each
+    /// mapping was parsed from, ordered as `by_generated_location` is.
+    ///
+    /// This is only available when parsing with
+    /// `ParseOptions::record_segment_indices`. The indices move along with their
+    /// mappings as mappings are inserted, removed, merged, or split off, and
+    /// mappings that were not parsed from any segment are at `usize::MAX`.
+    #[inline]
+    pub fn segment_indices(&self) -> Option<&[usize]> {
+        self.segment_indices.as_ref().map(|indices| &indices[..])
+    }
+
+    /// Get the index within the `"mappings"` string of the segment that the
+    /// given mapping was parsed from, if they were recorded and it is one of
+    /// these mappings that was parsed from a segment.
+    ///
+    /// See `segment_indices`.
+    pub fn segment_index(&self, mapping: &Mapping) -> Option<usize> {
+        let segment_indices = self.segment_indices.as_ref()?;
+        Some(segment_indices[self.mapping_id(mapping)?]).filter(|&idx| idx != NO_SEGMENT_INDEX)
+    }
+
+    /// Get the range of bytes within the `"mappings"` string of the segment
+    /// that the given mapping was parsed from, if they were recorded and it is
+    /// one of these mappings that was parsed from a segment.
+    ///
+    /// This is only available when parsing with
+    /// `ParseOptions::record_segment_ranges`. The ranges move along with their
+    /// mappings, just as `segment_indices` do.
+    pub fn segment_range(&self, mapping: &Mapping) -> Option<ops::Range<usize>> {
+        let segment_ranges = self.segment_ranges.as_ref()?;
+        let range = segment_ranges[self.mapping_id(mapping)?];
+        if range == NO_SEGMENT_RANGE {
+            return None;
+        }
+        Some(range.0 as usize..range.1 as usize)
+    }
+
+    /// Release any spare capacity held by these mappings and by the indices
+    /// that have been built over them so far.
+    ///
+    /// Parsing already allocates (nearly) exactly, but mappings that have since
+    /// been modified, for example by `retain` or `dedup_exact`, may hold on to
+    /// much more memory than they need. In `wasm32`, memory can never be
+    /// returned once grown, so long-lived mappings should be shrunk.
+    pub fn shrink_to_fit(&mut self) {
+        self.by_generated.shrink_to_fit();
+        if let Some(ref mut segment_indices) = self.segment_indices {
+            segment_indices.shrink_to_fit();
+        }
+        if let Some(ref mut segment_ranges) = self.segment_ranges {
+            segment_ranges.shrink_to_fit();
+        }
+        if let Some(ref mut buckets) = self.by_original {
+            buckets.shrink_to_fit();
+            for bucket in buckets {
+                bucket.indices.shrink_to_fit();
+            }
+        }
+        if let Some(ref mut by_name) = self.by_name {
+            by_name.shrink_to_fit();
+            for indices in by_name {
+                indices.shrink_to_fit();
+            }
+        }
+    }
+
     /// Compute the last generated column of each mapping.
     ///
     /// After this method has been called, any mappings with
@@ -266,6 +831,10 @@ impl<O: Observer> Mappings<O> {
 
         let mut by_generated = self.by_generated.iter_mut().peekable();
         while let Some(this_mapping) = by_generated.next() {
+            if this_mapping.last_generated_column.is_some() {
+                // Given explicitly to `Mappings::from_mappings`.
+                continue;
+            }
             if let Some(next_mapping) = by_generated.peek() {
                 if this_mapping.generated_line == next_mapping.generated_line {
                     this_mapping.last_generated_column = Some(next_mapping.generated_column);
@@ -277,7 +846,7 @@ impl<O: Observer> Mappings<O> {
     }
 
     #[inline]
-    fn source_buckets(&mut self) -> &mut [LazilySorted<Mapping, comparators::ByOriginalLocationSameSource, O::SortByOriginalLocation>] {
+    fn source_buckets(&mut self) -> &mut [SourceBucket<O::SortByOriginalLocation>] {
         if let Some(ref mut buckets) = self.by_original {
             return buckets;
         }
@@ -285,20 +854,33 @@ impl<O: Observer> Mappings<O> {
     }
 
     #[inline(never)]
-    fn source_buckets_slow_path(&mut self) -> &mut [LazilySorted<Mapping, comparators::ByOriginalLocationSameSource, O::SortByOriginalLocation>] {
+    fn source_buckets_slow_path(&mut self) -> &mut [SourceBucket<O::SortByOriginalLocation>] {
         debug_assert!(self.by_original.is_none());
 
         self.compute_column_spans();
 
         let _observer = O::SortByOriginalLocation::default();
 
-        let mut originals = vec![];
-        for m in self.by_generated.iter().filter(|m| m.original.is_some()) {
-            let source = unwrap(m.original.as_ref()).source as usize;
-            while originals.len() <= source {
-                originals.push(LazilySorted::Unsorted(vec![]));
+        // Count each source's mappings first, so that every bucket can be
+        // allocated exactly.
+        let mut counts: Vec<usize> = vec![];
+        for original in self.by_generated.iter().filter_map(|m| m.original.as_ref()) {
+            let source = original.source as usize;
+            if counts.len() <= source {
+                counts.resize(source + 1, 0);
+            }
+            counts[source] += 1;
+        }
+
+        let mut originals: Vec<_> = counts
+            .into_iter()
+            .map(|count| SourceBucket::new(Vec::with_capacity(count)))
+            .collect();
+        debug_assert!(self.by_generated.len() <= u32::MAX as usize);
+        for (idx, m) in self.by_generated.iter().enumerate() {
+            if let Some(ref original) = m.original {
+                originals[original.source as usize].indices.push(idx as u32);
             }
-            unwrap(originals[source].unsorted()).push(m.clone());
         }
 
         self.by_original = Some(originals);
@@ -307,22 +889,170 @@ impl<O: Observer> Mappings<O> {
 
     /// Get the set of mappings that have original location information for the
     /// given source and ordered by original location.
+    ///
+    /// The first query by original location groups every mapping by source,
+    /// but each source's mappings are only sorted the first time that source is
+    /// queried. Sessions that only ever look up a few of a bundle's sources
+    /// never pay to sort the rest.
     #[inline]
-    pub fn by_original_source(&mut self, source: u32) -> &[Mapping] {
-        if let Some(ms) = self.source_buckets().get_mut(source as usize) {
-            ms.sort()
-        } else {
-            &[]
+    pub fn by_original_source(&mut self, source: u32) -> ByOriginalSource<'_> {
+        self.source_buckets();
+        let by_generated = &self.by_generated;
+        let tie_break = self.original_tie_break;
+        let indices = match unwrap(self.by_original.as_mut()).get_mut(source as usize) {
+            Some(bucket) => bucket.sort(by_generated, tie_break),
+            None => &[],
+        };
+        ByOriginalSource {
+            by_generated,
+            indices,
         }
     }
 
     /// Iterate over all mappings that contain original location information,
     /// sorted by their original location information.
+    ///
+    /// Mappings that compare equal keep their relative order from
+    /// `by_generated_location`.
     #[inline]
-    pub fn by_original_location(&mut self) -> ByOriginalLocation<O::SortByOriginalLocation> {
+    pub fn by_original_location(&mut self) -> ByOriginalLocation<'_, O::SortByOriginalLocation> {
+        self.source_buckets();
         ByOriginalLocation {
-            buckets: self.source_buckets().iter_mut(),
+            buckets: unwrap(self.by_original.as_mut()).iter_mut(),
+            by_generated: &self.by_generated,
+            tie_break: self.original_tie_break,
+            this_bucket: [].iter(),
+        }
+    }
+
+    /// Iterate over all mappings that contain original location information,
+    /// sorted by their original location information, if the index of mappings
+    /// by original location is already built and fully sorted.
+    ///
+    /// Returns `None` otherwise. Call `ensure_original_index` to build it.
+    pub fn try_by_original_location(&self) -> Option<SortedByOriginalLocation<'_, O::SortByOriginalLocation>> {
+        let buckets = self.by_original.as_ref()?;
+        if !buckets.iter().all(|b| b.sorted) {
+            return None;
+        }
+        Some(SortedByOriginalLocation {
+            buckets: buckets.iter(),
+            by_generated: &self.by_generated,
             this_bucket: [].iter(),
+        })
+    }
+
+    /// Build the fully sorted index of mappings by original location, if it
+    /// isn't already, so that `try_by_original_location` succeeds until the
+    /// mappings are next modified.
+    pub fn ensure_original_index(&mut self) {
+        self.source_buckets();
+        for bucket in unwrap(self.by_original.as_mut()) {
+            bucket.sort(&self.by_generated, self.original_tie_break);
+        }
+    }
+
+    /// Choose how mappings at the same original location are ordered by
+    /// `by_original_location`, `by_original_source`, and the queries built on
+    /// them.
+    ///
+    /// Changing the tie break throws away the index of mappings by original
+    /// location, which is rebuilt on demand.
+    pub fn set_original_tie_break(&mut self, tie_break: OriginalTieBreak) {
+        if tie_break != self.original_tie_break {
+            self.original_tie_break = tie_break;
+            self.by_original = None;
+        }
+    }
+
+    /// Get how mappings at the same original location are ordered.
+    #[inline]
+    pub fn original_tie_break(&self) -> OriginalTieBreak {
+        self.original_tie_break
+    }
+
+    /// Eagerly compute column spans and build the fully sorted index of
+    /// mappings by original location, so that later queries never have to.
+    ///
+    /// This is useful for building the indexes ahead of time on some other
+    /// thread, for example within a thread pool, so that the first query by
+    /// original location doesn't block a latency-sensitive request.
+    pub fn build_indexes(&mut self) {
+        self.compute_column_spans();
+        self.ensure_original_index();
+    }
+
+    /// Get the mappings on the given generated line, ordered by generated
+    /// column.
+    #[inline]
+    pub fn mappings_in_generated_line(&self, generated_line: u32) -> &[Mapping] {
+        match self.line_index.line_range(generated_line) {
+            Some(range) => &self.by_generated[range],
+            None => {
+                let start = self.by_generated.partition_point(|m| m.generated_line < generated_line);
+                let end = start + self.by_generated[start..]
+                    .partition_point(|m| m.generated_line == generated_line);
+                &self.by_generated[start..end]
+            }
+        }
+    }
+
+    /// Get a compact line-only map: for each generated line, the `(generated
+    /// line, source, original line)` of its first mapping with original
+    /// location information, ordered by generated line.
+    ///
+    /// Generated lines without any such mapping are left out. This is all that
+    /// line-precision symbolication of stack traces needs.
+    pub fn line_map(&self) -> Vec<(u32, u32, u32)> {
+        let mut line_map: Vec<(u32, u32, u32)> = vec![];
+        for m in &self.by_generated {
+            if line_map.last().is_some_and(|&(line, _, _)| line == m.generated_line) {
+                continue;
+            }
+            if let Some(ref original) = m.original {
+                line_map.push((m.generated_line, original.source, original.original_line));
+            }
+        }
+        line_map
+    }
+
+    /// Get the mappings whose generated locations are within the range from
+    /// `(start_line, start_column)`, inclusive, to `(end_line, end_column)`,
+    /// exclusive, ordered by generated location.
+    ///
+    /// This is useful for rendering only the visible part of a generated file.
+    pub fn by_generated_range(
+        &self,
+        start_line: u32,
+        start_column: u32,
+        end_line: u32,
+        end_column: u32,
+    ) -> &[Mapping] {
+        let start = self.by_generated
+            .partition_point(|m| generated_location(m) < (start_line, start_column));
+        let end = start + self.by_generated[start..]
+            .partition_point(|m| generated_location(m) < (end_line, end_column));
+        &self.by_generated[start..end]
+    }
+
+    /// Binary search `by_generated` for the given generated location.
+    #[inline]
+    fn search_generated(&self, generated_line: u32, generated_column: u32) -> Result<usize, usize> {
+        let compare = |m: &Mapping| {
+            m.generated_line
+                .cmp(&generated_line)
+                .then(m.generated_column.cmp(&generated_column))
+        };
+
+        match self.line_index.search_range(generated_line, generated_column) {
+            None => self.by_generated.binary_search_by(compare),
+            Some(range) => {
+                let start = range.start;
+                match self.by_generated[range].binary_search_by(compare) {
+                    Ok(idx) => Ok(start + idx),
+                    Err(idx) => Err(start + idx),
+                }
+            }
         }
     }
 
@@ -336,23 +1066,309 @@ impl<O: Observer> Mappings<O> {
         let _observer = O::OriginalLocationFor::default();
 
         let by_generated = self.by_generated_location();
-
-        let position = by_generated.binary_search_by(|m| {
-            m.generated_line
-                .cmp(&generated_line)
-                .then(m.generated_column.cmp(&generated_column))
-        });
+        let position = self.search_generated(generated_line, generated_column);
 
         match position {
             Ok(idx) => Some(&by_generated[idx]),
-            Err(idx) => match bias {
-                Bias::LeastUpperBound => by_generated.get(idx),
-                Bias::GreatestLowerBound => if idx == 0 {
-                    None
-                } else {
-                    by_generated.get(idx - 1)
-                },
-            },
+            Err(idx) => self.slide_generated(generated_line, generated_column, idx, bias),
+        }
+    }
+
+    /// Get the mapping closest to the given generated location, if any exists,
+    /// along with the range of generated columns it spans.
+    ///
+    /// This computes column spans, if they haven't been computed already, so
+    /// the span only ends at the end of the generated line when the mapping is
+    /// the last on its line.
+    pub fn original_location_and_span_for(
+        &mut self,
+        generated_line: u32,
+        generated_column: u32,
+        bias: Bias,
+    ) -> Option<(&Mapping, GeneratedRange)> {
+        self.compute_column_spans();
+        self.original_location_for(generated_line, generated_column, bias)
+            .map(|m| (m, m.generated_range()))
+    }
+
+    /// Slide from the position in `by_generated` at which the given generated
+    /// location would be inserted to a neighboring mapping, according to the
+    /// given bias.
+    #[inline]
+    fn slide_generated(&self, generated_line: u32, generated_column: u32, idx: usize, bias: Bias) -> Option<&Mapping> {
+        let lower = || idx.checked_sub(1).map(|i| &self.by_generated[i]);
+        let upper = || self.by_generated.get(idx);
+        match bias {
+            Bias::LeastUpperBound => upper(),
+            Bias::GreatestLowerBound => lower(),
+            Bias::ClosestMatch => nearer(
+                (generated_line, generated_column),
+                lower().map(|m| (m, generated_location(m))),
+                upper().map(|m| (m, generated_location(m))),
+            ),
+        }
+    }
+
+    /// Get both the `GreatestLowerBound` and the `LeastUpperBound` mappings for
+    /// the given generated location, with a single search.
+    ///
+    /// If there is a mapping exactly at the given location, it is both.
+    pub fn surrounding_mappings(
+        &self,
+        generated_line: u32,
+        generated_column: u32,
+    ) -> (Option<&Mapping>, Option<&Mapping>) {
+        let _observer = O::OriginalLocationFor::default();
+
+        match self.search_generated(generated_line, generated_column) {
+            Ok(idx) => (Some(&self.by_generated[idx]), Some(&self.by_generated[idx])),
+            Err(idx) => (
+                idx.checked_sub(1).map(|i| &self.by_generated[i]),
+                self.by_generated.get(idx),
+            ),
+        }
+    }
+
+    /// Get the mapping closest to the given generated location, like
+    /// `original_location_for`, starting the search from where the query that
+    /// last updated `hint` ended up, and then updating `hint` for the next
+    /// query.
+    ///
+    /// When querying monotonically increasing generated locations, such as
+    /// when scanning through a file, each search only gallops forward over the
+    /// mappings between the previous query and this one. Queries before the
+    /// previous one fall back to an ordinary binary search.
+    pub fn original_location_for_with_hint(
+        &self,
+        generated_line: u32,
+        generated_column: u32,
+        bias: Bias,
+        hint: &mut QueryHint,
+    ) -> Option<&Mapping> {
+        let _observer = O::OriginalLocationFor::default();
+
+        let location = (generated_line, generated_column);
+        let before = |m: &Mapping| generated_location(m) < location;
+
+        let start = cmp::min(hint.idx, self.by_generated.len());
+        let idx = if start > 0 && !before(&self.by_generated[start - 1]) {
+            self.by_generated[..start].partition_point(before)
+        } else {
+            // Gallop forward to bound the search, then binary search within
+            // the bound.
+            let mut low = start;
+            let mut step = 1;
+            let high = loop {
+                let probe = start.saturating_add(step - 1);
+                if probe >= self.by_generated.len() {
+                    break self.by_generated.len();
+                }
+                if !before(&self.by_generated[probe]) {
+                    break probe;
+                }
+                low = probe + 1;
+                step *= 2;
+            };
+            low + self.by_generated[low..high].partition_point(before)
+        };
+
+        hint.idx = idx;
+        self.by_generated
+            .get(idx)
+            .filter(|m| generated_location(m) == location)
+            .or_else(|| self.slide_generated(generated_line, generated_column, idx, bias))
+    }
+
+    /// Get the mapping closest to each of the given generated locations, like
+    /// calling `original_location_for` for each of them, with the results in
+    /// the same order as the queries.
+    ///
+    /// The queries are sorted and then answered in a single forward pass over
+    /// the mappings, which is much faster than many independent searches when
+    /// symbolicating a whole stack trace. When several mappings are exactly at
+    /// a queried location, the first of them is returned.
+    pub fn original_locations_for_batch(&self, queries: &[(u32, u32)], bias: Bias) -> Vec<Option<&Mapping>> {
+        let _observer = O::OriginalLocationFor::default();
+
+        let mut order: Vec<usize> = (0..queries.len()).collect();
+        order.sort_unstable_by_key(|&q| queries[q]);
+
+        let mut results = vec![None; queries.len()];
+        let mut idx = 0;
+        for q in order {
+            let location = queries[q];
+            idx += self.by_generated[idx..]
+                .partition_point(|m| (m.generated_line, m.generated_column) < location);
+
+            let exact = self.by_generated
+                .get(idx)
+                .filter(|m| (m.generated_line, m.generated_column) == location);
+            results[q] = exact.or_else(|| self.slide_generated(location.0, location.1, idx, bias));
+        }
+        results
+    }
+
+    /// Get the mapping closest to the given generated location, if any exists,
+    /// as configured by the given query options.
+    pub fn original_location_for_with_options(
+        &self,
+        generated_line: u32,
+        generated_column: u32,
+        options: &QueryOptions,
+    ) -> Option<&Mapping> {
+        let mapping = if options.require_original {
+            let _observer = O::OriginalLocationFor::default();
+            self.nearest_matching(generated_line, generated_column, options.bias, |m| {
+                m.original.is_some()
+            })?
+        } else {
+            self.original_location_for(generated_line, generated_column, options.bias)?
+        };
+
+        if options.exact && generated_location(mapping) != (generated_line, generated_column) {
+            return None;
+        }
+
+        if options.within_span && !mapping.span_contains(generated_line, generated_column) {
+            return None;
+        }
+
+        if options.same_line_only && mapping.generated_line != generated_line {
+            return None;
+        }
+
+        Some(mapping)
+    }
+
+    /// Get the mapping at the given generated location, or if there is none,
+    /// the nearest mapping on the same generated line that is at most
+    /// `max_column_skew` columns away.
+    ///
+    /// Returns the mapping along with its distance, in columns, from the
+    /// queried location. When two mappings are equally near, the one before
+    /// the queried location is preferred.
+    ///
+    /// This is useful for symbolicating stack traces whose column numbers are
+    /// off by a few relative to the source map.
+    pub fn original_location_for_with_skew(
+        &self,
+        generated_line: u32,
+        generated_column: u32,
+        max_column_skew: u32,
+    ) -> Option<(&Mapping, u32)> {
+        let _observer = O::OriginalLocationFor::default();
+
+        let idx = match self.search_generated(generated_line, generated_column) {
+            Ok(idx) => return Some((&self.by_generated[idx], 0)),
+            Err(idx) => idx,
+        };
+
+        let before = idx.checked_sub(1)
+            .map(|idx| &self.by_generated[idx])
+            .filter(|m| m.generated_line == generated_line)
+            .map(|m| (m, generated_column - m.generated_column));
+
+        let after = self.by_generated
+            .get(idx)
+            .filter(|m| m.generated_line == generated_line)
+            .map(|m| (m, m.generated_column - generated_column));
+
+        let nearest = match (before, after) {
+            (Some(b), Some(a)) => if a.1 < b.1 {
+                a
+            } else {
+                b
+            },
+            (Some(b), None) => b,
+            (None, Some(a)) => a,
+            (None, None) => return None,
+        };
+
+        if nearest.1 <= max_column_skew {
+            Some(nearest)
+        } else {
+            None
+        }
+    }
+
+    /// Get the closest mapping that has an associated name, starting at the
+    /// given generated location and searching in the direction given by
+    /// `bias`.
+    ///
+    /// A named mapping exactly at the given location is always returned.
+    /// Otherwise, `GreatestLowerBound` searches backwards through preceding
+    /// mappings, `LeastUpperBound` searches forwards, and `ClosestMatch`
+    /// searches both ways, crossing generated lines as needed. This is how
+    /// devtools derive a function name for an anonymous stack frame.
+    pub fn nearest_named_mapping(
+        &self,
+        generated_line: u32,
+        generated_column: u32,
+        bias: Bias,
+    ) -> Option<&Mapping> {
+        let _observer = O::OriginalLocationFor::default();
+        self.nearest_matching(generated_line, generated_column, bias, |m| {
+            m.original.as_ref().is_some_and(|o| o.name.is_some())
+        })
+    }
+
+    /// Get the nearest mapping that has an associated name at or before the
+    /// given generated location.
+    ///
+    /// This is `nearest_named_mapping` with a `GreatestLowerBound` bias, which
+    /// is how error reporting services recover the enclosing function's name
+    /// for a frame in minified code.
+    #[inline]
+    pub fn closest_named_mapping_before(&self, generated_line: u32, generated_column: u32) -> Option<&Mapping> {
+        self.nearest_named_mapping(generated_line, generated_column, Bias::GreatestLowerBound)
+    }
+
+    /// Get the closest mapping satisfying `predicate`, starting at the given
+    /// generated location and searching in the direction given by `bias`.
+    fn nearest_matching<F>(
+        &self,
+        generated_line: u32,
+        generated_column: u32,
+        bias: Bias,
+        predicate: F,
+    ) -> Option<&Mapping>
+    where
+        F: Fn(&Mapping) -> bool,
+    {
+        let matches = |m: &&Mapping| predicate(m);
+
+        let (before, after) = match self.search_generated(generated_line, generated_column) {
+            Ok(idx) => {
+                // Include every mapping at exactly this location, whichever of
+                // them the binary search happened to find.
+                let location = (generated_line, generated_column);
+                let at = |m: &Mapping| (m.generated_line, m.generated_column) == location;
+                let start = self.by_generated[..idx]
+                    .iter()
+                    .rposition(|m| !at(m))
+                    .map_or(0, |i| i + 1);
+                let end = idx + self.by_generated[idx..]
+                    .iter()
+                    .position(|m| !at(m))
+                    .unwrap_or(self.by_generated.len() - idx);
+                if let Some(m) = self.by_generated[start..end].iter().find(matches) {
+                    return Some(m);
+                }
+                (start, end)
+            }
+            Err(idx) => (idx, idx),
+        };
+
+        let lower = || self.by_generated[..before].iter().rev().find(matches);
+        let upper = || self.by_generated[after..].iter().find(matches);
+        match bias {
+            Bias::GreatestLowerBound => lower(),
+            Bias::LeastUpperBound => upper(),
+            Bias::ClosestMatch => nearer(
+                (generated_line, generated_column),
+                lower().map(|m| (m, generated_location(m))),
+                upper().map(|m| (m, generated_location(m))),
+            ),
         }
     }
 
@@ -366,368 +1382,2206 @@ impl<O: Observer> Mappings<O> {
     ) -> Option<&Mapping> {
         let _observer = O::GeneratedLocationFor::default();
 
-        let position = {
+        // Sort the given source's mappings, and those of the neighboring source
+        // that the search would slide into, if it would.
+        let (position, len) = {
             let by_original = self.by_original_source(source);
-
-            by_original.binary_search_by(|m| {
-                let original = unwrap(m.original.as_ref());
-                original
-                    .source
-                    .cmp(&source)
-                    .then(original.original_line.cmp(&original_line))
-                    .then(original.original_column.cmp(&original_column))
-            })
+            let position = by_original.binary_search_by(|m| {
+                original_location(m).cmp(&(original_line, original_column))
+            });
+            (position, by_original.len())
         };
+        let slide_to = match (position, bias) {
+            (Err(idx), Bias::LeastUpperBound) if idx == len => Some(self.next_source(source)),
+            (Err(0), Bias::GreatestLowerBound) if source > 0 => Some(self.previous_source(source)),
+            _ => None,
+        };
+        if let Some(slide_to) = slide_to {
+            self.by_original_source(slide_to);
+        }
+
+        self.sorted_generated_location_for(source, original_line, original_column, bias)
+    }
+
+    /// `generated_location_for`, once the given source's mappings, and those
+    /// of any neighboring source the search slides into, are sorted.
+    fn sorted_generated_location_for(
+        &self,
+        source: u32,
+        original_line: u32,
+        original_column: u32,
+        bias: Bias,
+    ) -> Option<&Mapping> {
+        let by_original = self.sorted_source(source);
+
+        let position = by_original.binary_search_by(|m| {
+            original_location(m).cmp(&(original_line, original_column))
+        });
 
         let idx = match position {
-            Ok(idx) => return Some(&self.by_original_source(source)[idx]),
+            Ok(idx) => return by_original.get(idx),
             Err(idx) => idx,
         };
 
         match bias {
-            Bias::LeastUpperBound => if idx == self.by_original_source(source).len() {
+            Bias::LeastUpperBound => if idx == by_original.len() {
                 // Slide down to the next source's set of mappings.
-                let mut source = source + 1;
-                while unwrap(self.by_original.as_ref())
-                    .get(source as usize)
-                    .map_or(false, |b| b.is_empty())
-                {
-                    source += 1;
-                }
-                unwrap(self.by_original.as_mut())
-                    .get_mut(source as usize)
-                    .and_then(|ms| ms.sort().first())
+                self.sorted_source(self.next_source(source)).first()
             } else {
-                self.by_original_source(source).get(idx)
+                by_original.get(idx)
             },
 
+            // Never slide into a neighboring source.
+            Bias::ClosestMatch => nearer(
+                (original_line, original_column),
+                idx.checked_sub(1)
+                    .and_then(|i| by_original.get(i))
+                    .map(|m| (m, original_location(m))),
+                by_original.get(idx).map(|m| (m, original_location(m))),
+            ),
+
             Bias::GreatestLowerBound => if idx == 0 {
                 if source == 0 {
                     return None;
                 }
 
                 // Slide up to the previous source's set of mappings.
-                let mut source = source - 1;
-                while source > 0 && unwrap(self.by_original.as_ref())
-                    .get(source as usize)
-                    .map_or(false, |b| b.is_empty())
-                {
-                    source -= 1;
-                }
-                unwrap(self.by_original.as_mut())
-                    .get_mut(source as usize)
-                    .and_then(|ms| ms.sort().first())
+                self.sorted_source(self.previous_source(source)).first()
             } else {
-                self.by_original_source(source).get(idx - 1)
+                by_original.get(idx - 1)
             },
         }
     }
 
+    /// The first source after the given one that has any mappings, or the
+    /// source just past the last one if none do.
+    fn next_source(&self, source: u32) -> u32 {
+        let buckets = unwrap(self.by_original.as_ref());
+        let mut source = source + 1;
+        while buckets.get(source as usize).is_some_and(|b| b.indices.is_empty()) {
+            source += 1;
+        }
+        source
+    }
+
+    /// The last source before the given, non-zero, one that has any mappings,
+    /// or the zeroth source if none do.
+    fn previous_source(&self, source: u32) -> u32 {
+        let buckets = unwrap(self.by_original.as_ref());
+        let mut source = source - 1;
+        while source > 0 && buckets.get(source as usize).is_some_and(|b| b.indices.is_empty()) {
+            source -= 1;
+        }
+        source
+    }
+
+    /// Get the given source's mappings, ordered by original location, which
+    /// must already have been sorted.
+    #[inline]
+    fn sorted_source(&self, source: u32) -> ByOriginalSource<'_> {
+        let indices = match unwrap(self.by_original.as_ref()).get(source as usize) {
+            Some(bucket) => {
+                debug_assert!(bucket.sorted);
+                &bucket.indices[..]
+            }
+            None => &[],
+        };
+        ByOriginalSource {
+            by_generated: &self.by_generated,
+            indices,
+        }
+    }
+
     /// Get all mappings at the given original location.
     ///
     /// If `original_column` is `None`, get all mappings on the given source and
     /// original line regardless what columns they have. If `original_column` is
     /// `Some`, only return mappings for which all of source, original line, and
     /// original column match.
+    ///
+    /// This is `all_generated_locations_for_with_bias` with a
+    /// `LeastUpperBound` bias.
     pub fn all_generated_locations_for(
         &mut self,
         source: u32,
         original_line: u32,
         original_column: Option<u32>,
-    ) -> AllGeneratedLocationsFor {
-        let _observer = O::AllGeneratedLocationsFor::default();
-
-        let query_column = original_column.unwrap_or(0);
+    ) -> AllGeneratedLocationsFor<'_> {
+        self.all_generated_locations_for_with_bias(
+            source,
+            original_line,
+            original_column,
+            Bias::LeastUpperBound,
+        )
+    }
 
+    /// Get all mappings at the original location found by sliding from the
+    /// given one in the direction given by `bias`, like `mozilla/source-map`'s
+    /// `allGeneratedPositionsFor`.
+    ///
+    /// If `original_column` is `None`, find the nearest original line in the
+    /// given source that has any mappings, and get all of that line's mappings
+    /// regardless what columns they have. If `original_column` is `Some`, find
+    /// the nearest original column on the given original line that has any
+    /// mappings, and get all of that column's mappings.
+    pub fn all_generated_locations_for_with_bias(
+        &mut self,
+        source: u32,
+        original_line: u32,
+        original_column: Option<u32>,
+        bias: Bias,
+    ) -> AllGeneratedLocationsFor<'_> {
+        let _observer = O::AllGeneratedLocationsFor::default();
         let by_original = self.by_original_source(source);
+        all_generated_locations_in(by_original, original_line, original_column, bias)
+    }
 
-        let compare = |m: &Mapping| {
-            let original: &OriginalLocation = unwrap(m.original.as_ref());
-            debug_assert_eq!(unwrap(m.original.as_ref()).source, source);
-            original.original_line.cmp(&original_line)
-                .then(original.original_column.cmp(&query_column))
-        };
+    /// Iterate over the mappings with the given name, ordered by generated
+    /// location.
+    ///
+    /// The first call builds an index of mappings by name, which is kept until
+    /// the mappings are next modified.
+    pub fn generated_locations_for_name(&mut self, name: u32) -> GeneratedLocationsForName<'_> {
+        self.build_name_index();
+        self.indexed_generated_locations_for_name(name)
+    }
 
-        let idx = by_original.binary_search_by(&compare);
-        let mut idx = match idx {
-            Ok(idx) | Err(idx) => idx,
-        };
+    fn build_name_index(&mut self) {
+        if self.by_name.is_some() {
+            return;
+        }
 
-        // If there are multiple mappings for this original location, the binary
-        // search gives no guarantees that this is the index for the first of
-        // them, so back up to the first.
-        while idx > 0 && compare(&by_original[idx - 1]) == cmp::Ordering::Equal {
-            idx -= 1;
+        let mut by_name: Vec<Vec<usize>> = vec![];
+        for (idx, m) in self.by_generated.iter().enumerate() {
+            if let Some(name) = m.original.as_ref().and_then(|o| o.name) {
+                let name = name as usize;
+                if by_name.len() <= name {
+                    by_name.resize(name + 1, vec![]);
+                }
+                by_name[name].push(idx);
+            }
         }
+        self.by_name = Some(by_name);
+    }
 
-        let (mappings, original_line, original_column) = if idx < by_original.len() {
-            let orig = unwrap(by_original[idx].original.as_ref());
-            let mappings = by_original[idx..].iter();
+    /// `generated_locations_for_name`, once the index of mappings by name is
+    /// built.
+    fn indexed_generated_locations_for_name(&self, name: u32) -> GeneratedLocationsForName<'_> {
+        let indices = unwrap(self.by_name.as_ref())
+            .get(name as usize)
+            .map_or(&[][..], |indices| &indices[..]);
+        GeneratedLocationsForName {
+            by_generated: &self.by_generated,
+            indices: indices.iter(),
+        }
+    }
 
-            // Fuzzy line matching only happens when we don't have a column.
-            let original_line = if original_column.is_some() {
-                original_line
-            } else {
-                orig.original_line
-            };
+    /// Bring every cache and index up to date after mappings were added to,
+    /// removed from, or moved within `by_generated`.
+    fn generated_locations_changed(&mut self) {
+        self.reset_column_spans();
+        self.positions_changed();
+    }
 
-            let original_column = if original_column.is_some() {
-                Some(orig.original_column)
-            } else {
-                None
-            };
+    /// Bring every cache and index, except for column spans, up to date after
+    /// mappings were added to, removed from, or moved within `by_generated`.
+    ///
+    /// Callers that do this must have already patched up the column spans
+    /// themselves.
+    fn positions_changed(&mut self) {
+        self.invalidate_original_index();
+        self.line_index = line_index::GeneratedLineIndex::new(&self.by_generated);
+    }
 
-            (mappings, original_line, original_column)
-        } else {
-            ([].iter(), original_line, original_column)
-        };
+    /// Are there any per-mapping columns, such as attached payloads, that must
+    /// be edited in step with `by_generated`?
+    #[inline]
+    fn has_columns(&self) -> bool {
+        self.payload.is_some() || self.segment_indices.is_some() || self.segment_ranges.is_some()
+    }
 
-        AllGeneratedLocationsFor {
-            mappings,
-            original_line,
-            original_column,
+    /// Keep the per-mapping columns in step after removing the mappings whose
+    /// entry in `keep` is `false` from `by_generated`.
+    fn retain_columns(&mut self, keep: &[bool]) {
+        if let Some(ref mut payload) = self.payload {
+            payload.retain_mask(keep);
+        }
+        if let Some(ref mut segment_indices) = self.segment_indices {
+            payload::retain_mask(segment_indices, keep);
+        }
+        if let Some(ref mut segment_ranges) = self.segment_ranges {
+            payload::retain_mask(segment_ranges, keep);
         }
     }
-}
 
-impl<O: Observer> Default for Mappings<O> {
-    #[inline]
-    fn default() -> Mappings<O> {
-        Mappings {
-            by_generated: vec![],
-            by_original: None,
-            computed_column_spans: false,
-            observer: Default::default(),
+    /// Keep the per-mapping columns in step after inserting a mapping into
+    /// `by_generated` at the given index.
+    fn insert_columns(&mut self, idx: usize) {
+        if let Some(ref mut payload) = self.payload {
+            payload.insert_default(idx);
+        }
+        if let Some(ref mut segment_indices) = self.segment_indices {
+            segment_indices.insert(idx, NO_SEGMENT_INDEX);
+        }
+        if let Some(ref mut segment_ranges) = self.segment_ranges {
+            segment_ranges.insert(idx, NO_SEGMENT_RANGE);
         }
     }
-}
-
-/// An iterator returned by `Mappings::by_original_location`.
-#[derive(Debug)]
-pub struct ByOriginalLocation<'a, O: 'a> {
-    buckets: slice::IterMut<'a, LazilySorted<Mapping, comparators::ByOriginalLocationSameSource, O>>,
-    this_bucket: slice::Iter<'a, Mapping>,
-}
 
-impl<'a, O: 'a + Default> Iterator for ByOriginalLocation<'a, O> {
-    type Item = &'a Mapping;
+    /// Keep the per-mapping columns in step after removing the given range of
+    /// `by_generated`.
+    fn drain_columns(&mut self, range: ops::Range<usize>) {
+        if let Some(ref mut payload) = self.payload {
+            payload.drain(range.clone());
+        }
+        if let Some(ref mut segment_indices) = self.segment_indices {
+            segment_indices.drain(range.clone());
+        }
+        if let Some(ref mut segment_ranges) = self.segment_ranges {
+            segment_ranges.drain(range);
+        }
+    }
 
+    /// Throw away the cached indexes of mappings sorted by original location
+    /// and grouped by name.
+    ///
+    /// Must be called by every operation that changes the mappings in a way
+    /// that the indexes would not reflect.
     #[inline]
-    fn next(&mut self) -> Option<Self::Item> {
-        loop {
-            if let Some(m) = self.this_bucket.next() {
-                return Some(m);
-            }
+    fn invalidate_original_index(&mut self) {
+        self.by_original = None;
+        self.by_name = None;
+    }
 
-            if let Some(b) = self.buckets.next() {
-                self.this_bucket = b.sort().iter();
-                continue;
+    /// Clear every mapping's column span, and then recompute them if they had
+    /// previously been computed.
+    ///
+    /// Must be called by every operation that adds, removes, or moves mappings,
+    /// since that can change the spans of their neighbors.
+    fn reset_column_spans(&mut self) {
+        for m in &mut self.by_generated {
+            m.last_generated_column = None;
+        }
+        if self.computed_column_spans {
+            self.computed_column_spans = false;
+            self.compute_column_spans_slow_path();
+        }
+    }
+
+    /// Remove the associated name from every mapping.
+    ///
+    /// This is useful for publishing source maps that don't reveal the original
+    /// program's identifiers.
+    pub fn strip_names(&mut self) {
+        for m in &mut self.by_generated {
+            if let Some(ref mut original) = m.original {
+                original.name = None;
             }
+        }
+        self.invalidate_original_index();
+    }
 
-            return None;
+    /// Remove all original location information from every mapping, leaving
+    /// only the generated locations.
+    ///
+    /// The result describes the structure of the generated code's mapped spans,
+    /// without revealing anything about the original sources.
+    pub fn strip_original(&mut self) {
+        for m in &mut self.by_generated {
+            m.original = None;
         }
+        self.invalidate_original_index();
     }
-}
 
-/// An iterator returned by `Mappings::all_generated_locations_for`.
-#[derive(Debug)]
-pub struct AllGeneratedLocationsFor<'a> {
-    mappings: slice::Iter<'a, Mapping>,
-    original_line: u32,
-    original_column: Option<u32>,
-}
+    /// Insert a single mapping, keeping the mappings sorted by generated
+    /// location.
+    ///
+    /// If column spans have been computed, only the spans of the new mapping
+    /// and its predecessor on the same generated line are updated. If payloads
+    /// are attached, the new mapping gets a default payload.
+    pub fn insert(&mut self, mut mapping: Mapping) {
+        let idx = self.by_generated.partition_point(|m| {
+            comparators::ByGeneratedLocation::compare(m, &mapping) != cmp::Ordering::Greater
+        });
 
-impl<'a> Iterator for AllGeneratedLocationsFor<'a> {
-    type Item = &'a Mapping;
+        if self.computed_column_spans {
+            mapping.last_generated_column = self.by_generated
+                .get(idx)
+                .filter(|next| next.generated_line == mapping.generated_line)
+                .map(|next| next.generated_column);
+            if let Some(prev) = idx.checked_sub(1).map(|i| &mut self.by_generated[i]) {
+                if prev.generated_line == mapping.generated_line {
+                    prev.last_generated_column = Some(mapping.generated_column);
+                }
+            }
+        } else {
+            mapping.last_generated_column = None;
+        }
 
-    #[inline]
-    fn next(&mut self) -> Option<Self::Item> {
-        match self.mappings.next() {
-            None => None,
-            Some(m) => {
-                let m_orig = unwrap(m.original.as_ref());
+        let generated_line = mapping.generated_line;
+        self.by_generated.insert(idx, mapping);
+        self.insert_columns(idx);
+        self.invalidate_original_index();
+        self.line_index.line_changed(&self.by_generated, generated_line, 1);
+    }
 
-                if m_orig.original_line != self.original_line {
-                    return None;
-                }
+    /// Remove every mapping at exactly the given generated location, keeping
+    /// the mappings sorted by generated location, and return them.
+    ///
+    /// If column spans have been computed, only the span of the removed
+    /// mappings' predecessor on the same generated line is updated. Their
+    /// payloads, if any are attached, are removed along with them.
+    pub fn remove_at_generated(&mut self, generated_line: u32, generated_column: u32) -> Vec<Mapping> {
+        let location = (generated_line, generated_column);
+        let start = self.by_generated
+            .partition_point(|m| (m.generated_line, m.generated_column) < location);
+        let end = self.by_generated
+            .partition_point(|m| (m.generated_line, m.generated_column) <= location);
+        if start == end {
+            return vec![];
+        }
 
-                if let Some(original_column) = self.original_column {
-                    if m_orig.original_column != original_column {
-                        return None;
-                    }
+        if self.computed_column_spans {
+            let last = self.by_generated[end - 1].last_generated_column;
+            if let Some(prev) = start.checked_sub(1).map(|i| &mut self.by_generated[i]) {
+                if prev.generated_line == generated_line {
+                    prev.last_generated_column = last;
                 }
-
-                Some(m)
             }
         }
+
+        let removed = self.by_generated.drain(start..end).collect();
+        self.drain_columns(start..end);
+        self.invalidate_original_index();
+        self.line_index.line_changed(&self.by_generated, generated_line, -((end - start) as isize));
+        removed
     }
-}
 
-/// A single bidirectional mapping.
-///
-/// Always contains generated location information.
-///
-/// Might contain original location information, and if so, might also have an
-/// associated name.
+    /// Keep only the mappings for which the given predicate returns `true`,
+    /// filtering them in place.
+    ///
+    /// For example, this can drop every mapping into an ignored vendor source
+    /// without the peak memory cost of building a filtered copy. The payloads
+    /// of removed mappings, if any are attached, are removed too.
+    pub fn retain<F>(&mut self, mut predicate: F)
+    where
+        F: FnMut(&Mapping) -> bool,
+    {
+        let len = self.by_generated.len();
+        if self.has_columns() {
+            let keep: Vec<bool> = self.by_generated.iter().map(&mut predicate).collect();
+            payload::retain_mask(&mut self.by_generated, &keep);
+            self.retain_columns(&keep);
+        } else {
+            self.by_generated.retain(predicate);
+        }
+        if self.by_generated.len() < len {
+            self.generated_locations_changed();
+        }
+    }
+
+    /// Shift every mapping's generated location, as if `line_delta` lines
+    /// followed by `column_delta_for_first_line` columns were prepended to the
+    /// generated code.
+    ///
+    /// Every mapping moves down `line_delta` lines, and mappings on the first
+    /// generated line also move right `column_delta_for_first_line` columns,
+    /// since they now share a line with the end of the prepended code. This is
+    /// useful when prepending a banner, or wrapping the generated code in an
+    /// IIFE.
+    ///
+    /// Returns `Error::UnexpectedlyBigGeneratedLine` or
+    /// `Error::UnexpectedlyBigGeneratedColumn`, leaving the mappings unchanged,
+    /// if some mapping would be shifted past `u32::MAX`.
+    pub fn offset_generated(&mut self, line_delta: u32, column_delta_for_first_line: u32) -> Result<(), Error> {
+        if line_delta == 0 && column_delta_for_first_line == 0 {
+            return Ok(());
+        }
+
+        let last_line = self.by_generated.last().map_or(0, |m| m.generated_line);
+        if last_line.checked_add(line_delta).is_none() {
+            return Err(Error::UnexpectedlyBigGeneratedLine);
+        }
+        let columns_overflow = self.by_generated
+            .iter()
+            .take_while(|m| m.generated_line == 0)
+            .map(|m| m.last_generated_column.unwrap_or(m.generated_column))
+            .any(|column| column.checked_add(column_delta_for_first_line).is_none());
+        if columns_overflow {
+            return Err(Error::UnexpectedlyBigGeneratedColumn);
+        }
+
+        for m in &mut self.by_generated {
+            if m.generated_line == 0 {
+                m.generated_column += column_delta_for_first_line;
+                if let Some(ref mut last) = m.last_generated_column {
+                    *last += column_delta_for_first_line;
+                }
+            }
+            m.generated_line += line_delta;
+        }
+
+        // The mappings' order is unchanged, so their ids and payloads are too.
+        self.invalidate_original_index();
+        self.line_index = line_index::GeneratedLineIndex::new(&self.by_generated);
+        Ok(())
+    }
+
+    /// Update these mappings for an edit to the generated code that replaced
+    /// `deleted_lines` whole lines, starting at `start_line`, with
+    /// `inserted_lines` new lines.
+    ///
+    /// Mappings within the deleted lines are dropped, and mappings after them
+    /// are shifted to follow the inserted lines. The inserted lines have no
+    /// mappings. This keeps source maps roughly correct during live editing
+    /// and hot module replacement, without rebuilding them.
+    ///
+    /// Returns `Error::UnexpectedlyBigGeneratedLine`, leaving the mappings
+    /// unchanged, if some mapping would be shifted past line `u32::MAX`.
+    pub fn apply_generated_edit(
+        &mut self,
+        start_line: u32,
+        deleted_lines: u32,
+        inserted_lines: u32,
+    ) -> Result<(), Error> {
+        let start = self.by_generated.partition_point(|m| m.generated_line < start_line);
+        let end = match start_line.checked_add(deleted_lines) {
+            Some(end_line) => self.by_generated.partition_point(|m| m.generated_line < end_line),
+            // The deleted lines run past the last possible line.
+            None => self.by_generated.len(),
+        };
+
+        // Every mapping after the deleted lines is at or after the line just
+        // past them, so subtracting `deleted_lines` can't underflow, and the
+        // last of them is the only one that could overflow.
+        let shifted = |m: &Mapping| (m.generated_line - deleted_lines).checked_add(inserted_lines);
+        if self.by_generated[end..].last().is_some_and(|m| shifted(m).is_none()) {
+            return Err(Error::UnexpectedlyBigGeneratedLine);
+        }
+
+        if deleted_lines != inserted_lines {
+            for m in &mut self.by_generated[end..] {
+                m.generated_line = unwrap(shifted(m));
+            }
+        } else if start == end {
+            return Ok(());
+        }
+
+        // Whole lines are deleted, so the remaining mappings' column spans are
+        // unaffected.
+        self.by_generated.drain(start..end);
+        self.drain_columns(start..end);
+        self.positions_changed();
+        Ok(())
+    }
+
+    /// Append another set of mappings, as if its generated code were
+    /// concatenated with this one's starting at `generated_line_offset`.
+    ///
+    /// The other mappings' source and name indices are shifted by
+    /// `source_index_offset` and `name_index_offset`, for when their `"sources"`
+    /// and `"names"` were appended to this source map's. This is what bundlers
+    /// do when concatenating files.
+    ///
+    /// Returns `Error::UnexpectedlyBigGeneratedLine`,
+    /// `Error::UnexpectedlyBigSource`, or `Error::UnexpectedlyBigName`, leaving
+    /// these mappings unchanged, if some offset line or index would be larger
+    /// than `u32::MAX`.
+    pub fn append_with_offset<O2: Observer>(
+        &mut self,
+        mut other: Mappings<O2>,
+        generated_line_offset: u32,
+        source_index_offset: u32,
+        name_index_offset: u32,
+    ) -> Result<(), Error> {
+        let last_line = other.by_generated.last().map_or(0, |m| m.generated_line);
+        if last_line.checked_add(generated_line_offset).is_none() {
+            return Err(Error::UnexpectedlyBigGeneratedLine);
+        }
+        for original in other.by_generated.iter().filter_map(|m| m.original.as_ref()) {
+            if original.source.checked_add(source_index_offset).is_none() {
+                return Err(Error::UnexpectedlyBigSource);
+            }
+            if original.name.is_some_and(|name| name.checked_add(name_index_offset).is_none()) {
+                return Err(Error::UnexpectedlyBigName);
+            }
+        }
+
+        for m in &mut other.by_generated {
+            m.generated_line += generated_line_offset;
+            if let Some(ref mut original) = m.original {
+                original.source += source_index_offset;
+                if let Some(ref mut name) = original.name {
+                    *name += name_index_offset;
+                }
+            }
+        }
+
+        self.merge(other, MergePolicy::KeepBoth);
+        Ok(())
+    }
+
+    /// Extract the mappings on generated lines `start_line..end_line` into
+    /// their own `Mappings` structure, rebased so that `start_line` becomes
+    /// line 0.
+    ///
+    /// This is useful for code splitting, where a chunk of a bundle is moved
+    /// into its own file that needs its own source map. Source and name indices
+    /// are unchanged, and any attached string tables are copied, but attached
+    /// payloads are not, since they can't be cloned without knowing their type.
+    pub fn slice_generated(&self, start_line: u32, end_line: u32) -> Mappings<O> {
+        let start = self.by_generated.partition_point(|m| m.generated_line < start_line);
+        let end = self.by_generated.partition_point(|m| m.generated_line < end_line);
+
+        let by_generated = self.by_generated[start..cmp::max(start, end)]
+            .iter()
+            .map(|m| Mapping {
+                generated_line: m.generated_line - start_line,
+                generated_column: m.generated_column,
+                last_generated_column: None,
+                original: m.original.clone(),
+            })
+            .collect();
+
+        Mappings {
+            string_tables: self.string_tables.clone(),
+            ..Mappings::from_sorted(by_generated)
+        }
+    }
+
+    /// Move the mappings on generated lines at or after `generated_line` into
+    /// their own `Mappings` structure, like `Vec::split_off`, leaving the rest
+    /// in place.
+    ///
+    /// If `rebase` is true, the split off mappings are rebased so that
+    /// `generated_line` becomes line 0. Either way, source and name indices
+    /// are unchanged, and any attached string tables are copied. Since the
+    /// split is between whole lines, both halves keep their column spans, and
+    /// any attached payloads are split along with the mappings.
+    pub fn split_off(&mut self, generated_line: u32, rebase: bool) -> Mappings<O> {
+        let idx = self.by_generated.partition_point(|m| m.generated_line < generated_line);
+
+        let mut tail = self.by_generated.split_off(idx);
+        if rebase {
+            for m in &mut tail {
+                m.generated_line -= generated_line;
+            }
+        }
+
+        let segment_indices = self.segment_indices.as_mut().map(|indices| indices.split_off(idx));
+        let segment_ranges = self.segment_ranges.as_mut().map(|ranges| ranges.split_off(idx));
+        let payload = self.payload.as_mut().map(|payload| payload.split_off(idx));
+
+        // The mappings left in place keep their ids.
+        self.invalidate_original_index();
+        self.line_index = line_index::GeneratedLineIndex::new(&self.by_generated);
+
+        Mappings {
+            computed_column_spans: self.computed_column_spans,
+            string_tables: self.string_tables.clone(),
+            original_tie_break: self.original_tie_break,
+            payload,
+            segment_indices,
+            segment_ranges,
+            ..Mappings::from_sorted(tail)
+        }
+    }
+
+    /// Split these mappings into one `Mappings` structure per source, for each
+    /// source that has any mappings, ordered by source index.
+    ///
+    /// Mappings without original location information belong to no source, and
+    /// are left out. Source and name indices are unchanged.
+    pub fn split_by_source(&self) -> Vec<(u32, Mappings<O>)> {
+        self.partition_by_source(|m| Mapping {
+            last_generated_column: None,
+            ..m.clone()
+        })
+    }
+
+    /// Invert these mappings, so that original locations become the primary,
+    /// generated side, producing one `Mappings` structure per source, for each
+    /// source that has any mappings, ordered by source index.
+    ///
+    /// In each inverted `Mappings`, a mapping's generated location is an
+    /// original location within that source, and its original location is the
+    /// corresponding location in the generated code, with source index 0 and
+    /// the same name. Mappings without original location information are left
+    /// out. Column spans are computed along the original axis, by calling
+    /// `compute_column_spans` on the results as usual.
+    ///
+    /// This is useful for building reverse lookup tables, and for serializing
+    /// reverse source maps.
+    pub fn invert(&self) -> Vec<(u32, Mappings<O>)> {
+        let mut inverted = self.partition_by_source(|m| {
+            let original = unwrap(m.original.as_ref());
+            Mapping {
+                generated_line: original.original_line,
+                generated_column: original.original_column,
+                last_generated_column: None,
+                original: Some(OriginalLocation {
+                    source: 0,
+                    original_line: m.generated_line,
+                    original_column: m.generated_column,
+                    name: original.name,
+                }),
+            }
+        });
+
+        for &mut (_, ref mut mappings) in &mut inverted {
+            let _observer = O::SortByGeneratedLocation::default();
+            sort::by_comparator::<comparators::ByGeneratedLocation, _>(&mut mappings.by_generated);
+            mappings.line_index = line_index::GeneratedLineIndex::new(&mappings.by_generated);
+        }
+        inverted
+    }
+
+    /// Partition the mappings with original location information by source,
+    /// transforming each of them with `f` along the way, and construct a
+    /// `Mappings` structure from each non-empty partition.
+    ///
+    /// If `f` reorders the mappings within a partition, the caller must sort
+    /// them again.
+    fn partition_by_source<F>(&self, mut f: F) -> Vec<(u32, Mappings<O>)>
+    where
+        F: FnMut(&Mapping) -> Mapping,
+    {
+        let mut by_source: Vec<Vec<Mapping>> = vec![];
+        for m in &self.by_generated {
+            let source = match m.original {
+                Some(ref original) => original.source as usize,
+                None => continue,
+            };
+            if by_source.len() <= source {
+                by_source.resize(source + 1, vec![]);
+            }
+            by_source[source].push(f(m));
+        }
+
+        by_source
+            .into_iter()
+            .enumerate()
+            .filter(|(_, mappings)| !mappings.is_empty())
+            .map(|(source, mappings)| (source as u32, Mappings::from_sorted(mappings)))
+            .collect()
+    }
+
+    /// Count the mappings that are exact duplicates of an earlier mapping: at
+    /// the same generated location, with the same original location
+    /// information.
+    ///
+    /// Concatenated source maps often contain many of these.
+    pub fn exact_duplicates(&self) -> usize {
+        self.by_generated
+            .windows(2)
+            .filter(|pair| is_exact_duplicate(&pair[1], &pair[0]))
+            .count()
+    }
+
+    /// Remove every mapping that is an exact duplicate of an earlier mapping,
+    /// and return how many were removed.
+    ///
+    /// Unlike `normalize`, mappings at the same generated location but with
+    /// different original location information are all kept. The payloads of
+    /// removed mappings, if any are attached, are removed too.
+    pub fn dedup_exact(&mut self) -> usize {
+        let len = self.by_generated.len();
+        if self.has_columns() {
+            let keep: Vec<bool> = (0..len)
+                .map(|i| i == 0 || !is_exact_duplicate(&self.by_generated[i], &self.by_generated[i - 1]))
+                .collect();
+            payload::retain_mask(&mut self.by_generated, &keep);
+            self.retain_columns(&keep);
+        } else {
+            self.by_generated.dedup_by(|m, prev| is_exact_duplicate(m, prev));
+        }
+        let removed = len - self.by_generated.len();
+        if removed > 0 {
+            self.generated_locations_changed();
+        }
+        removed
+    }
+
+    /// Put these mappings into a canonical form, so that serializing them is
+    /// deterministic and minimal.
+    ///
+    /// Exact duplicates are removed, as are zero-width mappings: those whose
+    /// generated span is empty, either explicitly or because another mapping
+    /// begins at the same generated location. Of several mappings at the same
+    /// generated location, only the last in generated order is kept, since it
+    /// is the only one that spans any generated columns.
+    ///
+    /// This is useful for diffing source maps produced by different
+    /// toolchains.
+    pub fn normalize(&mut self) {
+        let len = self.by_generated.len();
+        let track = self.has_columns();
+        let mut keep = vec![];
+        let mut kept = 0;
+        for i in 0..len {
+            let m = &self.by_generated[i];
+            let shadowed = self.by_generated.get(i + 1).is_some_and(|next| {
+                next.generated_line == m.generated_line && next.generated_column == m.generated_column
+            });
+            let empty = m.last_generated_column == Some(m.generated_column);
+            if track {
+                keep.push(!shadowed && !empty);
+            }
+            if !shadowed && !empty {
+                self.by_generated.swap(kept, i);
+                kept += 1;
+            }
+        }
+
+        if kept < len {
+            self.by_generated.truncate(kept);
+            self.retain_columns(&keep);
+            self.generated_locations_changed();
+        }
+    }
+
+    /// Rewrite every mapping's source index `s` to `sources[s]`.
+    ///
+    /// This is needed when a source map's `"sources"` are deduplicated or
+    /// reordered, for example while merging source maps. Source indices that
+    /// are out of bounds of `sources` are left unchanged.
+    pub fn remap_sources(&mut self, sources: &[u32]) {
+        for m in &mut self.by_generated {
+            if let Some(ref mut original) = m.original {
+                if let Some(&source) = sources.get(original.source as usize) {
+                    original.source = source;
+                }
+            }
+        }
+        self.invalidate_original_index();
+    }
+
+    /// Rewrite every mapping's name index `n` to `names[n]`.
+    ///
+    /// This is needed when a source map's `"names"` are deduplicated or
+    /// reordered, for example while merging source maps. Name indices that are
+    /// out of bounds of `names` are left unchanged.
+    pub fn remap_names(&mut self, names: &[u32]) {
+        for m in &mut self.by_generated {
+            if let Some(name) = m.original.as_mut().and_then(|o| o.name.as_mut()) {
+                if let Some(&new_name) = names.get(*name as usize) {
+                    *name = new_name;
+                }
+            }
+        }
+        self.invalidate_original_index();
+    }
+
+    /// Merge another set of mappings for the same generated file into this
+    /// one.
+    ///
+    /// This is useful when multiple tools each emit partial mappings for the
+    /// same generated output. When both sets have mappings at the same
+    /// generated location, `policy` decides which are kept. If payloads are
+    /// attached to these mappings, the other mappings keep theirs when they are
+    /// of the same type, and get default payloads otherwise.
+    pub fn merge<O2: Observer>(&mut self, mut other: Mappings<O2>, policy: MergePolicy) {
+        let ours = mem::take(&mut self.by_generated);
+        let theirs = mem::take(&mut other.by_generated);
+
+        // Which of each side's mappings are kept, and which side each merged
+        // mapping came from, for keeping the per-mapping columns in step.
+        let track = self.has_columns();
+        let mut ours_kept = vec![];
+        let mut theirs_kept = vec![];
+        let mut from_ours = vec![];
+
+        let mut merged = Vec::with_capacity(ours.len() + theirs.len());
+        let mut ours = ours.into_iter().peekable();
+        let mut theirs = theirs.into_iter().peekable();
+
+        loop {
+            let ordering = match (ours.peek(), theirs.peek()) {
+                (None, None) => break,
+                (Some(_), None) => cmp::Ordering::Less,
+                (None, Some(_)) => cmp::Ordering::Greater,
+                (Some(a), Some(b)) => {
+                    let same_location = a.generated_line == b.generated_line
+                        && a.generated_column == b.generated_column;
+                    match policy {
+                        MergePolicy::PreferSelf if same_location => {
+                            theirs.next();
+                            if track {
+                                theirs_kept.push(false);
+                            }
+                            continue;
+                        }
+                        MergePolicy::PreferOther if same_location => {
+                            ours.next();
+                            if track {
+                                ours_kept.push(false);
+                            }
+                            continue;
+                        }
+                        _ => comparators::ByGeneratedLocation::compare(a, b),
+                    }
+                }
+            };
+
+            let take_ours = ordering != cmp::Ordering::Greater;
+            merged.push(if take_ours {
+                unwrap(ours.next())
+            } else {
+                unwrap(theirs.next())
+            });
+            if track {
+                from_ours.push(take_ours);
+                if take_ours {
+                    ours_kept.push(true);
+                } else {
+                    theirs_kept.push(true);
+                }
+            }
+        }
+
+        self.by_generated = merged;
+        if track {
+            let mut their_payload = other.payload.take();
+            if let Some(ref mut payload) = their_payload {
+                payload.retain_mask(&theirs_kept);
+            }
+            self.retain_columns(&ours_kept);
+            if let Some(ref mut payload) = self.payload {
+                payload.interleave(their_payload, &from_ours);
+            }
+            // The other mappings' segments are in some other string.
+            if let Some(ref mut segment_indices) = self.segment_indices {
+                payload::interleave_with(segment_indices, &from_ours, NO_SEGMENT_INDEX);
+            }
+            if let Some(ref mut segment_ranges) = self.segment_ranges {
+                payload::interleave_with(segment_ranges, &from_ours, NO_SEGMENT_RANGE);
+            }
+        }
+        self.generated_locations_changed();
+    }
+}
+
+impl<O: Observer> Mappings<O> {
+    /// Construct a queryable `Mappings` structure directly from a set of
+    /// mappings, in any order.
+    ///
+    /// Any `last_generated_column`s that are given are kept, and
+    /// `compute_column_spans` only fills in the rest. Returns
+    /// `Error::InvalidColumnSpan` if a mapping's `last_generated_column` is
+    /// before its `generated_column`.
+    pub fn from_mappings(mut mappings: Vec<Mapping>) -> Result<Mappings<O>, Error> {
+        let invalid_span = mappings
+            .iter()
+            .any(|m| m.last_generated_column.is_some_and(|last| last < m.generated_column));
+        if invalid_span {
+            return Err(Error::InvalidColumnSpan);
+        }
+
+        {
+            let _observer = O::SortByGeneratedLocation::default();
+            sort::by_comparator::<comparators::ByGeneratedLocation, _>(&mut mappings);
+        }
+
+        Ok(Mappings::from_sorted(mappings))
+    }
+
+    /// Construct a `Mappings` from mappings that are already sorted by
+    /// generated location.
+    fn from_sorted(by_generated: Vec<Mapping>) -> Mappings<O> {
+        Mappings {
+            line_index: line_index::GeneratedLineIndex::new(&by_generated),
+            by_generated,
+            ..Default::default()
+        }
+    }
+
+    /// Consume these mappings, returning them ordered by generated location.
+    ///
+    /// Column spans are included only if they were computed.
+    #[inline]
+    pub fn into_vec(self) -> Vec<Mapping> {
+        self.by_generated
+    }
+}
+
+impl<O: Observer> IntoIterator for Mappings<O> {
+    type Item = Mapping;
+    type IntoIter = vec::IntoIter<Mapping>;
+
+    #[inline]
+    fn into_iter(self) -> vec::IntoIter<Mapping> {
+        self.into_vec().into_iter()
+    }
+}
+
+impl<'a, O: Observer> IntoIterator for &'a Mappings<O> {
+    type Item = &'a Mapping;
+    type IntoIter = slice::Iter<'a, Mapping>;
+
+    #[inline]
+    fn into_iter(self) -> slice::Iter<'a, Mapping> {
+        self.by_generated.iter()
+    }
+}
+
+impl<O: Observer> Default for Mappings<O> {
+    #[inline]
+    fn default() -> Mappings<O> {
+        Mappings {
+            by_generated: vec![],
+            by_original: None,
+            by_name: None,
+            original_tie_break: OriginalTieBreak::default(),
+            computed_column_spans: false,
+            _observer: Default::default(),
+            line_index: Default::default(),
+            payload: None,
+            string_tables: None,
+            segment_indices: None,
+            segment_ranges: None,
+        }
+    }
+}
+
+/// An iterator returned by `Mappings::by_original_location`.
+#[derive(Debug)]
+pub struct ByOriginalLocation<'a, O: 'a> {
+    buckets: slice::IterMut<'a, SourceBucket<O>>,
+    by_generated: &'a [Mapping],
+    tie_break: OriginalTieBreak,
+    this_bucket: slice::Iter<'a, u32>,
+}
+
+impl<'a, O: 'a + Default> Iterator for ByOriginalLocation<'a, O> {
+    type Item = &'a Mapping;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(&idx) = self.this_bucket.next() {
+                return Some(&self.by_generated[idx as usize]);
+            }
+
+            if let Some(b) = self.buckets.next() {
+                self.this_bucket = b.sort(self.by_generated, self.tie_break).iter();
+                continue;
+            }
+
+            return None;
+        }
+    }
+}
+
+/// An iterator returned by `Mappings::try_by_original_location` and
+/// `FrozenMappings::by_original_location`.
+#[derive(Debug)]
+pub struct SortedByOriginalLocation<'a, O: 'a> {
+    buckets: slice::Iter<'a, SourceBucket<O>>,
+    by_generated: &'a [Mapping],
+    this_bucket: slice::Iter<'a, u32>,
+}
+
+impl<'a, O: 'a> Iterator for SortedByOriginalLocation<'a, O> {
+    type Item = &'a Mapping;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(&idx) = self.this_bucket.next() {
+                return Some(&self.by_generated[idx as usize]);
+            }
+
+            self.this_bucket = self.buckets.next()?.indices.iter();
+        }
+    }
+}
+
+/// The mappings for a single original source, ordered by original location.
+///
+/// Returned by `Mappings::by_original_source`. This is a view through an index
+/// of positions into the mappings ordered by generated location, rather than a
+/// copy of the mappings.
+#[derive(Clone, Copy, Debug)]
+pub struct ByOriginalSource<'a> {
+    by_generated: &'a [Mapping],
+    indices: &'a [u32],
+}
+
+impl<'a> ByOriginalSource<'a> {
+    /// Get the number of mappings.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.indices.len()
+    }
+
+    /// Are there no mappings?
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    /// Get the `idx`th mapping, if there are that many.
+    #[inline]
+    pub fn get(&self, idx: usize) -> Option<&'a Mapping> {
+        self.indices.get(idx).map(|&i| &self.by_generated[i as usize])
+    }
+
+    /// Get the first mapping, if any.
+    #[inline]
+    pub fn first(&self) -> Option<&'a Mapping> {
+        self.get(0)
+    }
+
+    /// Get the last mapping, if any.
+    #[inline]
+    pub fn last(&self) -> Option<&'a Mapping> {
+        self.len().checked_sub(1).and_then(|idx| self.get(idx))
+    }
+
+    /// Iterate over the mappings.
+    #[inline]
+    pub fn iter(&self) -> ByOriginalSourceIter<'a> {
+        ByOriginalSourceIter {
+            by_generated: self.by_generated,
+            indices: self.indices.iter(),
+        }
+    }
+
+    /// Binary search the mappings with the given comparator function, like
+    /// `slice::binary_search_by`.
+    #[inline]
+    pub fn binary_search_by<F>(&self, mut f: F) -> Result<usize, usize>
+    where
+        F: FnMut(&'a Mapping) -> cmp::Ordering,
+    {
+        let by_generated = self.by_generated;
+        self.indices.binary_search_by(|&i| f(&by_generated[i as usize]))
+    }
+
+    /// Get the index of the first mapping for which the predicate is false,
+    /// like `slice::partition_point`.
+    #[inline]
+    pub fn partition_point<P>(&self, mut pred: P) -> usize
+    where
+        P: FnMut(&'a Mapping) -> bool,
+    {
+        let by_generated = self.by_generated;
+        self.indices.partition_point(|&i| pred(&by_generated[i as usize]))
+    }
+
+    #[inline]
+    fn slice(&self, range: ops::Range<usize>) -> ByOriginalSource<'a> {
+        ByOriginalSource {
+            by_generated: self.by_generated,
+            indices: &self.indices[range],
+        }
+    }
+}
+
+impl<'a> ops::Index<usize> for ByOriginalSource<'a> {
+    type Output = Mapping;
+
+    #[inline]
+    fn index(&self, idx: usize) -> &Mapping {
+        &self.by_generated[self.indices[idx] as usize]
+    }
+}
+
+impl<'a> IntoIterator for ByOriginalSource<'a> {
+    type Item = &'a Mapping;
+    type IntoIter = ByOriginalSourceIter<'a>;
+
+    #[inline]
+    fn into_iter(self) -> ByOriginalSourceIter<'a> {
+        self.iter()
+    }
+}
+
+/// An iterator over a `ByOriginalSource`.
+#[derive(Clone, Debug)]
+pub struct ByOriginalSourceIter<'a> {
+    by_generated: &'a [Mapping],
+    indices: slice::Iter<'a, u32>,
+}
+
+impl<'a> Iterator for ByOriginalSourceIter<'a> {
+    type Item = &'a Mapping;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.indices.next().map(|&idx| &self.by_generated[idx as usize])
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.indices.size_hint()
+    }
+}
+
+impl<'a> DoubleEndedIterator for ByOriginalSourceIter<'a> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.indices.next_back().map(|&idx| &self.by_generated[idx as usize])
+    }
+}
+
+impl<'a> ExactSizeIterator for ByOriginalSourceIter<'a> {}
+
+/// An iterator returned by `Mappings::generated_locations_for_name`.
+#[derive(Debug)]
+pub struct GeneratedLocationsForName<'a> {
+    by_generated: &'a [Mapping],
+    indices: slice::Iter<'a, usize>,
+}
+
+impl<'a> Iterator for GeneratedLocationsForName<'a> {
+    type Item = &'a Mapping;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.indices.next().map(|&idx| &self.by_generated[idx])
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.indices.size_hint()
+    }
+}
+
+/// An iterator returned by `Mappings::all_generated_locations_for`.
+///
+/// Knows how many mappings it will yield, and can be iterated from either end.
+#[derive(Clone, Debug)]
+pub struct AllGeneratedLocationsFor<'a> {
+    mappings: ByOriginalSourceIter<'a>,
+}
+
+impl<'a> Iterator for AllGeneratedLocationsFor<'a> {
+    type Item = &'a Mapping;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.mappings.next()
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.mappings.size_hint()
+    }
+}
+
+impl<'a> DoubleEndedIterator for AllGeneratedLocationsFor<'a> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.mappings.next_back()
+    }
+}
+
+impl<'a> ExactSizeIterator for AllGeneratedLocationsFor<'a> {}
+
+/// A single bidirectional mapping.
+///
+/// Always contains generated location information.
+///
+/// Might contain original location information, and if so, might also have an
+/// associated name.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Mapping {
     /// The generated line.
     pub generated_line: u32,
 
-    /// The generated column.
-    pub generated_column: u32,
+    /// The generated column.
+    pub generated_column: u32,
+
+    /// The end column of this mapping's generated location span.
+    ///
+    /// Before `Mappings::computed_column_spans` has been called, this is always
+    /// `None`, unless it was given explicitly to `Mappings::from_mappings`.
+    /// After `Mappings::computed_column_spans` has been called, it either
+    /// contains `Some` column at which the generated location ends (exclusive),
+    /// or it contains `None` if it spans until the end of the generated line.
+    pub last_generated_column: Option<u32>,
+
+    /// The original location information, if any.
+    pub original: Option<OriginalLocation>,
+}
+
+impl Default for Mapping {
+    #[inline]
+    fn default() -> Mapping {
+        Mapping {
+            generated_line: 0,
+            generated_column: 0,
+            last_generated_column: None,
+            original: None,
+        }
+    }
+}
+
+impl Mapping {
+    /// Does this mapping's generated span contain the given generated location?
+    ///
+    /// A mapping always contains its own starting location, even if its span
+    /// is empty because another mapping begins at the same column.
+    #[inline]
+    fn span_contains(&self, generated_line: u32, generated_column: u32) -> bool {
+        self.generated_line == generated_line
+            && (self.generated_column == generated_column
+                || (self.generated_column < generated_column
+                    && self.last_generated_column.is_none_or(|last| generated_column < last)))
+    }
+}
+
+/// Original location information within a mapping.
+///
+/// Contains a source filename, an original line, and an original column. Might
+/// also contain an associated name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OriginalLocation {
+    /// The source filename.
+    pub source: u32,
+
+    /// The original line.
+    pub original_line: u32,
+
+    /// The original column.
+    pub original_column: u32,
+
+    /// The associated name, if any.
+    pub name: Option<u32>,
+}
+
+#[inline]
+fn is_mapping_separator(byte: u8) -> bool {
+    byte == b';' || byte == b','
+}
+
+/// Is the input at the end of a segment at the given position?
+#[inline]
+fn at_segment_end(input: &[u8], position: usize) -> bool {
+    match input.get(position) {
+        None => true,
+        Some(&byte) => is_mapping_separator(byte),
+    }
+}
+
+/// Decode the VLQ at the given position in the input, advancing the position
+/// past it, and add it to `previous`, which is the running value of `field`.
+#[inline]
+fn read_relative_vlq(
+    previous: &mut u32,
+    field: SegmentField,
+    input: &[u8],
+    position: &mut usize,
+) -> Result<(), Error> {
+    let decoded = vlq_decode::decode(input, position)?;
+    add_relative(previous, field, decoded)
+}
+
+/// Add a decoded relative value to `previous`, which is the running value of
+/// `field`, checking that the result is still a valid `u32`.
+#[inline]
+fn add_relative(previous: &mut u32, field: SegmentField, decoded: i64) -> Result<(), Error> {
+    let (new, overflowed) = (*previous as i64).overflowing_add(decoded);
+    if overflowed || new > (u32::MAX as i64) {
+        return Err(field.too_big());
+    }
+
+    if new < 0 {
+        return Err(field.negative());
+    }
+
+    *previous = new as u32;
+    Ok(())
+}
+
+/// Parse a source map's `"mappings"` string into a queryable `Mappings`
+/// structure.
+pub fn parse_mappings<O: Observer>(input: &[u8]) -> Result<Mappings<O>, Error> {
+    MappingsParser::new(input).finish()
+}
+
+/// Parse a source map's `"mappings"` string into an existing `Mappings`
+/// structure, replacing its contents but reusing its allocation for mappings.
+///
+/// This avoids repeatedly allocating (and, in `wasm32`, repeatedly growing
+/// memory for) large mappings when the same source map is re-parsed over and
+/// over, for example each time a watched file is rebuilt. Everything else
+/// about the mappings, such as attached string tables or payloads, is reset
+/// as if they were freshly parsed. On failure, the mappings are left empty.
+pub fn parse_mappings_into<O: Observer>(mappings: &mut Mappings<O>, input: &[u8]) -> Result<(), Error> {
+    let mut by_generated = mem::take(&mut mappings.by_generated);
+    *mappings = Mappings::default();
+    by_generated.clear();
+    by_generated.reserve(scan::count_segments(input));
+
+    let mut parser = MappingsParser::with_capacity(input, 0);
+    parser.by_generated = by_generated;
+    match parser.finish_parsing() {
+        Ok(parsed) => {
+            *mappings = parsed;
+            Ok(())
+        }
+        Err(e) => {
+            mappings.by_generated = mem::take(&mut parser.by_generated);
+            mappings.by_generated.clear();
+            Err(e)
+        }
+    }
+}
+
+/// Parse a source map's `"mappings"` string into a queryable `Mappings`
+/// structure, using every thread in `rayon`'s global thread pool.
+///
+/// The string is split into chunks of whole generated lines, whose VLQs are
+/// decoded in parallel. Because the source, original line, original column,
+/// and name fields are relative to the previous segment's, even across lines,
+/// a quick sequential pass then works out each chunk's starting values before
+/// the chunks' mappings are resolved and sorted in parallel too. The result,
+/// including which error is reported for malformed input, is the same as
+/// `parse_mappings`'s. Strings too small to benefit are parsed sequentially.
+///
+/// Only available with the `rayon` feature.
+#[cfg(feature = "rayon")]
+pub fn parse_mappings_parallel<O: Observer>(input: &[u8]) -> Result<Mappings<O>, Error> {
+    if !parallel::worth_it(input) {
+        return parse_mappings(input);
+    }
+
+    let _observer = O::ParseMappings::default();
+    parallel::parse(input).map(Mappings::from_sorted)
+}
+
+/// Parse a source map's `"mappings"` string, given as a sequence of chunks,
+/// into a queryable `Mappings` structure.
+///
+/// This avoids concatenating a huge `"mappings"` string that arrives in pieces,
+/// for example streamed over the network, into one contiguous buffer first.
+/// Only a segment that straddles the boundary between chunks is copied.
+pub fn parse_mappings_chunked<'a, O, I>(chunks: I) -> Result<Mappings<O>, Error>
+where
+    O: Observer,
+    I: IntoIterator<Item = &'a [u8]>,
+{
+    let mut parser = MappingsParser::<O>::with_capacity(&[], 0);
+
+    {
+        let _observer = O::ParseMappings::default();
+
+        // The bytes since the last separator, which might be the start of a
+        // segment that continues into the next chunk.
+        let mut pending = vec![];
+        let mut position = 0;
+        for chunk in chunks {
+            let split = match scan::rfind(chunk, scan::Separators::Segments) {
+                Some(i) => i + 1,
+                None => {
+                    pending.extend_from_slice(chunk);
+                    continue;
+                }
+            };
+
+            let (complete, rest) = chunk.split_at(split);
+            if pending.is_empty() {
+                position = parser.parse_piece(complete, position)?;
+            } else {
+                pending.extend_from_slice(complete);
+                position = parser.parse_piece(&pending, position)?;
+                pending.clear();
+            }
+            pending.extend_from_slice(rest);
+        }
+        parser.parse_piece(&pending, position)?;
+    }
+
+    parser.finish()
+}
+
+/// Parse a source map's `"mappings"` string into a queryable `Mappings`
+/// structure, as configured by the given parse options.
+pub fn parse_mappings_with_options<O: Observer>(
+    input: &[u8],
+    options: &ParseOptions,
+) -> Result<Mappings<O>, Error> {
+    MappingsParser::with_options(input, options).finish()
+}
 
-    /// The end column of this mapping's generated location span.
-    ///
-    /// Before `Mappings::computed_column_spans` has been called, this is always
-    /// `None`. After `Mappings::computed_column_spans` has been called, it
-    /// either contains `Some` column at which the generated location ends
-    /// (exclusive), or it contains `None` if it spans until the end of the
-    /// generated line.
-    pub last_generated_column: Option<u32>,
+/// Parse a source map's `"mappings"` string, skipping over any malformed parts
+/// rather than failing, and return both the mappings that could be parsed and
+/// what was skipped.
+///
+/// When a segment is malformed, the rest of its generated line is skipped, and
+/// parsing resumes with the next generated line. Generated locations after the
+/// skipped bytes are still exact, but since original locations are encoded
+/// relative to the previous segment's, they may be off.
+///
+/// This is `parse_mappings_with_recovery` with `Recovery::SkipLine`.
+pub fn parse_mappings_lenient<O: Observer>(input: &[u8]) -> (Mappings<O>, Vec<ParseIssue>) {
+    parse_mappings_with_recovery(input, Recovery::SkipLine)
+}
 
-    /// The original location information, if any.
-    pub original: Option<OriginalLocation>,
+/// Parse a source map's `"mappings"` string, skipping over any malformed parts
+/// as directed by `recovery` rather than failing, and return both the mappings
+/// that could be parsed and what was skipped.
+pub fn parse_mappings_with_recovery<O: Observer>(
+    input: &[u8],
+    recovery: Recovery,
+) -> (Mappings<O>, Vec<ParseIssue>) {
+    let mut parser = MappingsParser::with_recovery(input, recovery);
+    let mappings = unwrap(parser.finish_parsing().ok());
+    (mappings, mem::take(&mut parser.issues))
 }
 
-impl Default for Mapping {
-    #[inline]
-    fn default() -> Mapping {
-        Mapping {
-            generated_line: 0,
-            generated_column: 0,
-            last_generated_column: None,
-            original: None,
+/// Do the two given `"mappings"` strings describe the same set of mappings?
+///
+/// The strings are decoded in lockstep, one generated line at a time, without
+/// materializing either of them completely. Differences that don't change the
+/// decoded mappings, such as redundant separators, the order of segments within
+/// a generated line, or non-canonical VLQ encodings, are ignored.
+pub fn mappings_equivalent(a: &[u8], b: &[u8]) -> Result<bool, Error> {
+    // How many segments to decode from each string before comparing the
+    // generated lines that both have finished.
+    const SEGMENTS_PER_STEP: usize = 1024;
+
+    let mut a = MappingsParser::<()>::with_capacity(a, 0);
+    let mut b = MappingsParser::<()>::with_capacity(b, 0);
+
+    loop {
+        let a_done = a.parse_step(SEGMENTS_PER_STEP)?;
+        let b_done = b.parse_step(SEGMENTS_PER_STEP)?;
+        if a_done {
+            a.finish_last_generated_line();
+        }
+        if b_done {
+            b.finish_last_generated_line();
+        }
+
+        let finished = cmp::min(a.generated_line_start_index, b.generated_line_start_index);
+        if a.by_generated[..finished] != b.by_generated[..finished] {
+            return Ok(false);
+        }
+        a.discard_finished(finished);
+        b.discard_finished(finished);
+
+        if a_done && b_done {
+            return Ok(a.by_generated.is_empty() && b.by_generated.is_empty());
         }
     }
 }
 
-/// Original location information within a mapping.
+/// A resumable parser for a source map's `"mappings"` string.
 ///
-/// Contains a source filename, an original line, and an original column. Might
-/// also contain an associated name.
-#[derive(Clone, Debug, PartialEq, Eq)]
-pub struct OriginalLocation {
-    /// The source filename.
-    pub source: u32,
+/// Where `parse_mappings` parses the whole string in one go, a
+/// `MappingsParser` can instead be driven in bounded slices of work with
+/// `parse_step`. This lets embedders that must parse on the main thread keep
+/// their event loop responsive while parsing a large source map.
+#[derive(Debug)]
+pub struct MappingsParser<'a, O = ()>
+where
+    O: Observer,
+{
+    input: &'a [u8],
+    position: usize,
+    error: Option<Error>,
 
-    /// The original line.
-    pub original_line: u32,
+    // Where the error that stopped parsing happened, if one did.
+    error_detail: Option<ErrorDetail>,
 
-    /// The original column.
-    pub original_column: u32,
+    // The field of the current segment that failed to decode, if any.
+    error_field: Option<SegmentField>,
 
-    /// The associated name, if any.
-    pub name: Option<u32>,
-}
+    // Where the segment currently being read begins within `input`.
+    segment_start: usize,
 
-#[inline]
-fn is_mapping_separator(byte: u8) -> bool {
-    byte == b';' || byte == b','
+    options: ParseOptions,
+
+    // The malformed parts of `input` that were skipped, when parsing leniently.
+    issues: Vec<ParseIssue>,
+
+    // The running values that each segment's fields are relative to.
+    generated_line: u32,
+    generated_column: u32,
+    original_line: u32,
+    original_column: u32,
+    source: u32,
+    name: u32,
+
+    generated_line_start_index: usize,
+    by_generated: Vec<Mapping>,
+
+    // How many mappings have been parsed so far, including any that were
+    // discarded since.
+    mappings_parsed: usize,
+
+    // Whether the current generated line's mappings have been in sorted order
+    // so far, in which case they needn't be sorted once the line is finished.
+    line_is_sorted: bool,
+
+    // How many segments have been seen so far, including malformed ones.
+    segments_seen: usize,
+
+    // The index of the segment that each of `by_generated` was parsed from,
+    // when recording them.
+    segment_indices: Option<Vec<usize>>,
+
+    // The start and end within `input` of the segment that each of
+    // `by_generated` was parsed from, when recording them.
+    segment_ranges: Option<Vec<(u32, u32)>>,
+
+    sparse: Option<SparseRetention>,
+    observer: PhantomData<O>,
 }
 
-#[inline]
-fn read_relative_vlq<B>(previous: &mut u32, input: &mut B) -> Result<(), Error>
-where
-    B: Iterator<Item = u8>,
-{
-    let decoded = vlq::decode(input)?;
-    let (new, overflowed) = (*previous as i64).overflowing_add(decoded);
-    if overflowed || new > (u32::MAX as i64) {
-        return Err(Error::UnexpectedlyBigNumber);
+impl<'a, O: Observer> MappingsParser<'a, O> {
+    /// Construct a new parser for the given `"mappings"` string.
+    pub fn new(input: &'a [u8]) -> MappingsParser<'a, O> {
+        // Counting the segments up front is much cheaper than decoding them,
+        // and lets us allocate exactly rather than for the worst case, where
+        // every other byte is a separator.
+        MappingsParser::with_capacity(input, scan::count_segments(input))
     }
 
-    if new < 0 {
-        return Err(Error::UnexpectedNegativeNumber);
+    #[inline]
+    fn with_capacity(input: &'a [u8], capacity: usize) -> MappingsParser<'a, O> {
+        MappingsParser {
+            input,
+            position: 0,
+            error: None,
+            error_detail: None,
+            error_field: None,
+            segment_start: 0,
+            options: ParseOptions::default(),
+            issues: vec![],
+            generated_line: 0,
+            generated_column: 0,
+            original_line: 0,
+            original_column: 0,
+            source: 0,
+            name: 0,
+            generated_line_start_index: 0,
+            by_generated: Vec::with_capacity(capacity),
+            mappings_parsed: 0,
+            line_is_sorted: true,
+            segments_seen: 0,
+            segment_indices: None,
+            segment_ranges: None,
+
+            sparse: None,
+            observer: PhantomData,
+        }
     }
 
-    *previous = new as u32;
-    Ok(())
-}
+    /// Construct a new parser for the given `"mappings"` string that skips over
+    /// malformed parts rather than failing, as `parse_mappings_lenient` does.
+    ///
+    /// What was skipped so far can be inspected with `issues`.
+    pub fn lenient(input: &'a [u8]) -> MappingsParser<'a, O> {
+        MappingsParser::with_recovery(input, Recovery::SkipLine)
+    }
 
-/// Parse a source map's `"mappings"` string into a queryable `Mappings`
-/// structure.
-pub fn parse_mappings<O: Observer>(input: &[u8]) -> Result<Mappings<O>, Error> {
-    let _observer = O::ParseMappings::default();
+    /// Construct a new parser for the given `"mappings"` string that skips over
+    /// malformed parts as directed by `recovery` rather than failing.
+    ///
+    /// What was skipped so far can be inspected with `issues`.
+    pub fn with_recovery(input: &'a [u8], recovery: Recovery) -> MappingsParser<'a, O> {
+        let options = ParseOptions {
+            recovery: Some(recovery),
+            ..Default::default()
+        };
+        MappingsParser::with_options(input, &options)
+    }
+
+    /// Construct a new parser for the given `"mappings"` string, configured by
+    /// the given parse options.
+    pub fn with_options(input: &'a [u8], options: &ParseOptions) -> MappingsParser<'a, O> {
+        let capacity = cmp::min(scan::count_segments(input), options.max_mappings.unwrap_or(usize::MAX));
+        let mut parser = MappingsParser::with_capacity(input, capacity);
+        parser.options = *options;
+        if options.record_segment_indices {
+            parser.segment_indices = Some(Vec::with_capacity(capacity));
+        }
+        if options.record_segment_ranges {
+            debug_assert!(input.len() <= u32::MAX as usize);
+            parser.segment_ranges = Some(Vec::with_capacity(capacity));
+        }
+        parser
+    }
+
+    /// Get the malformed parts of the `"mappings"` string that were skipped so
+    /// far. This is always empty unless the parser was configured with a
+    /// `Recovery`.
+    #[inline]
+    pub fn issues(&self) -> &[ParseIssue] {
+        &self.issues
+    }
+
+    /// Construct a new parser for the given `"mappings"` string that only
+    /// retains the mappings needed to answer `original_location_for` queries
+    /// at the given generated positions.
+    ///
+    /// See `parse_mappings_for_positions` for details.
+    pub fn for_positions(input: &'a [u8], positions: &[(u32, u32)]) -> MappingsParser<'a, O> {
+        let mut positions = positions.to_vec();
+        positions.sort_unstable();
+        positions.dedup();
+
+        let mut parser = MappingsParser::with_capacity(input, 0);
+        parser.sparse = Some(SparseRetention {
+            positions,
+            next_position: 0,
+            pending_tail: None,
+            need_next_head: false,
+        });
+        parser
+    }
+
+    /// Parse up to `max_segments` more segments of the `"mappings"` string.
+    ///
+    /// Returns `Ok(true)` once the whole string has been parsed, and `Ok(false)`
+    /// if there is more work left to do. Once an error has been returned, every
+    /// subsequent call returns that same error.
+    pub fn parse_step(&mut self, max_segments: usize) -> Result<bool, Error> {
+        let _observer = O::ParseMappings::default();
+        self.parse_segments(max_segments)
+    }
+
+    /// Parse whatever remains of the `"mappings"` string, and then finish
+    /// constructing the queryable `Mappings` structure.
+    pub fn finish(mut self) -> Result<Mappings<O>, Error> {
+        self.finish_parsing()
+    }
+
+    /// Like `finish`, but on failure, describe where in the `"mappings"`
+    /// string parsing failed.
+    pub fn finish_detailed(mut self) -> Result<Mappings<O>, ErrorDetail> {
+        self.finish_parsing().map_err(|_| unwrap(self.error_detail))
+    }
+
+    /// Describe where in the `"mappings"` string parsing failed, if it has.
+    #[inline]
+    pub fn error_detail(&self) -> Option<ErrorDetail> {
+        self.error_detail
+    }
+
+    fn finish_parsing(&mut self) -> Result<Mappings<O>, Error> {
+        let _observer = O::ParseMappings::default();
+
+        self.parse_segments(usize::MAX)?;
+        self.finish_last_generated_line();
+        if let Some(ref mut sparse) = self.sparse {
+            sparse.finish(&mut self.by_generated);
+        }
+
+        // Recovering from malformed segments and only retaining some mappings
+        // can both leave spare capacity.
+        if self.options.recovery.is_some() || self.sparse.is_some() {
+            self.by_generated.shrink_to_fit();
+            if let Some(ref mut segment_indices) = self.segment_indices {
+                segment_indices.shrink_to_fit();
+            }
+            if let Some(ref mut segment_ranges) = self.segment_ranges {
+                segment_ranges.shrink_to_fit();
+            }
+        }
+
+        let mut mappings = Mappings::from_sorted(mem::take(&mut self.by_generated));
+        mappings.segment_indices = self.segment_indices.take();
+        mappings.segment_ranges = self.segment_ranges.take();
+        Ok(mappings)
+    }
+
+    fn parse_segments(&mut self, max_segments: usize) -> Result<bool, Error> {
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+
+        let mut remaining_segments = max_segments;
+        loop {
+            let input = self.input;
+            let mut position = self.position;
+            let result = self.parse_segments_from(input, &mut position, 0, &mut remaining_segments);
+            self.position = position;
+
+            match result {
+                Err(e) if self.options.recovery.is_some() && e != Error::TooManyMappings => {
+                    self.skip_malformed_segment(e)
+                }
+                Err(e) => {
+                    self.error = Some(e);
+                    self.error_detail = Some(ErrorDetail {
+                        error: e,
+                        offset: self.segment_start,
+                        generated_line: self.generated_line,
+                        segment_index: self.segments_seen - 1,
+                        field: self.error_field.take(),
+                    });
+                    return Err(e);
+                }
+                Ok(finished) => return Ok(finished),
+            }
+        }
+    }
+
+    /// Parse the whole of `piece`, which is part of a `"mappings"` string given
+    /// in chunks and starts at the given position, and return the position just
+    /// past its end.
+    ///
+    /// The piece must end with a separator, or else be the last of the string.
+    fn parse_piece(&mut self, piece: &[u8], position: usize) -> Result<usize, Error> {
+        self.reserve_for(piece);
+        let mut remaining_segments = usize::MAX;
+        self.parse_segments_from(piece, &mut 0, position, &mut remaining_segments)?;
+        Ok(position + piece.len())
+    }
+
+    /// Make room for the mappings in `piece`, when the size of the whole
+    /// `"mappings"` string isn't known up front.
+    ///
+    /// Letting `Vec` double its capacity whenever it runs out would mean that
+    /// reallocating briefly needs three times the memory of the mappings so
+    /// far, and that up to half of the final allocation is wasted. Growing by
+    /// a quarter at a time keeps both of those much lower, while still only
+    /// copying each mapping a constant number of times on average.
+    #[inline]
+    fn reserve_for(&mut self, piece: &[u8]) {
+        let needed = scan::count_segments(piece);
+        if self.by_generated.capacity() - self.by_generated.len() < needed {
+            let growth = cmp::max(needed, self.by_generated.len() / 4);
+            self.by_generated.reserve_exact(growth);
+        }
+    }
 
-    let mut generated_line = 0;
-    let mut generated_column = 0;
-    let mut original_line = 0;
-    let mut original_column = 0;
-    let mut source = 0;
-    let mut name = 0;
-    let mut generated_line_start_index = 0;
-
-    let mut mappings = Mappings::default();
-
-    // `input.len() / 2` is the upper bound on how many mappings the string
-    // might contain. There would be some sequence like `A,A,A,...` or
-    // `A;A;A;...`.
-    let mut by_generated = Vec::with_capacity(input.len() / 2);
-
-    let mut input = input.iter().cloned().peekable();
-
-    while let Some(byte) = input.peek().cloned() {
-        match byte {
-            b';' => {
-                generated_line += 1;
-                generated_column = 0;
-                unwrap(input.next());
-
-                // Because mappings are sorted with regards to generated line
-                // due to the encoding format, and sorting by generated location
-                // starts by comparing generated line, we can sort only the
-                // smaller subsequence of this generated line's mappings and end
-                // up with a fully sorted array.
-                if generated_line_start_index < by_generated.len() {
-                    let _observer = O::SortByGeneratedLocation::default();
-                    by_generated[generated_line_start_index..].sort_unstable_by(comparators::ByGeneratedTail::compare);
-                    generated_line_start_index = by_generated.len();
+    /// Skip the malformed segment at `segment_start`, and possibly the rest of
+    /// its generated line, according to the `Recovery`, recording it as an
+    /// issue.
+    fn skip_malformed_segment(&mut self, error: Error) {
+        let start = self.segment_start;
+        let recovery = unwrap(self.options.recovery);
+        let separators = match recovery {
+            Recovery::SkipLine => scan::Separators::Lines,
+            Recovery::SkipSegment => scan::Separators::Segments,
+        };
+        let end = scan::find(&self.input[start..], separators).map_or(self.input.len(), |i| start + i);
+
+        self.error_field = None;
+
+        // The malformed segment itself was already counted.
+        self.segments_seen += scan::count_segments(&self.input[start..end]).saturating_sub(1);
+
+        self.position = end;
+        self.issues.push(ParseIssue {
+            error,
+            skipped: start..end,
+        });
+    }
+
+    /// Parse segments from `input`, starting at and advancing `position`.
+    ///
+    /// The `input` begins at the position `base` within the whole `"mappings"`
+    /// string.
+    #[inline]
+    fn parse_segments_from(
+        &mut self,
+        input: &[u8],
+        position: &mut usize,
+        base: usize,
+        remaining_segments: &mut usize,
+    ) -> Result<bool, Error> {
+        while let Some(&byte) = input.get(*position) {
+            match byte {
+                b';' => {
+                    let finished_line = self.generated_line;
+                    self.generated_line += 1;
+                    self.generated_column = 0;
+                    *position += 1;
+                    self.finish_generated_line(finished_line);
+                }
+                b',' => {
+                    *position += 1;
+                }
+                _ => {
+                    if *remaining_segments == 0 {
+                        return Ok(false);
+                    }
+                    *remaining_segments -= 1;
+
+                    self.segment_start = base + *position;
+                    let segment_index = self.segments_seen;
+                    self.segments_seen += 1;
+                    let mut mapping = self.read_segment(input, position)?;
+                    if self.options.generated_only {
+                        mapping.original = None;
+                    } else if self.options.skip_names {
+                        if let Some(ref mut original) = mapping.original {
+                            original.name = None;
+                        }
+                    }
+
+                    self.mappings_parsed += 1;
+                    if self.options.max_mappings.is_some_and(|max| self.mappings_parsed > max) {
+                        return Err(Error::TooManyMappings);
+                    }
+
+                    if self.line_is_sorted {
+                        if let Some(prev) = self.by_generated[self.generated_line_start_index..].last() {
+                            self.line_is_sorted =
+                                comparators::ByGeneratedTail::compare(prev, &mapping) != cmp::Ordering::Greater;
+                        }
+                    }
+                    self.by_generated.push(mapping);
+                    if let Some(ref mut segment_indices) = self.segment_indices {
+                        segment_indices.push(segment_index);
+                    }
+                    if let Some(ref mut segment_ranges) = self.segment_ranges {
+                        segment_ranges.push((self.segment_start as u32, (base + *position) as u32));
+                    }
                 }
             }
-            b',' => {
-                unwrap(input.next());
+        }
+
+        Ok(true)
+    }
+
+    /// Because mappings are sorted with regards to generated line due to the
+    /// encoding format, and sorting by generated location starts by comparing
+    /// generated line, we can sort only the smaller subsequence of each
+    /// generated line's mappings and end up with a fully sorted array. Most
+    /// toolchains emit each line's mappings in order already, and such lines
+    /// aren't sorted at all. The stable sort used for the rest finds the runs
+    /// that are already in order and merges them, so a line with only a few
+    /// out of order segments costs little more than a linear pass.
+    #[inline]
+    fn finish_generated_line(&mut self, generated_line: u32) {
+        let start = self.generated_line_start_index;
+        if self.options.assume_sorted {
+            debug_assert!(self.by_generated[start..].windows(2).all(|pair| {
+                comparators::ByGeneratedTail::compare(&pair[0], &pair[1]) != cmp::Ordering::Greater
+            }));
+        } else if !self.line_is_sorted {
+            let _observer = O::SortByGeneratedLocation::default();
+            if self.segment_indices.is_none() && self.segment_ranges.is_none() {
+                sort::by_comparator::<comparators::ByGeneratedTail, _>(&mut self.by_generated[start..]);
+            } else {
+                // Keep the recorded segment information in the same order.
+                let line = &mut self.by_generated[start..];
+                let order = sort::order_by_comparator::<comparators::ByGeneratedTail, _>(line);
+                sort::apply_order(line, &order);
+                if let Some(ref mut segment_indices) = self.segment_indices {
+                    sort::apply_order(&mut segment_indices[start..], &order);
+                }
+                if let Some(ref mut segment_ranges) = self.segment_ranges {
+                    sort::apply_order(&mut segment_ranges[start..], &order);
+                }
             }
-            _ => {
-                let mut mapping = Mapping::default();
-                mapping.generated_line = generated_line;
+        }
+        self.line_is_sorted = true;
+
+        if let Some(ref mut sparse) = self.sparse {
+            sparse.retain_line(&mut self.by_generated, start, generated_line);
+        }
+
+        self.generated_line_start_index = self.by_generated.len();
+    }
+
+    #[inline]
+    fn finish_last_generated_line(&mut self) {
+        let last_line = self.generated_line;
+        self.finish_generated_line(last_line);
+    }
 
-                // First is a generated column that is always present.
-                read_relative_vlq(&mut generated_column, &mut input)?;
-                mapping.generated_column = generated_column as u32;
+    /// Throw away the first `count` parsed mappings, which must all be within
+    /// finished generated lines.
+    #[inline]
+    fn discard_finished(&mut self, count: usize) {
+        debug_assert!(count <= self.generated_line_start_index);
+        self.by_generated.drain(..count);
+        self.generated_line_start_index -= count;
+    }
+
+    #[inline]
+    fn read_segment(&mut self, input: &[u8], position: &mut usize) -> Result<Mapping, Error> {
+        let mut mapping = Mapping {
+            generated_line: self.generated_line,
+            ..Default::default()
+        };
 
-                // Read source, original line, and original column if the
-                // mapping has them.
-                mapping.original = if input.peek().cloned().map_or(true, is_mapping_separator) {
+        // First is a generated column that is always present.
+        read_relative_vlq(&mut self.generated_column, SegmentField::GeneratedColumn, input, position)
+            .map_err(|e| self.failed_in(SegmentField::GeneratedColumn, e))?;
+        mapping.generated_column = self.generated_column;
+
+        // Read source, original line, and original column if the mapping has
+        // them.
+        let strict = self.options.strict;
+        let check_length = |complete: bool| {
+            if strict && !complete {
+                Err(Error::InvalidSegmentLength)
+            } else {
+                Ok(())
+            }
+        };
+        mapping.original = if at_segment_end(input, *position) {
+            None
+        } else {
+            read_relative_vlq(&mut self.source, SegmentField::Source, input, position)
+                .map_err(|e| self.failed_in(SegmentField::Source, e))?;
+            check_length(!at_segment_end(input, *position))?;
+            read_relative_vlq(&mut self.original_line, SegmentField::OriginalLine, input, position)
+                .map_err(|e| self.failed_in(SegmentField::OriginalLine, e))?;
+            check_length(!at_segment_end(input, *position))?;
+            read_relative_vlq(&mut self.original_column, SegmentField::OriginalColumn, input, position)
+                .map_err(|e| self.failed_in(SegmentField::OriginalColumn, e))?;
+
+            Some(OriginalLocation {
+                source: self.source,
+                original_line: self.original_line,
+                original_column: self.original_column,
+                name: if at_segment_end(input, *position) {
                     None
                 } else {
-                    read_relative_vlq(&mut source, &mut input)?;
-                    read_relative_vlq(&mut original_line, &mut input)?;
-                    read_relative_vlq(&mut original_column, &mut input)?;
+                    read_relative_vlq(&mut self.name, SegmentField::Name, input, position)
+                        .map_err(|e| self.failed_in(SegmentField::Name, e))?;
+                    check_length(at_segment_end(input, *position))?;
+                    Some(self.name)
+                },
+            })
+        };
+
+        Ok(mapping)
+    }
+
+    /// Note that decoding the given field failed, for `ErrorDetail`.
+    #[cold]
+    fn failed_in(&mut self, field: SegmentField, error: Error) -> Error {
+        self.error_field = Some(field);
+        error
+    }
+}
+
+/// Parse a source map's `"mappings"` string, but only retain the mappings needed
+/// to answer `original_location_for` queries at the given generated positions.
+///
+/// For each position, the mappings that either bias could find are retained,
+/// so querying the result at one of these positions gives the same answer as
+/// querying the fully parsed mappings would. Everything else is decoded (the
+/// format requires it) but thrown away. This is much cheaper than a full parse
+/// when symbolicating a handful of stack frames against a huge source map.
+pub fn parse_mappings_for_positions<O: Observer>(
+    input: &[u8],
+    positions: &[(u32, u32)],
+) -> Result<Mappings<O>, Error> {
+    MappingsParser::for_positions(input, positions).finish()
+}
+
+/// The state for only retaining the mappings that are needed to answer queries
+/// at a set of generated positions, as each generated line is parsed.
+#[derive(Debug)]
+struct SparseRetention {
+    // Sorted and deduplicated.
+    positions: Vec<(u32, u32)>,
+    next_position: usize,
+
+    // The last mapping of the most recent non-empty generated line, if it
+    // wasn't already retained. A greatest lower bound search for a position
+    // before the first mapping on its line slides back to this mapping.
+    pending_tail: Option<Mapping>,
+
+    // Whether to retain the first mapping of the next non-empty generated
+    // line, because a least upper bound search for some position after the
+    // last mapping on its line slides forward to it.
+    need_next_head: bool,
+}
+
+impl SparseRetention {
+    /// Throw away the mappings in `by_generated[start..]`, which are the sorted
+    /// mappings for `generated_line`, that aren't needed for any position.
+    fn retain_line(&mut self, by_generated: &mut Vec<Mapping>, start: usize, generated_line: u32) {
+        let mut keep = vec![];
+        let mut need_tail = false;
+
+        {
+            let line = &by_generated[start..];
+
+            if self.need_next_head && !line.is_empty() {
+                keep.push(0..1);
+                self.need_next_head = false;
+            }
+
+            while let Some(&(position_line, column)) = self.positions.get(self.next_position) {
+                if position_line > generated_line {
+                    break;
+                }
+                self.next_position += 1;
+                debug_assert_eq!(position_line, generated_line);
+
+                // All the mappings at the greatest lower bound's column.
+                let end = line.partition_point(|m| m.generated_column <= column);
+                if end == 0 {
+                    need_tail = true;
+                } else {
+                    let lower = line[end - 1].generated_column;
+                    keep.push(line.partition_point(|m| m.generated_column < lower)..end);
+                }
+
+                // All the mappings at the least upper bound's column.
+                let begin = line.partition_point(|m| m.generated_column < column);
+                if begin == line.len() {
+                    self.need_next_head = true;
+                } else {
+                    let upper = line[begin].generated_column;
+                    keep.push(begin..line.partition_point(|m| m.generated_column <= upper));
+                }
+            }
+        }
+
+        let line_len = by_generated.len() - start;
+        let new_tail = by_generated[start..].last().map(|tail| {
+            let retained = keep.iter().any(|r| r.end == line_len);
+            (tail.clone(), retained)
+        });
+
+        // Compact the retained mappings to the front of this line's mappings.
+        keep.sort_unstable_by_key(|r| r.start);
+        let mut write = start;
+        let mut retained_through = 0;
+        for range in keep {
+            for i in cmp::max(range.start, retained_through)..range.end {
+                by_generated.swap(write, start + i);
+                write += 1;
+            }
+            retained_through = cmp::max(retained_through, range.end);
+        }
+        by_generated.truncate(write);
 
-                    Some(OriginalLocation {
-                        source: source,
-                        original_line: original_line,
-                        original_column: original_column,
-                        name: if input.peek().cloned().map_or(true, is_mapping_separator) {
-                            None
-                        } else {
-                            read_relative_vlq(&mut name, &mut input)?;
-                            Some(name)
-                        },
-                    })
-                };
+        if need_tail {
+            if let Some(tail) = self.pending_tail.take() {
+                by_generated.insert(start, tail);
+            }
+        }
+        if let Some((tail, retained)) = new_tail {
+            self.pending_tail = if retained { None } else { Some(tail) };
+        }
+    }
 
-                by_generated.push(mapping);
+    /// Retain whatever is needed for positions after the last generated line.
+    fn finish(&mut self, by_generated: &mut Vec<Mapping>) {
+        if self.next_position < self.positions.len() {
+            if let Some(tail) = self.pending_tail.take() {
+                by_generated.push(tail);
             }
         }
     }
+}
+
+/// Incrementally constructs a queryable `Mappings` structure, one mapping at a
+/// time.
+///
+/// This is for producers, such as bundlers and transpilers, that want to build
+/// mappings directly rather than encoding a `"mappings"` string only to parse
+/// it again. Mappings are cheapest to add in generated order, but may be added
+/// in any order.
+#[derive(Debug)]
+pub struct MappingsBuilder<O = ()>
+where
+    O: Observer,
+{
+    by_generated: Vec<Mapping>,
+
+    // Everything before this index is sorted by generated location, and comes
+    // before any mapping on the current generated line.
+    sorted_until: usize,
+    generated_line: u32,
+
+    // Was a mapping added for a generated line before the current one?
+    out_of_order: bool,
+
+    observer: PhantomData<O>,
+}
+
+impl<O: Observer> Default for MappingsBuilder<O> {
+    #[inline]
+    fn default() -> MappingsBuilder<O> {
+        MappingsBuilder::with_capacity(0)
+    }
+}
+
+impl<O: Observer> MappingsBuilder<O> {
+    /// Construct a new, empty builder.
+    #[inline]
+    pub fn new() -> MappingsBuilder<O> {
+        Default::default()
+    }
+
+    /// Construct a new, empty builder with room for `capacity` mappings.
+    #[inline]
+    pub fn with_capacity(capacity: usize) -> MappingsBuilder<O> {
+        MappingsBuilder {
+            by_generated: Vec::with_capacity(capacity),
+            sorted_until: 0,
+            generated_line: 0,
+            out_of_order: false,
+            observer: PhantomData,
+        }
+    }
+
+    /// Add a mapping at the given generated location, with the given original
+    /// location information, if any.
+    pub fn add_mapping(
+        &mut self,
+        generated_line: u32,
+        generated_column: u32,
+        original: Option<OriginalLocation>,
+    ) {
+        if generated_line > self.generated_line {
+            self.sort_current_line();
+            self.generated_line = generated_line;
+        } else if generated_line < self.generated_line {
+            self.out_of_order = true;
+        }
+
+        self.by_generated.push(Mapping {
+            generated_line,
+            generated_column,
+            last_generated_column: None,
+            original,
+        });
+    }
+
+    /// The number of mappings added so far.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.by_generated.len()
+    }
+
+    /// Have no mappings been added yet?
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.by_generated.is_empty()
+    }
 
-    if generated_line_start_index < by_generated.len() {
-        let _observer = O::SortByGeneratedLocation::default();
-        by_generated[generated_line_start_index..].sort_unstable_by(comparators::ByGeneratedTail::compare);
+    /// Finish constructing the queryable `Mappings` structure.
+    pub fn finish(mut self) -> Mappings<O> {
+        if self.out_of_order {
+            let _observer = O::SortByGeneratedLocation::default();
+            sort::by_comparator::<comparators::ByGeneratedLocation, _>(&mut self.by_generated);
+        } else {
+            self.sort_current_line();
+        }
+        Mappings::from_sorted(self.by_generated)
     }
 
-    mappings.by_generated = by_generated;
-    Ok(mappings)
+    #[inline]
+    fn sort_current_line(&mut self) {
+        if self.out_of_order {
+            // Everything gets sorted at the end anyways.
+            return;
+        }
+
+        let start = self.sorted_until;
+        if start < self.by_generated.len() {
+            let _observer = O::SortByGeneratedLocation::default();
+            sort::by_comparator::<comparators::ByGeneratedTail, _>(&mut self.by_generated[start..]);
+        }
+        self.sorted_until = self.by_generated.len();
+    }
 }
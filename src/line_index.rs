@@ -0,0 +1,234 @@
+//! An index over the mappings sorted by generated location, for speeding up
+//! searches by generated location.
+//!
+//! The start of each generated line's mappings is recorded, so that searches
+//! only have to look within the queried line, and getting all of a line's
+//! mappings is constant time.
+//!
+//! Minified bundles often put hundreds of thousands of mappings on a single
+//! generated line. Rather than binary searching that whole line, each such line
+//! is split into buckets of columns that each hold only a few mappings, and a
+//! search only has to look within the queried column's bucket.
+
+use super::Mapping;
+use std::cmp;
+use std::ops::Range;
+
+/// Generated lines with at least this many mappings get column buckets.
+const LARGE_LINE_THRESHOLD: usize = 1024;
+
+/// The average number of mappings that each column bucket should hold.
+const MAPPINGS_PER_BUCKET: usize = 16;
+
+/// Don't record line starts if there would be more than this many per
+/// mapping, which only happens when line numbers are very sparse. This also
+/// keeps a single mapping at a huge generated line from allocating a huge index.
+const MAX_LINE_STARTS_PER_MAPPING: u64 = 4;
+
+/// Are line starts worth recording for the given number of generated lines and
+/// mappings?
+///
+/// Counted in `u64`, since there are `u32::MAX + 1` possible generated lines,
+/// which doesn't fit in a 32-bit `usize`.
+#[inline]
+fn worth_line_starts(num_lines: u64, num_mappings: usize) -> bool {
+    num_lines <= (num_mappings as u64).saturating_mul(MAX_LINE_STARTS_PER_MAPPING)
+}
+
+#[derive(Clone, Debug, Default)]
+pub(crate) struct GeneratedLineIndex {
+    // `line_starts[l]` is the index of the first mapping whose generated line
+    // is at least `l`. The last entry is the number of mappings. Empty if line
+    // numbers are too sparse to be worth indexing this way.
+    line_starts: Vec<usize>,
+
+    // Sorted by generated line.
+    large_lines: Vec<ColumnBuckets>,
+}
+
+#[derive(Clone, Debug)]
+struct ColumnBuckets {
+    generated_line: u32,
+
+    // This line's range within the mappings sorted by generated location.
+    start: usize,
+    end: usize,
+
+    min_column: u32,
+    bucket_width: u32,
+
+    // `bucket_starts[b]` is the index, relative to `start`, of the first
+    // mapping whose generated column is at least `min_column + b *
+    // bucket_width`. The last entry is the line's length.
+    bucket_starts: Vec<u32>,
+}
+
+impl GeneratedLineIndex {
+    /// Build the index for the given mappings, which must be sorted by generated
+    /// location.
+    pub(crate) fn new(by_generated: &[Mapping]) -> GeneratedLineIndex {
+        let mut line_starts = vec![];
+        let num_lines = by_generated.last().map_or(0, |m| m.generated_line as u64 + 1);
+        if worth_line_starts(num_lines, by_generated.len()) {
+            // Bounded by a few times the number of mappings, so this fits.
+            line_starts.reserve_exact(num_lines as usize + 1);
+            for (i, m) in by_generated.iter().enumerate() {
+                while line_starts.len() <= m.generated_line as usize {
+                    line_starts.push(i);
+                }
+            }
+            line_starts.push(by_generated.len());
+        }
+
+        let mut large_lines = vec![];
+
+        let mut start = 0;
+        while start < by_generated.len() {
+            let generated_line = by_generated[start].generated_line;
+            let len = by_generated[start..]
+                .iter()
+                .take_while(|m| m.generated_line == generated_line)
+                .count();
+
+            if len >= LARGE_LINE_THRESHOLD {
+                large_lines.push(ColumnBuckets::new(by_generated, start, start + len));
+            }
+
+            start += len;
+        }
+        large_lines.shrink_to_fit();
+
+        GeneratedLineIndex {
+            line_starts,
+            large_lines,
+        }
+    }
+
+    /// Update the index after mappings were inserted into or removed from one
+    /// generated line, without rebuilding it.
+    ///
+    /// `by_generated` must already be updated, and `added` is how many mappings
+    /// the line gained, or lost if it is negative. Later lines' starts are
+    /// shifted, and only this line's column buckets are rebuilt.
+    pub(crate) fn line_changed(&mut self, by_generated: &[Mapping], generated_line: u32, added: isize) {
+        let line = generated_line as usize;
+        if let Some(old_len) = self.line_starts.last().cloned() {
+            let num_lines = self.line_starts.len() - 1;
+            if line < num_lines {
+                for start in &mut self.line_starts[line + 1..] {
+                    *start = shifted(*start, added);
+                }
+            } else if worth_line_starts(generated_line as u64 + 1, by_generated.len()) {
+                // Mappings were added to a new line after every indexed line,
+                // at the end of `by_generated`.
+                self.line_starts.pop();
+                self.line_starts.resize(line + 1, old_len);
+                self.line_starts.push(by_generated.len());
+            } else {
+                self.line_starts = vec![];
+            }
+        }
+
+        for buckets in &mut self.large_lines {
+            if buckets.generated_line > generated_line {
+                buckets.start = shifted(buckets.start, added);
+                buckets.end = shifted(buckets.end, added);
+            }
+        }
+
+        let start = by_generated.partition_point(|m| m.generated_line < generated_line);
+        let end = by_generated.partition_point(|m| m.generated_line <= generated_line);
+        let large = end - start >= LARGE_LINE_THRESHOLD;
+        match self.large_lines.binary_search_by_key(&generated_line, |b| b.generated_line) {
+            Ok(idx) if large => self.large_lines[idx] = ColumnBuckets::new(by_generated, start, end),
+            Ok(idx) => {
+                self.large_lines.remove(idx);
+            }
+            Err(idx) if large => self.large_lines.insert(idx, ColumnBuckets::new(by_generated, start, end)),
+            Err(_) => {}
+        }
+    }
+
+    /// Get the range of the mappings sorted by generated location that are on
+    /// the given generated line, or `None` if lines are not indexed.
+    #[inline]
+    pub(crate) fn line_range(&self, generated_line: u32) -> Option<Range<usize>> {
+        let last = *self.line_starts.last()?;
+        let line = generated_line as usize;
+        let next = line.checked_add(1).and_then(|next| self.line_starts.get(next));
+        match (self.line_starts.get(line), next) {
+            (Some(&start), Some(&end)) => Some(start..end),
+            _ => Some(last..last),
+        }
+    }
+
+    /// Narrow down the range of the mappings sorted by generated location that
+    /// must be searched to find the given generated location.
+    ///
+    /// Any mappings before the returned range are less than the location, and
+    /// any after it are greater. Returns `None` if nothing is indexed, and the
+    /// whole set of mappings must be searched.
+    #[inline]
+    pub(crate) fn search_range(&self, generated_line: u32, generated_column: u32) -> Option<Range<usize>> {
+        if !self.large_lines.is_empty() {
+            if let Ok(idx) = self.large_lines.binary_search_by_key(&generated_line, |b| b.generated_line) {
+                return Some(self.large_lines[idx].search_range(generated_column));
+            }
+        }
+
+        self.line_range(generated_line)
+    }
+}
+
+#[inline]
+fn shifted(idx: usize, added: isize) -> usize {
+    (idx as isize + added) as usize
+}
+
+impl ColumnBuckets {
+    fn new(by_generated: &[Mapping], start: usize, end: usize) -> ColumnBuckets {
+        let line = &by_generated[start..end];
+        let min_column = line[0].generated_column;
+        let max_column = line[line.len() - 1].generated_column;
+
+        let num_buckets = line.len().div_ceil(MAPPINGS_PER_BUCKET) as u64;
+        let span = (max_column - min_column) as u64 + 1;
+        let bucket_width = cmp::max(1, span.div_ceil(num_buckets)) as u32;
+
+        let mut bucket_starts = vec![];
+        let mut bucket_min = min_column as u64;
+        for (i, m) in line.iter().enumerate() {
+            while (m.generated_column as u64) >= bucket_min {
+                bucket_starts.push(i as u32);
+                bucket_min += bucket_width as u64;
+            }
+        }
+        bucket_starts.push(line.len() as u32);
+        bucket_starts.shrink_to_fit();
+
+        ColumnBuckets {
+            generated_line: line[0].generated_line,
+            start,
+            end,
+            min_column,
+            bucket_width,
+            bucket_starts,
+        }
+    }
+
+    #[inline]
+    fn search_range(&self, generated_column: u32) -> Range<usize> {
+        if generated_column < self.min_column {
+            return self.start..self.start;
+        }
+
+        let bucket = ((generated_column - self.min_column) / self.bucket_width) as usize;
+        if bucket + 1 >= self.bucket_starts.len() {
+            return self.end..self.end;
+        }
+
+        let lo = self.start + self.bucket_starts[bucket] as usize;
+        let hi = self.start + self.bucket_starts[bucket + 1] as usize;
+        lo..hi
+    }
+}
@@ -0,0 +1,227 @@
+//! Parsing a `"mappings"` string on many threads at once.
+//!
+//! See `parse_mappings_parallel` for the overall approach.
+
+use super::{add_relative, at_segment_end, read_relative_vlq, scan, vlq_decode};
+use super::{Error, Mapping, OriginalLocation, SegmentField};
+use comparators::{self, ComparatorFunction};
+use rayon;
+use rayon::prelude::*;
+use std::cmp;
+
+/// The relative fields after the generated column, in the order they appear.
+const FIELDS: [SegmentField; 4] = [
+    SegmentField::Source,
+    SegmentField::OriginalLine,
+    SegmentField::OriginalColumn,
+    SegmentField::Name,
+];
+
+/// Strings shorter than this are parsed sequentially, because splitting them
+/// up costs more than it saves.
+const MIN_PARALLEL_LEN: usize = 256 * 1024;
+
+/// Split the string into about this many chunks per thread, so that a few
+/// chunks full of unusually dense lines don't hold everything else up.
+const CHUNKS_PER_THREAD: usize = 4;
+
+/// Is the given string big enough to be worth parsing in parallel?
+pub(crate) fn worth_it(input: &[u8]) -> bool {
+    input.len() >= MIN_PARALLEL_LEN
+}
+
+/// A segment decoded without knowing the values of the fields that are
+/// relative to the previous chunk.
+#[derive(Debug)]
+struct Segment {
+    // Relative to the chunk's first generated line.
+    generated_line: u32,
+    generated_column: u32,
+
+    // The source, original line, original column, and name deltas, of which
+    // the first `fields` were present.
+    deltas: [i64; 4],
+    fields: usize,
+}
+
+/// A chunk of whole generated lines, with its VLQs decoded.
+#[derive(Debug, Default)]
+struct DecodedChunk {
+    segments: Vec<Segment>,
+
+    // How many generated lines the chunk ends, which is how many `;` it
+    // contains.
+    lines: u32,
+
+    // The sum of each field's deltas across the chunk.
+    totals: [i64; 4],
+
+    // The error that stopped decoding, if any. A segment that was cut short by
+    // it is still the last of `segments`, since resolving its fields that did
+    // decode might turn up an earlier error.
+    error: Option<Error>,
+}
+
+/// Parse the string into mappings sorted by generated location.
+pub(crate) fn parse(input: &[u8]) -> Result<Vec<Mapping>, Error> {
+    let decoded: Vec<DecodedChunk> = split(input).into_par_iter().map(decode).collect();
+
+    // Each chunk's first generated line and starting field values depend on
+    // every chunk before it.
+    let mut starts = Vec::with_capacity(decoded.len());
+    let mut generated_line = 0u32;
+    let mut fields = [0i64; 4];
+    for chunk in &decoded {
+        starts.push((generated_line, fields));
+        generated_line = generated_line.wrapping_add(chunk.lines);
+        for (field, total) in fields.iter_mut().zip(chunk.totals.iter()) {
+            *field = field.wrapping_add(*total);
+        }
+    }
+
+    let resolved: Vec<_> = decoded
+        .par_iter()
+        .zip(starts.into_par_iter())
+        .map(|(chunk, (generated_line, fields))| resolve(chunk, generated_line, fields))
+        .collect();
+
+    let mut by_generated = Vec::with_capacity(resolved.iter().map(|r| r.as_ref().map_or(0, Vec::len)).sum());
+    for mappings in resolved {
+        by_generated.extend(mappings?);
+    }
+    Ok(by_generated)
+}
+
+/// Split the string into chunks that each end just after a `;`, except for
+/// the last.
+fn split(input: &[u8]) -> Vec<&[u8]> {
+    let count = rayon::current_num_threads() * CHUNKS_PER_THREAD;
+    let target_len = cmp::max(input.len() / count, 1);
+
+    let mut chunks = Vec::with_capacity(count);
+    let mut start = 0;
+    while start < input.len() {
+        let end = cmp::min(start + target_len, input.len());
+        let end = scan::find(&input[end..], scan::Separators::Lines).map_or(input.len(), |i| end + i + 1);
+        chunks.push(&input[start..end]);
+        start = end;
+    }
+    chunks
+}
+
+/// Decode the VLQs of a chunk, just as `MappingsParser` would.
+fn decode(input: &[u8]) -> DecodedChunk {
+    let mut chunk = DecodedChunk::default();
+    let mut generated_column = 0;
+    let mut position = 0;
+
+    while let Some(&byte) = input.get(position) {
+        match byte {
+            b';' => {
+                chunk.lines += 1;
+                generated_column = 0;
+                position += 1;
+            }
+            b',' => {
+                position += 1;
+            }
+            _ => {
+                if let Err(e) = read_relative_vlq(&mut generated_column, SegmentField::GeneratedColumn, input, &mut position) {
+                    chunk.error = Some(e);
+                    break;
+                }
+
+                let mut segment = Segment {
+                    generated_line: chunk.lines,
+                    generated_column,
+                    deltas: [0; 4],
+                    fields: 0,
+                };
+                let result = decode_original(input, &mut position, &mut segment);
+                for (total, delta) in chunk.totals.iter_mut().zip(segment.deltas.iter()) {
+                    *total = total.wrapping_add(*delta);
+                }
+                chunk.segments.push(segment);
+
+                if let Err(e) = result {
+                    chunk.error = Some(e);
+                    break;
+                }
+            }
+        }
+    }
+
+    chunk
+}
+
+/// Decode the deltas of a segment's original location fields, if it has them.
+fn decode_original(input: &[u8], position: &mut usize, segment: &mut Segment) -> Result<(), Error> {
+    if at_segment_end(input, *position) {
+        return Ok(());
+    }
+
+    for _ in 0..3 {
+        segment.deltas[segment.fields] = vlq_decode::decode(input, position)?;
+        segment.fields += 1;
+    }
+
+    if !at_segment_end(input, *position) {
+        segment.deltas[3] = vlq_decode::decode(input, position)?;
+        segment.fields += 1;
+    }
+
+    Ok(())
+}
+
+/// Turn a decoded chunk into mappings, given its first generated line and the
+/// values of the relative fields before it, and sort each of its lines.
+fn resolve(chunk: &DecodedChunk, first_line: u32, fields: [i64; 4]) -> Result<Vec<Mapping>, Error> {
+    // If any starting value is out of range, some earlier chunk failed to
+    // resolve, and its error is the one that will be reported.
+    let mut values = [0u32; 4];
+    for (value, field) in values.iter_mut().zip(fields.iter()) {
+        if *field < 0 || *field > u32::MAX as i64 {
+            return Ok(vec![]);
+        }
+        *value = *field as u32;
+    }
+
+    let mut mappings = Vec::with_capacity(chunk.segments.len());
+    for segment in &chunk.segments {
+        let present = values.iter_mut().zip(FIELDS.iter()).zip(segment.deltas[..segment.fields].iter());
+        for ((value, field), delta) in present {
+            add_relative(value, *field, *delta)?;
+        }
+
+        mappings.push(Mapping {
+            generated_line: first_line + segment.generated_line,
+            generated_column: segment.generated_column,
+            last_generated_column: None,
+            original: if segment.fields < 3 {
+                None
+            } else {
+                Some(OriginalLocation {
+                    source: values[0],
+                    original_line: values[1],
+                    original_column: values[2],
+                    name: if segment.fields == 4 { Some(values[3]) } else { None },
+                })
+            },
+        });
+    }
+
+    if let Some(e) = chunk.error {
+        return Err(e);
+    }
+
+    for line in mappings.chunk_by_mut(|a, b| a.generated_line == b.generated_line) {
+        let sorted = line
+            .windows(2)
+            .all(|pair| comparators::ByGeneratedTail::compare(&pair[0], &pair[1]) != cmp::Ordering::Greater);
+        if !sorted {
+            line.sort_by(comparators::ByGeneratedTail::compare);
+        }
+    }
+
+    Ok(mappings)
+}
@@ -0,0 +1,211 @@
+//! Associating user data with individual mappings.
+//!
+//! Downstream tools often want to hang their own data, such as coverage counts
+//! or AST node ids, off of each mapping. Rather than maintaining a fragile
+//! external index, a `Vec` of payloads can be attached to a `Mappings`, with
+//! one payload per mapping.
+//!
+//! The payloads are kept in step with the mappings as they are inserted,
+//! removed, and split off, without knowing their type, through the
+//! `PayloadColumn` trait.
+
+use super::{Mapping, Mappings, Observer};
+use std::any::Any;
+use std::fmt;
+use std::mem;
+use std::ops::Range;
+
+/// A type-erased `Vec` of per-mapping payloads, which can be edited in step
+/// with the mappings it is attached to.
+pub(crate) trait PayloadColumn: Send + Sync {
+    /// Keep only the payloads whose entry in `keep` is `true`.
+    fn retain_mask(&mut self, keep: &[bool]);
+
+    /// Insert a default payload at the given index.
+    fn insert_default(&mut self, idx: usize);
+
+    /// Remove the payloads within the given range.
+    fn drain(&mut self, range: Range<usize>);
+
+    /// Split off the payloads at and after the given index.
+    fn split_off(&mut self, at: usize) -> Box<dyn PayloadColumn>;
+
+    /// Interleave these payloads with another set, taking the next of these
+    /// payloads where `take_self` is `true`, and the next of the others
+    /// otherwise. If the others are missing or of a different type, default
+    /// payloads are taken in their place.
+    fn interleave(&mut self, other: Option<Box<dyn PayloadColumn>>, take_self: &[bool]);
+
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+    fn into_any(self: Box<Self>) -> Box<dyn Any>;
+}
+
+impl<T: 'static + Send + Sync + Default> PayloadColumn for Vec<T> {
+    fn retain_mask(&mut self, keep: &[bool]) {
+        retain_mask(self, keep);
+    }
+
+    fn insert_default(&mut self, idx: usize) {
+        self.insert(idx, T::default());
+    }
+
+    fn drain(&mut self, range: Range<usize>) {
+        Vec::drain(self, range);
+    }
+
+    fn split_off(&mut self, at: usize) -> Box<dyn PayloadColumn> {
+        Box::new(Vec::split_off(self, at))
+    }
+
+    fn interleave(&mut self, other: Option<Box<dyn PayloadColumn>>, take_self: &[bool]) {
+        let others = other.and_then(|o| o.into_any().downcast::<Vec<T>>().ok());
+        let mut others = others.map(|o| o.into_iter());
+        let mut ours = mem::take(self).into_iter();
+        self.extend(take_self.iter().map(|&take_self| {
+            if take_self {
+                ours.next().unwrap_or_default()
+            } else {
+                others.as_mut().and_then(Iterator::next).unwrap_or_default()
+            }
+        }));
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+}
+
+impl fmt::Debug for dyn PayloadColumn {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("PayloadColumn")
+    }
+}
+
+/// Interleave the items with copies of `filler`, taking the next of the items
+/// where `take_self` is `true`, and `filler` otherwise.
+pub(crate) fn interleave_with<T: Copy>(items: &mut Vec<T>, take_self: &[bool], filler: T) {
+    let mut ours = mem::take(items).into_iter();
+    items.extend(take_self.iter().map(|&take_self| {
+        if take_self {
+            ours.next().unwrap_or(filler)
+        } else {
+            filler
+        }
+    }));
+}
+
+/// Keep only the items whose entry in `keep` is `true`.
+pub(crate) fn retain_mask<T>(items: &mut Vec<T>, keep: &[bool]) {
+    debug_assert_eq!(items.len(), keep.len());
+    let mut keep = keep.iter();
+    items.retain(|_| *keep.next().unwrap_or(&false));
+}
+
+impl<O: Observer> Mappings<O> {
+    /// Get the id of the given mapping, if it is one of these mappings.
+    ///
+    /// A mapping's id is its index within `by_generated_location`. The mapping
+    /// may be a reference into `by_generated_location`, or a mapping found some
+    /// other way, such as via `by_original_location`. In the latter case, if
+    /// several identical mappings exist, the id of the first is returned.
+    ///
+    /// Ids are invalidated by any edit that adds or removes mappings, such as
+    /// `insert`, `remove_at_generated`, `retain`, `normalize`, or `merge`.
+    /// Attached payloads move along with their mappings, but ids that were
+    /// stored elsewhere do not.
+    pub fn mapping_id(&self, mapping: &Mapping) -> Option<usize> {
+        let start = self.by_generated.as_ptr() as usize;
+        let end = start + self.by_generated.len() * mem::size_of::<Mapping>();
+        let address = mapping as *const Mapping as usize;
+        if address >= start && address < end {
+            return Some((address - start) / mem::size_of::<Mapping>());
+        }
+
+        let mut idx = self.search_generated(mapping.generated_line, mapping.generated_column)
+            .ok()?;
+        while idx > 0 && self.by_generated[idx - 1].generated_column == mapping.generated_column
+            && self.by_generated[idx - 1].generated_line == mapping.generated_line
+        {
+            idx -= 1;
+        }
+
+        self.by_generated[idx..]
+            .iter()
+            .take_while(|m| {
+                m.generated_line == mapping.generated_line
+                    && m.generated_column == mapping.generated_column
+            })
+            .position(|m| m == mapping)
+            .map(|offset| idx + offset)
+    }
+
+    /// Attach one payload to each mapping, replacing any previously attached
+    /// payloads.
+    ///
+    /// `payloads[id]` is associated with the mapping whose id is `id`, that is,
+    /// the mapping at that index within `by_generated_location`. The payloads
+    /// move along with their mappings as mappings are inserted, removed, merged,
+    /// or split off, and mappings that are added get a default payload.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is not exactly one payload per mapping.
+    pub fn attach_payload<T: 'static + Send + Sync + Default>(&mut self, payloads: Vec<T>) {
+        assert_eq!(
+            payloads.len(),
+            self.by_generated.len(),
+            "must attach exactly one payload per mapping"
+        );
+        self.payload = Some(Box::new(payloads));
+    }
+
+    /// Get all of the attached payloads, indexed by mapping id, if payloads of
+    /// type `T` are attached.
+    pub fn payloads<T: 'static + Send + Sync>(&self) -> Option<&[T]> {
+        self.payload
+            .as_ref()
+            .and_then(|p| p.as_any().downcast_ref::<Vec<T>>())
+            .map(|p| &p[..])
+    }
+
+    /// Get all of the attached payloads mutably, indexed by mapping id, if
+    /// payloads of type `T` are attached.
+    pub fn payloads_mut<T: 'static + Send + Sync>(&mut self) -> Option<&mut [T]> {
+        self.payload
+            .as_mut()
+            .and_then(|p| p.as_any_mut().downcast_mut::<Vec<T>>())
+            .map(|p| &mut p[..])
+    }
+
+    /// Get the payload attached to the given mapping, if payloads of type `T`
+    /// are attached and the mapping is one of these mappings.
+    pub fn payload<T: 'static + Send + Sync>(&self, mapping: &Mapping) -> Option<&T> {
+        let payloads = self.payloads::<T>()?;
+        self.mapping_id(mapping).map(|id| &payloads[id])
+    }
+
+    /// Detach and return the attached payloads, if payloads of type `T` are
+    /// attached.
+    pub fn detach_payload<T: 'static + Send + Sync>(&mut self) -> Option<Vec<T>> {
+        match self.payload.take() {
+            Some(payload) => {
+                if payload.as_any().is::<Vec<T>>() {
+                    payload.into_any().downcast::<Vec<T>>().ok().map(|payloads| *payloads)
+                } else {
+                    self.payload = Some(payload);
+                    None
+                }
+            }
+            None => None,
+        }
+    }
+}
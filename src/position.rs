@@ -0,0 +1,149 @@
+//! Typed generated and original positions.
+//!
+//! Most of this crate's queries take bare `u32` lines and columns, for the sake
+//! of the C API, which makes it easy to pass a line where a column was meant.
+//! These types name each field, and order positions exactly as the
+//! comparators in `comparators` order mappings, so they can be compared and
+//! sorted directly.
+
+use super::{Bias, Mapping, Mappings, MappingsBuilder, Observer, OriginalLocation};
+use coverage::GeneratedRange;
+
+/// A location in the generated file.
+///
+/// Ordered by line, then column, just as `comparators::ByGeneratedLocation`
+/// orders mappings.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct GeneratedPosition {
+    /// The generated line.
+    pub line: u32,
+
+    /// The generated column.
+    pub column: u32,
+}
+
+impl From<(u32, u32)> for GeneratedPosition {
+    #[inline]
+    fn from((line, column): (u32, u32)) -> GeneratedPosition {
+        GeneratedPosition { line, column }
+    }
+}
+
+/// A location in some original source.
+///
+/// Ordered by source, then line, then column, just as
+/// `comparators::ByOriginalLocation` orders mappings.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct OriginalPosition {
+    /// The source filename.
+    pub source: u32,
+
+    /// The original line.
+    pub line: u32,
+
+    /// The original column.
+    pub column: u32,
+}
+
+impl From<(u32, u32, u32)> for OriginalPosition {
+    #[inline]
+    fn from((source, line, column): (u32, u32, u32)) -> OriginalPosition {
+        OriginalPosition { source, line, column }
+    }
+}
+
+impl Mapping {
+    /// Get this mapping's generated location.
+    #[inline]
+    pub fn generated_position(&self) -> GeneratedPosition {
+        GeneratedPosition {
+            line: self.generated_line,
+            column: self.generated_column,
+        }
+    }
+
+    /// Get this mapping's original location, if it has one.
+    #[inline]
+    pub fn original_position(&self) -> Option<OriginalPosition> {
+        self.original.as_ref().map(OriginalLocation::position)
+    }
+
+    /// Get the range of generated columns this mapping spans.
+    ///
+    /// The range is open-ended until column spans are computed.
+    #[inline]
+    pub fn generated_range(&self) -> GeneratedRange {
+        GeneratedRange {
+            generated_line: self.generated_line,
+            generated_column: self.generated_column,
+            last_generated_column: self.last_generated_column,
+        }
+    }
+}
+
+impl OriginalLocation {
+    /// Get this original location's source, line, and column.
+    #[inline]
+    pub fn position(&self) -> OriginalPosition {
+        OriginalPosition {
+            source: self.source,
+            line: self.original_line,
+            column: self.original_column,
+        }
+    }
+}
+
+impl<O: Observer> Mappings<O> {
+    /// Get the mapping closest to the given generated position, if any exists.
+    ///
+    /// This is `original_location_for` with a typed position.
+    #[inline]
+    pub fn original_location_at(&self, position: GeneratedPosition, bias: Bias) -> Option<&Mapping> {
+        self.original_location_for(position.line, position.column, bias)
+    }
+
+    /// Get where the given mapping's generated span ends, when spans may
+    /// continue across generated lines.
+    ///
+    /// Column spans end at the end of the generated line when the mapping is
+    /// the last on its line. Highlighting a mapping's generated code usually
+    /// wants to continue on until the start of the next line's first mapping
+    /// instead, which is where this says the span ends. A span that ends on a
+    /// later line than it starts spans to the next line.
+    ///
+    /// Returns `None` for the very last mapping, whose span continues until
+    /// the end of the generated file, and for mappings that are not among
+    /// these mappings.
+    pub fn generated_end(&self, mapping: &Mapping) -> Option<GeneratedPosition> {
+        if let Some(last) = mapping.last_generated_column {
+            return Some(GeneratedPosition {
+                line: mapping.generated_line,
+                column: last,
+            });
+        }
+
+        let idx = self.mapping_id(mapping)?;
+        self.by_generated_location()
+            .get(idx + 1)
+            .map(Mapping::generated_position)
+    }
+
+    /// Get the mapping closest to the given original position, if any exists.
+    ///
+    /// This is `generated_location_for` with a typed position.
+    #[inline]
+    pub fn generated_location_at(&mut self, position: OriginalPosition, bias: Bias) -> Option<&Mapping> {
+        self.generated_location_for(position.source, position.line, position.column, bias)
+    }
+}
+
+impl<O: Observer> MappingsBuilder<O> {
+    /// Add a mapping at the given generated position, with the given original
+    /// location information, if any.
+    ///
+    /// This is `add_mapping` with a typed position.
+    #[inline]
+    pub fn add_mapping_at(&mut self, position: GeneratedPosition, original: Option<OriginalLocation>) {
+        self.add_mapping(position.line, position.column, original)
+    }
+}
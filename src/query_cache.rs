@@ -0,0 +1,180 @@
+//! Caching the results of repeated generated location queries.
+//!
+//! Devtools resolve the same hot stack frames over and over again. Wrapping a
+//! `Mappings` with `Mappings::with_query_cache` remembers the results of the
+//! most recently used `original_location_for` queries, so that repeating one
+//! skips the search entirely.
+
+use super::{unwrap, Bias, Mapping, Mappings, Observer};
+use std::collections::HashMap;
+use std::fmt;
+
+type Key = (u32, u32, Bias);
+
+const NIL: usize = usize::MAX;
+
+/// A `Mappings` with a least-recently-used cache of `original_location_for`
+/// results.
+///
+/// Returned by `Mappings::with_query_cache`.
+pub struct CachedMappings<O = ()>
+where
+    O: Observer,
+{
+    mappings: Mappings<O>,
+    capacity: usize,
+
+    // Each cached query's entry in `entries`.
+    by_key: HashMap<Key, usize>,
+
+    // The cached queries, as a doubly linked list from the most recently used
+    // at `head` to the least recently used at `tail`.
+    entries: Vec<Entry>,
+    head: usize,
+    tail: usize,
+}
+
+impl<O> fmt::Debug for CachedMappings<O>
+where
+    O: Observer,
+    Mappings<O>: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CachedMappings")
+            .field("mappings", &self.mappings)
+            .field("capacity", &self.capacity)
+            .field("cached", &self.entries.len())
+            .finish()
+    }
+}
+
+struct Entry {
+    key: Key,
+    // The position of the resulting mapping within `by_generated`.
+    result: Option<usize>,
+    prev: usize,
+    next: usize,
+}
+
+impl<O: Observer> Mappings<O> {
+    /// Wrap these mappings with a cache of the results of the last `capacity`
+    /// distinct `original_location_for` queries.
+    pub fn with_query_cache(self, capacity: usize) -> CachedMappings<O> {
+        CachedMappings {
+            mappings: self,
+            capacity,
+            by_key: HashMap::with_capacity(capacity),
+            entries: Vec::with_capacity(capacity),
+            head: NIL,
+            tail: NIL,
+        }
+    }
+}
+
+impl<O: Observer> CachedMappings<O> {
+    /// Get the mapping closest to the given generated location, if any exists,
+    /// as `Mappings::original_location_for` does, remembering the result.
+    pub fn original_location_for(
+        &mut self,
+        generated_line: u32,
+        generated_column: u32,
+        bias: Bias,
+    ) -> Option<&Mapping> {
+        let key = (generated_line, generated_column, bias);
+
+        let result = match self.by_key.get(&key).cloned() {
+            Some(idx) => {
+                self.unlink(idx);
+                self.push_front(idx);
+                self.entries[idx].result
+            }
+            None => {
+                let result = self.mappings
+                    .original_location_for(generated_line, generated_column, bias)
+                    .map(|m| unwrap(self.mappings.mapping_id(m)));
+                self.insert(key, result);
+                result
+            }
+        };
+
+        result.map(move |idx| &self.mappings.by_generated[idx])
+    }
+
+    /// Get the wrapped mappings.
+    #[inline]
+    pub fn mappings(&self) -> &Mappings<O> {
+        &self.mappings
+    }
+
+    /// Get the wrapped mappings for modification, clearing the cache.
+    pub fn mappings_mut(&mut self) -> &mut Mappings<O> {
+        self.clear();
+        &mut self.mappings
+    }
+
+    /// Unwrap the mappings, discarding the cache.
+    #[inline]
+    pub fn into_inner(self) -> Mappings<O> {
+        self.mappings
+    }
+
+    /// Forget every cached result.
+    pub fn clear(&mut self) {
+        self.by_key.clear();
+        self.entries.clear();
+        self.head = NIL;
+        self.tail = NIL;
+    }
+
+    fn insert(&mut self, key: Key, result: Option<usize>) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let idx = if self.entries.len() < self.capacity {
+            self.entries.push(Entry {
+                key,
+                result,
+                prev: NIL,
+                next: NIL,
+            });
+            self.entries.len() - 1
+        } else {
+            // Evict the least recently used entry, and reuse its slot.
+            let idx = self.tail;
+            self.unlink(idx);
+            self.by_key.remove(&self.entries[idx].key);
+            self.entries[idx].key = key;
+            self.entries[idx].result = result;
+            idx
+        };
+
+        self.by_key.insert(key, idx);
+        self.push_front(idx);
+    }
+
+    fn unlink(&mut self, idx: usize) {
+        let (prev, next) = (self.entries[idx].prev, self.entries[idx].next);
+        if prev == NIL {
+            self.head = next;
+        } else {
+            self.entries[prev].next = next;
+        }
+        if next == NIL {
+            self.tail = prev;
+        } else {
+            self.entries[next].prev = prev;
+        }
+    }
+
+    fn push_front(&mut self, idx: usize) {
+        self.entries[idx].prev = NIL;
+        self.entries[idx].next = self.head;
+        if self.head == NIL {
+            self.tail = idx;
+        } else {
+            self.entries[self.head].prev = idx;
+        }
+        self.head = idx;
+    }
+}
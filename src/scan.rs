@@ -0,0 +1,146 @@
+//! Scanning a `"mappings"` string for segment and line separators.
+//!
+//! With the `simd` feature enabled, whole 16 byte blocks are compared against
+//! `;` and `,` at once, using SSE2 on `x86_64` and the `simd128` proposal on
+//! `wasm32` when the target supports it. Everywhere else, and for the tail of
+//! the input that doesn't fill a block, bytes are scanned one at a time.
+
+/// Which separators to scan for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Separators {
+    /// Only `;`, which ends a generated line.
+    Lines,
+
+    /// Either `;` or `,`, which end a segment.
+    Segments,
+}
+
+impl Separators {
+    #[inline]
+    fn matches(self, byte: u8) -> bool {
+        match self {
+            Separators::Lines => byte == b';',
+            Separators::Segments => byte == b';' || byte == b',',
+        }
+    }
+}
+
+/// Count the segments in the input, which is how many mappings parsing it
+/// will produce, unless it is malformed.
+///
+/// A segment starts wherever a byte that isn't a separator follows a
+/// separator, or the start of the input.
+pub(crate) fn count_segments(input: &[u8]) -> usize {
+    let mut count = 0;
+    let mut after_separator = true;
+    for &byte in input {
+        let separator = Separators::Segments.matches(byte);
+        count += (after_separator && !separator) as usize;
+        after_separator = separator;
+    }
+    count
+}
+
+/// Find the index of the first separator in the input, if any.
+#[inline]
+pub(crate) fn find(input: &[u8], separators: Separators) -> Option<usize> {
+    let mut start = 0;
+    if SIMD {
+        while start + BLOCK <= input.len() {
+            let mask = block_mask(&input[start..start + BLOCK], separators);
+            if mask != 0 {
+                return Some(start + mask.trailing_zeros() as usize);
+            }
+            start += BLOCK;
+        }
+    }
+
+    input[start..]
+        .iter()
+        .position(|&byte| separators.matches(byte))
+        .map(|i| start + i)
+}
+
+/// Find the index of the last separator in the input, if any.
+#[inline]
+pub(crate) fn rfind(input: &[u8], separators: Separators) -> Option<usize> {
+    let mut end = input.len();
+    if SIMD {
+        while end >= BLOCK {
+            let mask = block_mask(&input[end - BLOCK..end], separators);
+            if mask != 0 {
+                return Some(end - 1 - mask.leading_zeros() as usize);
+            }
+            end -= BLOCK;
+        }
+    }
+
+    input[..end].iter().rposition(|&byte| separators.matches(byte))
+}
+
+const BLOCK: usize = 16;
+
+#[cfg(any(
+    all(feature = "simd", target_arch = "x86_64"),
+    all(feature = "simd", target_arch = "wasm32", target_feature = "simd128")
+))]
+const SIMD: bool = true;
+
+// Without SIMD there are no blocks, and every byte is scanned by the tail
+// loop.
+#[cfg(not(any(
+    all(feature = "simd", target_arch = "x86_64"),
+    all(feature = "simd", target_arch = "wasm32", target_feature = "simd128")
+)))]
+const SIMD: bool = false;
+
+/// Get a mask of the separators in the given block, with bit `i` set when
+/// `block[i]` is a separator.
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+#[inline]
+fn block_mask(block: &[u8], separators: Separators) -> u16 {
+    use std::arch::x86_64::*;
+
+    debug_assert_eq!(block.len(), BLOCK);
+
+    // Safe because SSE2 is part of the `x86_64` baseline, and the load is
+    // unaligned and within the block.
+    unsafe {
+        let bytes = _mm_loadu_si128(block.as_ptr() as *const __m128i);
+        let mut found = _mm_cmpeq_epi8(bytes, _mm_set1_epi8(b';' as i8));
+        if separators == Separators::Segments {
+            found = _mm_or_si128(found, _mm_cmpeq_epi8(bytes, _mm_set1_epi8(b',' as i8)));
+        }
+        _mm_movemask_epi8(found) as u16
+    }
+}
+
+/// Get a mask of the separators in the given block, with bit `i` set when
+/// `block[i]` is a separator.
+#[cfg(all(feature = "simd", target_arch = "wasm32", target_feature = "simd128"))]
+#[inline]
+fn block_mask(block: &[u8], separators: Separators) -> u16 {
+    use std::arch::wasm32::*;
+
+    debug_assert_eq!(block.len(), BLOCK);
+
+    // Safe because the target supports `simd128`, and the load is unaligned
+    // and within the block.
+    unsafe {
+        let bytes = v128_load(block.as_ptr() as *const v128);
+        let mut found = u8x16_eq(bytes, u8x16_splat(b';'));
+        if separators == Separators::Segments {
+            found = v128_or(found, u8x16_eq(bytes, u8x16_splat(b',')));
+        }
+        u8x16_bitmask(found)
+    }
+}
+
+#[cfg(not(any(
+    all(feature = "simd", target_arch = "x86_64"),
+    all(feature = "simd", target_arch = "wasm32", target_feature = "simd128")
+)))]
+#[inline]
+fn block_mask(_block: &[u8], _separators: Separators) -> u16 {
+    unreachable!()
+}
@@ -0,0 +1,480 @@
+//! Support for the `sections` of indexed source maps.
+//!
+//! An indexed source map is made up of many child source maps, each of which
+//! begins at some offset within the generated file. Each section's
+//! `"mappings"` string is parsed on its own, and then accumulated into a
+//! `MappingsSections` structure that can answer queries across all of them.
+//!
+//! Each section has its own `"sources"` and `"names"`, so source indices can't
+//! be compared across sections. Queries by original location therefore take a
+//! source filename, and require each section's string tables to have been
+//! attached with `Mappings::with_string_tables`.
+
+use super::{comparators, sort, Bias, Error, Mapping, Mappings, Observer, OriginalLocation};
+use std::cmp;
+use std::collections::HashMap;
+use std::fmt;
+
+/// The generated location at which a section of an indexed source map begins.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SectionOffset {
+    /// The generated line at which the section begins.
+    pub generated_line: u32,
+
+    /// The generated column at which the section begins.
+    pub generated_column: u32,
+}
+
+impl SectionOffset {
+    /// Is the given generated location at or after this offset?
+    #[inline]
+    fn contains(&self, generated_line: u32, generated_column: u32) -> bool {
+        (generated_line, generated_column) >= (self.generated_line, self.generated_column)
+    }
+
+    /// Translate an absolute generated location into one that is relative to
+    /// this offset.
+    ///
+    /// The location must be at or after this offset.
+    #[inline]
+    fn relativize(&self, generated_line: u32, generated_column: u32) -> (u32, u32) {
+        debug_assert!(self.contains(generated_line, generated_column));
+        if generated_line == self.generated_line {
+            (0, generated_column - self.generated_column)
+        } else {
+            (generated_line - self.generated_line, generated_column)
+        }
+    }
+
+    /// Translate a generated line that is relative to this offset into an
+    /// absolute one, or `None` if it would be larger than `u32::MAX`.
+    #[inline]
+    fn absolute_line(&self, generated_line: u32) -> Option<u32> {
+        self.generated_line.checked_add(generated_line)
+    }
+
+    /// Translate a generated column on the given line, both relative to this
+    /// offset, into an absolute column, or `None` if it would be larger than
+    /// `u32::MAX`.
+    #[inline]
+    fn absolute_column(&self, generated_line: u32, generated_column: u32) -> Option<u32> {
+        if generated_line == 0 {
+            self.generated_column.checked_add(generated_column)
+        } else {
+            Some(generated_column)
+        }
+    }
+
+    /// Translate a generated location that is relative to this offset into an
+    /// absolute one.
+    ///
+    /// Returns `Error::UnexpectedlyBigGeneratedLine` or
+    /// `Error::UnexpectedlyBigGeneratedColumn` if it would be larger than
+    /// `u32::MAX`.
+    #[inline]
+    fn absolutize(&self, generated_line: u32, generated_column: u32) -> Result<(u32, u32), Error> {
+        let line = self.absolute_line(generated_line)
+            .ok_or(Error::UnexpectedlyBigGeneratedLine)?;
+        let column = self.absolute_column(generated_line, generated_column)
+            .ok_or(Error::UnexpectedlyBigGeneratedColumn)?;
+        Ok((line, column))
+    }
+}
+
+/// A single parsed section of an indexed source map, along with its metadata.
+pub struct Section<O = ()>
+where
+    O: Observer,
+{
+    index: usize,
+    offset: SectionOffset,
+    mappings: Mappings<O>,
+}
+
+impl<O> fmt::Debug for Section<O>
+where
+    O: Observer,
+    Mappings<O>: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Section")
+            .field("index", &self.index)
+            .field("offset", &self.offset)
+            .field("mappings", &self.mappings)
+            .finish()
+    }
+}
+
+impl<O: Observer> Section<O> {
+    /// The index of this section within the indexed source map's `"sections"`
+    /// array, which is also the order in which it was pushed.
+    #[inline]
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The generated location at which this section begins.
+    #[inline]
+    pub fn offset(&self) -> SectionOffset {
+        self.offset
+    }
+
+    /// This section's mappings. Their generated locations are relative to the
+    /// section's offset.
+    #[inline]
+    pub fn mappings(&self) -> &Mappings<O> {
+        &self.mappings
+    }
+
+    /// This section's mappings, mutably. Their generated locations are relative
+    /// to the section's offset.
+    #[inline]
+    pub fn mappings_mut(&mut self) -> &mut Mappings<O> {
+        &mut self.mappings
+    }
+}
+
+/// A mapping found in a section of an indexed source map.
+///
+/// The mapping's `source` and `name` indices refer to the `sources` and
+/// `names` arrays of the child source map for `section`, and its generated
+/// location is relative to the section's offset. Use `generated_line` and
+/// `generated_column` to get the absolute generated location.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SectionMapping<'a> {
+    /// The index of the section this mapping came from.
+    pub section: usize,
+
+    /// The generated location at which the section begins.
+    pub offset: SectionOffset,
+
+    /// The mapping itself, relative to the section's offset.
+    pub mapping: &'a Mapping,
+}
+
+impl<'a> SectionMapping<'a> {
+    /// The absolute generated line of this mapping, or `None` if the
+    /// section's offset pushes it past `u32::MAX`.
+    #[inline]
+    pub fn generated_line(&self) -> Option<u32> {
+        self.offset.absolute_line(self.mapping.generated_line)
+    }
+
+    /// The absolute generated column of this mapping, or `None` if the
+    /// section's offset pushes it past `u32::MAX`.
+    #[inline]
+    pub fn generated_column(&self) -> Option<u32> {
+        self.offset
+            .absolute_column(self.mapping.generated_line, self.mapping.generated_column)
+    }
+}
+
+/// How one section's source and name indices were translated into those of
+/// the flattened mappings.
+///
+/// Returned by `MappingsSections::flatten`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SectionRemapping {
+    /// The flattened index of each of the section's sources, indexed by the
+    /// section's source index.
+    pub sources: Vec<u32>,
+
+    /// The flattened index of each of the section's names, indexed by the
+    /// section's name index.
+    pub names: Vec<u32>,
+}
+
+/// Assigns flattened indices to the sources or names of each section in turn.
+#[derive(Default)]
+struct FlattenedTable {
+    strings: Vec<String>,
+    indices: HashMap<String, u32>,
+    len: u32,
+}
+
+impl FlattenedTable {
+    /// Assign flattened indices to a section's `count` sources or names,
+    /// deduplicating them by their strings if `strings` is given.
+    ///
+    /// Indices past the end of `strings` still get flattened indices of their
+    /// own, with empty strings. Returns `too_big` if there would be more
+    /// flattened indices than fit in a `u32`.
+    fn add_section(&mut self, count: u32, strings: Option<&[String]>, too_big: Error) -> Result<Vec<u32>, Error> {
+        let table = strings.unwrap_or(&[]);
+        let mut indices = Vec::with_capacity(cmp::max(count as usize, table.len()));
+
+        for s in table {
+            let next = self.len;
+            let index = *self.indices.entry(s.clone()).or_insert(next);
+            if index == next {
+                self.strings.push(s.clone());
+                self.len = next.checked_add(1).ok_or(too_big)?;
+            }
+            indices.push(index);
+        }
+
+        while indices.len() < count as usize {
+            if strings.is_some() {
+                self.strings.push(String::new());
+            }
+            indices.push(self.len);
+            self.len = self.len.checked_add(1).ok_or(too_big)?;
+        }
+
+        Ok(indices)
+    }
+}
+
+/// The parsed sections of an indexed source map, accumulated into a single
+/// queryable structure.
+pub struct MappingsSections<O = ()>
+where
+    O: Observer,
+{
+    // Always sorted by offset.
+    sections: Vec<Section<O>>,
+}
+
+impl<O> fmt::Debug for MappingsSections<O>
+where
+    O: Observer,
+    Mappings<O>: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MappingsSections")
+            .field("sections", &self.sections)
+            .finish()
+    }
+}
+
+impl<O: Observer> Default for MappingsSections<O> {
+    #[inline]
+    fn default() -> MappingsSections<O> {
+        MappingsSections { sections: vec![] }
+    }
+}
+
+impl<O: Observer> MappingsSections<O> {
+    /// Construct a new, empty set of sections.
+    #[inline]
+    pub fn new() -> MappingsSections<O> {
+        Default::default()
+    }
+
+    /// Add the parsed mappings for the next section, which begins at the given
+    /// generated offset.
+    ///
+    /// Returns the new section's index, which is the number of sections that
+    /// were pushed before it.
+    pub fn push_section(
+        &mut self,
+        generated_line_offset: u32,
+        generated_column_offset: u32,
+        mappings: Mappings<O>,
+    ) -> usize {
+        let index = self.sections.len();
+        let offset = SectionOffset {
+            generated_line: generated_line_offset,
+            generated_column: generated_column_offset,
+        };
+
+        // Sections are supposed to be given in order, but be robust to ones
+        // that aren't by inserting after every section with a lesser or equal
+        // offset.
+        let position = self.sections.partition_point(|s| s.offset <= offset);
+
+        self.sections.insert(
+            position,
+            Section {
+                index,
+                offset,
+                mappings,
+            },
+        );
+        index
+    }
+
+    /// Get the sections, ordered by their generated offsets.
+    #[inline]
+    pub fn sections(&self) -> &[Section<O>] {
+        &self.sections
+    }
+
+    /// Find the section that contains the given generated location, if any.
+    #[inline]
+    fn section_for(&self, generated_line: u32, generated_column: u32) -> Option<&Section<O>> {
+        let idx = self.sections
+            .partition_point(|s| s.offset.contains(generated_line, generated_column));
+        if idx == 0 {
+            None
+        } else {
+            Some(&self.sections[idx - 1])
+        }
+    }
+
+    /// Get the mapping closest to the given absolute generated location, if any
+    /// exists, along with the section it came from.
+    ///
+    /// Like `mozilla/source-map`'s `IndexedSourceMapConsumer`, only the section
+    /// containing the generated location is searched; fuzzy matching never
+    /// slides into a neighboring section.
+    pub fn original_location_for(
+        &self,
+        generated_line: u32,
+        generated_column: u32,
+        bias: Bias,
+    ) -> Option<SectionMapping<'_>> {
+        let section = self.section_for(generated_line, generated_column)?;
+        let (line, column) = section.offset.relativize(generated_line, generated_column);
+        section
+            .mappings
+            .original_location_for(line, column, bias)
+            .map(|mapping| SectionMapping {
+                section: section.index,
+                offset: section.offset,
+                mapping,
+            })
+    }
+
+    /// Get the mapping closest to the given original location in the first
+    /// section, in generated order, that has the given source and a mapping
+    /// near that location.
+    ///
+    /// A section's closest mapping is only used if it is into the given source,
+    /// as with `Mappings::generated_location_for_source`; otherwise the search
+    /// moves on to the next section. Sections without string tables attached
+    /// are never searched.
+    pub fn generated_location_for(
+        &mut self,
+        source: &str,
+        original_line: u32,
+        original_column: u32,
+        bias: Bias,
+    ) -> Option<SectionMapping<'_>> {
+        for section in &mut self.sections {
+            let (index, offset) = (section.index, section.offset);
+            let found = section
+                .mappings
+                .generated_location_for_source(source, original_line, original_column, bias);
+            if let Some(mapping) = found {
+                return Some(SectionMapping {
+                    section: index,
+                    offset,
+                    mapping,
+                });
+            }
+        }
+        None
+    }
+
+    /// Get all of the mappings, across every section, for the given source and
+    /// original line, and optionally the given original column.
+    ///
+    /// The results are grouped by section, in generated order, and are ordered
+    /// by original location within each section. Sections without string
+    /// tables attached are never searched.
+    pub fn all_generated_locations_for(
+        &mut self,
+        source: &str,
+        original_line: u32,
+        original_column: Option<u32>,
+    ) -> Vec<SectionMapping<'_>> {
+        let mut results = vec![];
+        for section in &mut self.sections {
+            let (index, offset) = (section.index, section.offset);
+            let found = section
+                .mappings
+                .all_generated_locations_for_source(source, original_line, original_column);
+            results.extend(found.map(|mapping| SectionMapping {
+                section: index,
+                offset,
+                mapping,
+            }));
+        }
+        results
+    }
+
+    /// Flatten every section into a single `Mappings` structure, with each
+    /// section's mappings rebased to its offset, along with how each section's
+    /// source and name indices were translated, indexed by section index.
+    ///
+    /// If every section has string tables attached, then sources and names
+    /// that are shared between sections are deduplicated by their strings, and
+    /// the flattened string tables are attached to the result. Otherwise, each
+    /// section's sources and names are simply concatenated, in generated
+    /// order. Either way, a mapping whose source or name index is past the end
+    /// of its section's string tables keeps it, translated to a flattened index
+    /// of its own, with an empty string.
+    ///
+    /// Returns `Error::UnexpectedlyBigSource` or `Error::UnexpectedlyBigName`
+    /// if there would be more flattened sources or names than fit in a `u32`,
+    /// and `Error::UnexpectedlyBigGeneratedLine` or
+    /// `Error::UnexpectedlyBigGeneratedColumn` if a section's offset would push
+    /// one of its mappings past `u32::MAX`.
+    pub fn flatten(self) -> Result<(Mappings<O>, Vec<SectionRemapping>), Error> {
+        let use_strings = self.sections
+            .iter()
+            .all(|s| s.mappings.string_tables.is_some());
+
+        let mut sources = FlattenedTable::default();
+        let mut names = FlattenedTable::default();
+        let mut remappings = vec![SectionRemapping::default(); self.sections.len()];
+        let mut by_generated = vec![];
+
+        for section in self.sections {
+            let (index, offset, mappings) = (section.index, section.offset, section.mappings);
+
+            let (mut source_count, mut name_count) = (0u32, 0u32);
+            for o in mappings.by_generated.iter().filter_map(|m| m.original.as_ref()) {
+                let source_end = o.source.checked_add(1).ok_or(Error::UnexpectedlyBigSource)?;
+                source_count = cmp::max(source_count, source_end);
+                if let Some(name) = o.name {
+                    let name_end = name.checked_add(1).ok_or(Error::UnexpectedlyBigName)?;
+                    name_count = cmp::max(name_count, name_end);
+                }
+            }
+            let remapping = SectionRemapping {
+                sources: sources.add_section(
+                    source_count,
+                    if use_strings { Some(mappings.sources()) } else { None },
+                    Error::UnexpectedlyBigSource,
+                )?,
+                names: names.add_section(
+                    name_count,
+                    if use_strings { Some(mappings.names()) } else { None },
+                    Error::UnexpectedlyBigName,
+                )?,
+            };
+
+            by_generated.reserve(mappings.by_generated.len());
+            for m in &mappings.by_generated {
+                let (generated_line, generated_column) = offset.absolutize(m.generated_line, m.generated_column)?;
+                by_generated.push(Mapping {
+                    generated_line,
+                    generated_column,
+                    last_generated_column: None,
+                    original: m.original.as_ref().map(|o| OriginalLocation {
+                        source: remapping.sources[o.source as usize],
+                        original_line: o.original_line,
+                        original_column: o.original_column,
+                        name: o.name.map(|n| remapping.names[n as usize]),
+                    }),
+                });
+            }
+
+            remappings[index] = remapping;
+        }
+
+        // Sections shouldn't overlap, but be robust to ones that do.
+        {
+            let _observer = O::SortByGeneratedLocation::default();
+            sort::by_comparator::<comparators::ByGeneratedLocation, _>(&mut by_generated);
+        }
+
+        let mut flattened = Mappings::from_sorted(by_generated);
+        if use_strings {
+            flattened = flattened.with_string_tables(sources.strings, names.strings);
+        }
+        Ok((flattened, remappings))
+    }
+}
@@ -0,0 +1,78 @@
+//! Sorting mappings, or indices of mappings.
+//!
+//! All sorts are stable, so that mappings that compare equal keep the order in
+//! which they were given. With the `rayon` feature, large sorts are spread
+//! across `rayon`'s global thread pool.
+
+use comparators::ComparatorFunction;
+
+/// Sorts of fewer items than this aren't worth splitting across threads.
+#[cfg(feature = "rayon")]
+const MIN_PARALLEL_SORT_LEN: usize = 64 * 1024;
+
+/// Stably sort the given items with the comparator `F`.
+#[inline]
+pub(crate) fn by_comparator<F, T>(items: &mut [T])
+where
+    F: ComparatorFunction<T>,
+    T: Send,
+{
+    #[cfg(feature = "rayon")]
+    {
+        if items.len() >= MIN_PARALLEL_SORT_LEN {
+            use rayon::slice::ParallelSliceMut;
+            items.par_sort_by(F::compare);
+            return;
+        }
+    }
+
+    items.sort_by(F::compare);
+}
+
+/// Stably sort the given indices into `items` by comparing the items they
+/// point to with the comparator `F`.
+#[inline]
+pub(crate) fn indices_by_comparator<F, T>(indices: &mut [u32], items: &[T])
+where
+    F: ComparatorFunction<T>,
+    T: Sync,
+{
+    let compare = |a: &u32, b: &u32| F::compare(&items[*a as usize], &items[*b as usize]);
+
+    #[cfg(feature = "rayon")]
+    {
+        if indices.len() >= MIN_PARALLEL_SORT_LEN {
+            use rayon::slice::ParallelSliceMut;
+            indices.par_sort_by(compare);
+            return;
+        }
+    }
+
+    indices.sort_by(compare);
+}
+
+/// Get the order in which a stable sort with the comparator `F` would put the
+/// given items, as indices into them.
+///
+/// This lets other items that correspond to each of them be put in the same
+/// order with `apply_order`.
+#[inline]
+pub(crate) fn order_by_comparator<F, T>(items: &[T]) -> Vec<u32>
+where
+    F: ComparatorFunction<T>,
+    T: Sync,
+{
+    debug_assert!(items.len() <= u32::MAX as usize);
+    let mut order: Vec<u32> = (0..items.len() as u32).collect();
+    indices_by_comparator::<F, T>(&mut order, items);
+    order
+}
+
+/// Put the given items in the given order, as returned by
+/// `order_by_comparator`.
+#[inline]
+pub(crate) fn apply_order<T: Clone>(items: &mut [T], order: &[u32]) {
+    debug_assert_eq!(items.len(), order.len());
+    let ordered: Vec<T> = order.iter().map(|&i| items[i as usize].clone()).collect();
+    items.clone_from_slice(&ordered);
+}
@@ -0,0 +1,106 @@
+//! Attaching a source map's `"sources"` and `"names"` to its mappings.
+//!
+//! Mappings only refer to sources and names by index. With the string tables
+//! attached, queries can take a source filename instead of an index, and
+//! results can be resolved back to strings, sparing every consumer from doing
+//! that bookkeeping itself.
+
+use super::{AllGeneratedLocationsFor, Bias, Mapping, Mappings, Observer};
+use std::collections::HashMap;
+
+#[derive(Clone, Debug, Default)]
+pub(crate) struct StringTables {
+    sources: Vec<String>,
+    names: Vec<String>,
+    source_indices: HashMap<String, u32>,
+}
+
+impl<O: Observer> Mappings<O> {
+    /// Attach the source map's `"sources"` and `"names"` arrays to these
+    /// mappings, replacing any that were previously attached.
+    pub fn with_string_tables(mut self, sources: Vec<String>, names: Vec<String>) -> Mappings<O> {
+        let mut source_indices = HashMap::with_capacity(sources.len());
+        for (index, source) in sources.iter().enumerate().rev() {
+            // Iterate in reverse so that the first of any duplicates wins.
+            source_indices.insert(source.clone(), index as u32);
+        }
+
+        self.string_tables = Some(StringTables {
+            sources,
+            names,
+            source_indices,
+        });
+        self
+    }
+
+    /// Get the attached `"sources"` array, or an empty slice if none is
+    /// attached.
+    #[inline]
+    pub fn sources(&self) -> &[String] {
+        self.string_tables.as_ref().map_or(&[], |t| &t.sources[..])
+    }
+
+    /// Get the attached `"names"` array, or an empty slice if none is attached.
+    #[inline]
+    pub fn names(&self) -> &[String] {
+        self.string_tables.as_ref().map_or(&[], |t| &t.names[..])
+    }
+
+    /// Get the index of the given source filename within the attached
+    /// `"sources"` array, if it is there.
+    #[inline]
+    pub fn source_index(&self, source: &str) -> Option<u32> {
+        self.string_tables
+            .as_ref()
+            .and_then(|t| t.source_indices.get(source).cloned())
+    }
+
+    /// Get the source filename of the given mapping, if it has original
+    /// location information and its source is in the attached `"sources"`.
+    #[inline]
+    pub fn source_of(&self, mapping: &Mapping) -> Option<&str> {
+        let original = mapping.original.as_ref()?;
+        self.sources().get(original.source as usize).map(|s| &s[..])
+    }
+
+    /// Get the associated name of the given mapping, if it has one and it is
+    /// in the attached `"names"`.
+    #[inline]
+    pub fn name_of(&self, mapping: &Mapping) -> Option<&str> {
+        let name = mapping.original.as_ref()?.name?;
+        self.names().get(name as usize).map(|s| &s[..])
+    }
+
+    /// Like `generated_location_for`, but takes a source filename from the
+    /// attached `"sources"` rather than an index.
+    ///
+    /// Unlike `generated_location_for`, this never slides into another source:
+    /// if the closest mapping in the direction of `bias` is in some other
+    /// source, the result is `None`, just as with `mozilla/source-map`.
+    pub fn generated_location_for_source(
+        &mut self,
+        source: &str,
+        original_line: u32,
+        original_column: u32,
+        bias: Bias,
+    ) -> Option<&Mapping> {
+        let source = self.source_index(source)?;
+        self.generated_location_for(source, original_line, original_column, bias)
+            .filter(|m| m.original.as_ref().is_some_and(|o| o.source == source))
+    }
+
+    /// Like `all_generated_locations_for`, but takes a source filename from the
+    /// attached `"sources"` rather than an index.
+    ///
+    /// Yields nothing if the source filename isn't in the attached `"sources"`.
+    pub fn all_generated_locations_for_source(
+        &mut self,
+        source: &str,
+        original_line: u32,
+        original_column: Option<u32>,
+    ) -> AllGeneratedLocationsFor<'_> {
+        // An index past every source has no mappings.
+        let source = self.source_index(source).unwrap_or(u32::MAX);
+        self.all_generated_locations_for(source, original_line, original_column)
+    }
+}
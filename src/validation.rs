@@ -0,0 +1,200 @@
+//! Checking mappings against the original sources they refer to.
+//!
+//! A source map that is paired with the wrong version of its sources will
+//! happily map generated code to original positions that don't exist. These
+//! checks catch that mismatch up front, rather than leaving users to puzzle
+//! over nonsense positions.
+//!
+//! The source and name indices that the mappings use can also be compared
+//! against the lengths of the source map's `"sources"` and `"names"` arrays,
+//! to detect dangling indices, and `Mappings::span_anomalies` reports
+//! suspicious generated column spans.
+
+use super::{Mapping, Mappings, Observer, OriginalLocation};
+
+/// Why a mapping's original location doesn't fit within its source.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum OriginalLocationProblem {
+    /// The mapping's source index is out of bounds of the given sources.
+    UnknownSource,
+
+    /// The mapping's original line is past the end of its source.
+    LineOutOfBounds,
+
+    /// The mapping's original column is past the end of its original line.
+    ColumnOutOfBounds,
+}
+
+/// A mapping whose original location doesn't fit within its source.
+///
+/// Returned by `Mappings::check_against_sources` and
+/// `Mappings::check_against_source_lines`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct InvalidOriginalLocation<'a> {
+    /// The offending mapping.
+    pub mapping: &'a Mapping,
+
+    /// What is wrong with the mapping's original location.
+    pub problem: OriginalLocationProblem,
+}
+
+/// What is suspicious about a mapping's generated column span.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum SpanAnomalyKind {
+    /// The mapping's span doesn't cover any generated columns.
+    ZeroWidth,
+
+    /// The mapping is at the same generated location as the mapping before
+    /// it.
+    DuplicateGeneratedLocation,
+
+    /// The mapping's span ends before it begins.
+    Inverted,
+
+    /// The mapping's span extends past the start of the next mapping on the
+    /// same generated line.
+    Overlapping,
+}
+
+/// A mapping with a suspicious generated column span.
+///
+/// Returned by `Mappings::span_anomalies`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SpanAnomaly<'a> {
+    /// The offending mapping.
+    pub mapping: &'a Mapping,
+
+    /// What is suspicious about the mapping's span.
+    pub kind: SpanAnomalyKind,
+}
+
+impl<O: Observer> Mappings<O> {
+    /// Find every mapping whose generated column span is suspicious, ordered by
+    /// generated location.
+    ///
+    /// This computes column spans, if they haven't been computed already. A
+    /// mapping may have several anomalies, in which case it is reported once
+    /// for each of them.
+    pub fn span_anomalies(&mut self) -> Vec<SpanAnomaly<'_>> {
+        self.compute_column_spans();
+
+        let mut anomalies = vec![];
+        let mut by_generated = self.by_generated.iter().peekable();
+        let mut prev: Option<&Mapping> = None;
+        while let Some(mapping) = by_generated.next() {
+            let mut report = |kind| anomalies.push(SpanAnomaly { mapping, kind });
+
+            let location = (mapping.generated_line, mapping.generated_column);
+            if prev.is_some_and(|p| (p.generated_line, p.generated_column) == location) {
+                report(SpanAnomalyKind::DuplicateGeneratedLocation);
+            }
+
+            if let Some(last) = mapping.last_generated_column {
+                if last == mapping.generated_column {
+                    report(SpanAnomalyKind::ZeroWidth);
+                } else if last < mapping.generated_column {
+                    report(SpanAnomalyKind::Inverted);
+                }
+
+                let overlaps = by_generated.peek().is_some_and(|next| {
+                    next.generated_line == mapping.generated_line && last > next.generated_column
+                });
+                if overlaps {
+                    report(SpanAnomalyKind::Overlapping);
+                }
+            }
+
+            prev = Some(mapping);
+        }
+        anomalies
+    }
+
+    /// Find every mapping whose original line is past the end of its source,
+    /// given the number of lines in each source, indexed by source.
+    ///
+    /// The results are ordered by generated location.
+    pub fn check_against_sources(&self, line_counts: &[u32]) -> Vec<InvalidOriginalLocation<'_>> {
+        self.check_original_locations(|source, line, _| match line_counts.get(source as usize) {
+            None => Some(OriginalLocationProblem::UnknownSource),
+            Some(&count) if line >= count => Some(OriginalLocationProblem::LineOutOfBounds),
+            Some(_) => None,
+        })
+    }
+
+    /// Find every mapping whose original line or column is past the end of its
+    /// source, given the length, in columns, of each line of each source,
+    /// indexed by source.
+    ///
+    /// A column equal to its line's length is allowed, since it refers to the
+    /// end of the line. The results are ordered by generated location.
+    pub fn check_against_source_lines<L>(&self, line_lengths: &[L]) -> Vec<InvalidOriginalLocation<'_>>
+    where
+        L: AsRef<[u32]>,
+    {
+        self.check_original_locations(|source, line, column| {
+            let lines = match line_lengths.get(source as usize) {
+                None => return Some(OriginalLocationProblem::UnknownSource),
+                Some(lines) => lines.as_ref(),
+            };
+            match lines.get(line as usize) {
+                None => Some(OriginalLocationProblem::LineOutOfBounds),
+                Some(&length) if column > length => Some(OriginalLocationProblem::ColumnOutOfBounds),
+                Some(_) => None,
+            }
+        })
+    }
+
+    /// Get the source indices referenced by any mapping, sorted and without
+    /// duplicates.
+    pub fn source_indices_used(&self) -> Vec<u32> {
+        self.indices_used(|original| Some(original.source))
+    }
+
+    /// Get the name indices referenced by any mapping, sorted and without
+    /// duplicates.
+    pub fn name_indices_used(&self) -> Vec<u32> {
+        self.indices_used(|original| original.name)
+    }
+
+    /// Get the largest source index referenced by any mapping, if any.
+    ///
+    /// The source map's `"sources"` array must be longer than this.
+    pub fn max_source_index(&self) -> Option<u32> {
+        self.originals().map(|original| original.source).max()
+    }
+
+    /// Get the largest name index referenced by any mapping, if any.
+    ///
+    /// The source map's `"names"` array must be longer than this.
+    pub fn max_name_index(&self) -> Option<u32> {
+        self.originals().filter_map(|original| original.name).max()
+    }
+
+    fn originals(&self) -> impl Iterator<Item = &OriginalLocation> {
+        self.by_generated.iter().filter_map(|m| m.original.as_ref())
+    }
+
+    fn indices_used<F>(&self, index: F) -> Vec<u32>
+    where
+        F: Fn(&OriginalLocation) -> Option<u32>,
+    {
+        let mut indices: Vec<u32> = self.originals().filter_map(index).collect();
+        indices.sort_unstable();
+        indices.dedup();
+        indices
+    }
+
+    fn check_original_locations<F>(&self, mut check: F) -> Vec<InvalidOriginalLocation<'_>>
+    where
+        F: FnMut(u32, u32, u32) -> Option<OriginalLocationProblem>,
+    {
+        self.by_generated
+            .iter()
+            .filter_map(|mapping| {
+                let original = mapping.original.as_ref()?;
+                check(original.source, original.original_line, original.original_column)
+                    .map(|problem| InvalidOriginalLocation { mapping, problem })
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,97 @@
+//! Decoding the base 64 VLQs that make up a `"mappings"` string.
+//!
+//! This is the hottest code in the crate: every field of every segment goes
+//! through it. Rather than the general purpose iterator-based decoder of the
+//! `vlq` crate, it decodes directly out of the input slice, classifies bytes
+//! with a lookup table, and handles the common single digit case up front.
+
+use super::Error;
+
+const CONTINUED: u8 = 1 << 5;
+const MASK: u8 = CONTINUED - 1;
+const SHIFT: u32 = 5;
+
+// Marks bytes that are not base 64 digits.
+const __: u8 = 0xff;
+
+// The value of each base 64 digit, indexed by its byte.
+static BASE64: [u8; 256] = [
+    __, __, __, __, __, __, __, __, __, __, __, __, __, __, __, __,
+    __, __, __, __, __, __, __, __, __, __, __, __, __, __, __, __,
+    __, __, __, __, __, __, __, __, __, __, __, 62, __, __, __, 63,
+    52, 53, 54, 55, 56, 57, 58, 59, 60, 61, __, __, __, __, __, __,
+    __,  0,  1,  2,  3,  4,  5,  6,  7,  8,  9, 10, 11, 12, 13, 14,
+    15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25, __, __, __, __, __,
+    __, 26, 27, 28, 29, 30, 31, 32, 33, 34, 35, 36, 37, 38, 39, 40,
+    41, 42, 43, 44, 45, 46, 47, 48, 49, 50, 51, __, __, __, __, __,
+    __, __, __, __, __, __, __, __, __, __, __, __, __, __, __, __,
+    __, __, __, __, __, __, __, __, __, __, __, __, __, __, __, __,
+    __, __, __, __, __, __, __, __, __, __, __, __, __, __, __, __,
+    __, __, __, __, __, __, __, __, __, __, __, __, __, __, __, __,
+    __, __, __, __, __, __, __, __, __, __, __, __, __, __, __, __,
+    __, __, __, __, __, __, __, __, __, __, __, __, __, __, __, __,
+    __, __, __, __, __, __, __, __, __, __, __, __, __, __, __, __,
+    __, __, __, __, __, __, __, __, __, __, __, __, __, __, __, __,
+];
+
+/// Decode the VLQ at the given position in the input, advancing the position
+/// past it.
+///
+/// Supports the same range of numbers as `vlq::decode`, and reports the same
+/// errors.
+#[inline]
+pub(crate) fn decode(input: &[u8], position: &mut usize) -> Result<i64, Error> {
+    let digit = digit_at(input, position)?;
+
+    // Most fields are small deltas that fit in a single digit.
+    if digit & CONTINUED == 0 {
+        return Ok(with_sign((digit & MASK) as u64));
+    }
+
+    let mut accum = (digit & MASK) as u64;
+    let mut shift = SHIFT;
+    loop {
+        let digit = digit_at(input, position)?;
+        if shift >= 64 {
+            return Err(Error::VlqOverflow);
+        }
+        accum = accum
+            .checked_add(((digit & MASK) as u64) << shift)
+            .ok_or(Error::VlqOverflow)?;
+        if digit & CONTINUED == 0 {
+            break;
+        }
+        shift += SHIFT;
+    }
+
+    if accum / 2 > (i64::MAX as u64) {
+        return Err(Error::VlqOverflow);
+    }
+    Ok(with_sign(accum))
+}
+
+#[inline]
+fn digit_at(input: &[u8], position: &mut usize) -> Result<u8, Error> {
+    let byte = match input.get(*position) {
+        Some(&byte) => byte,
+        None => return Err(Error::VlqUnexpectedEof),
+    };
+    *position += 1;
+
+    let digit = BASE64[byte as usize];
+    if digit == __ {
+        return Err(Error::VlqInvalidBase64);
+    }
+    Ok(digit)
+}
+
+// The low bit holds the sign.
+#[inline]
+fn with_sign(accum: u64) -> i64 {
+    let abs_value = (accum / 2) as i64;
+    if accum & 1 != 0 {
+        -abs_value
+    } else {
+        abs_value
+    }
+}
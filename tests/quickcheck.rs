@@ -7,7 +7,6 @@ use quickcheck::{Arbitrary, Gen};
 use source_map_mappings::{Bias, Error};
 use std::cmp::Ordering;
 use std::fmt;
-use std::i64;
 use std::iter;
 use std::marker::PhantomData;
 
@@ -27,7 +26,7 @@ where
         Vlq(g.gen_range(R::low(), R::high()), PhantomData)
     }
 
-    fn shrink(&self) -> Box<Iterator<Item = Self>> {
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
         Box::new(self.0.shrink().map(|x| Vlq(x, PhantomData)))
     }
 }
@@ -86,7 +85,7 @@ where
         }
     }
 
-    fn shrink(&self) -> Box<Iterator<Item = Self>> {
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
         match *self {
             Mapping::Generated { generated_column } => Box::new(
                 generated_column
@@ -207,7 +206,7 @@ where
         GeneratedLine(Vec::arbitrary(g))
     }
 
-    fn shrink(&self) -> Box<Iterator<Item = Self>> {
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
         Box::new(self.0.shrink().map(|v| GeneratedLine(v)))
     }
 }
@@ -237,7 +236,7 @@ where
         Mappings(Vec::arbitrary(g))
     }
 
-    fn shrink(&self) -> Box<Iterator<Item = Self>> {
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
         Box::new(self.0.shrink().map(|v| Mappings(v)))
     }
 }
@@ -304,6 +303,63 @@ quickcheck! {
         Ok(())
     }
 
+    fn parse_in_steps(mappings: Mappings<SmallPositives>, max_segments: usize) -> Result<(), Error> {
+        let mappings_string = mappings.to_string();
+        let input = mappings_string.as_bytes();
+        let expected = source_map_mappings::parse_mappings::<()>(input)?;
+
+        let mut parser = source_map_mappings::MappingsParser::<()>::new(input);
+        while !parser.parse_step(max_segments % 8 + 1)? {}
+        let actual = parser.finish()?;
+
+        assert_eq!(actual.by_generated_location(), expected.by_generated_location());
+        Ok(())
+    }
+
+    fn serialize_round_trips(mappings: Mappings<SmallPositives>) -> Result<(), Error> {
+        let mappings_string = mappings.to_string();
+        let expected = source_map_mappings::parse_mappings::<()>(mappings_string.as_bytes())?;
+
+        let serialized = expected.serialize();
+        let actual = source_map_mappings::parse_mappings::<()>(serialized.as_bytes())?;
+
+        assert_eq!(actual.by_generated_location(), expected.by_generated_location());
+        Ok(())
+    }
+
+    fn parse_for_positions(
+        mappings: Mappings<SmallPositives>,
+        positions: Vec<(u32, u32)>
+    ) -> Result<(), Error> {
+        let mappings_string = mappings.to_string();
+        let input = mappings_string.as_bytes();
+        let full = source_map_mappings::parse_mappings::<()>(input)?;
+
+        // Wrap the positions around the maximum line and column, plus a little
+        // extra, so that they're likely to hit interesting places.
+        let max_line = full.by_generated_location().iter().map(|m| m.generated_line).max().unwrap_or(0);
+        let max_col = full.by_generated_location().iter().map(|m| m.generated_column).max().unwrap_or(0);
+        let positions: Vec<_> = positions
+            .into_iter()
+            .map(|(line, col)| (line % (max_line + 2), col % (max_col + 2)))
+            .collect();
+
+        let sparse = source_map_mappings::parse_mappings_for_positions::<()>(input, &positions)?;
+        assert!(sparse.by_generated_location().len() <= full.by_generated_location().len());
+
+        for &(line, col) in &positions {
+            for bias in &[Bias::GreatestLowerBound, Bias::LeastUpperBound] {
+                let location = |m: &source_map_mappings::Mapping| (m.generated_line, m.generated_column);
+                assert_eq!(
+                    sparse.original_location_for(line, col, *bias).map(&location),
+                    full.original_location_for(line, col, *bias).map(&location),
+                );
+            }
+        }
+
+        Ok(())
+    }
+
     fn compute_column_spans(mappings: Mappings<SmallPositives>) -> Result<(), Error> {
         let mappings_string = mappings.to_string();
         let mut mappings = source_map_mappings::parse_mappings::<()>(mappings_string.as_bytes())?;
@@ -399,6 +455,70 @@ quickcheck! {
         Ok(())
     }
 
+    fn original_locations_for_batch(
+        mappings: Mappings<SmallPositives>,
+        queries: Vec<(u32, u32)>,
+        lub: bool
+    ) -> Result<(), Error> {
+        let mappings_string = mappings.to_string();
+        let mappings = source_map_mappings::parse_mappings::<()>(mappings_string.as_bytes())?;
+
+        let bias = if lub {
+            Bias::LeastUpperBound
+        } else {
+            Bias::GreatestLowerBound
+        };
+
+        // Keep the queries near the mappings, so that some of them hit.
+        let queries: Vec<_> = queries.iter().map(|&(line, col)| (line % 8, col % 16)).collect();
+
+        let location = |m: Option<&source_map_mappings::Mapping>| {
+            m.map(|m| (m.generated_line, m.generated_column))
+        };
+        let batch: Vec<_> = mappings
+            .original_locations_for_batch(&queries, bias)
+            .into_iter()
+            .map(location)
+            .collect();
+        let single: Vec<_> = queries
+            .iter()
+            .map(|&(line, col)| location(mappings.original_location_for(line, col, bias)))
+            .collect();
+
+        assert_eq!(batch, single);
+        Ok(())
+    }
+
+    fn original_location_for_with_hint(
+        mappings: Mappings<SmallPositives>,
+        queries: Vec<(u32, u32)>,
+        lub: bool
+    ) -> Result<(), Error> {
+        let mappings_string = mappings.to_string();
+        let mappings = source_map_mappings::parse_mappings::<()>(mappings_string.as_bytes())?;
+
+        let bias = if lub {
+            Bias::LeastUpperBound
+        } else {
+            Bias::GreatestLowerBound
+        };
+
+        let location = |m: Option<&source_map_mappings::Mapping>| {
+            m.map(|m| (m.generated_line, m.generated_column))
+        };
+
+        // Keep the queries near the mappings, so that some of them hit.
+        let mut hint = source_map_mappings::QueryHint::default();
+        for &(line, col) in &queries {
+            let (line, col) = (line % 8, col % 16);
+            assert_eq!(
+                location(mappings.original_location_for_with_hint(line, col, bias, &mut hint)),
+                location(mappings.original_location_for(line, col, bias))
+            );
+        }
+        Ok(())
+    }
+
     fn original_mappings_have_original(
         mappings: Mappings<SmallPositives>
     ) -> Result<bool, Error> {
@@ -526,11 +646,7 @@ quickcheck! {
             .unwrap();
         let source = source % (max_source + 1);
         let mut line = line % (max_line + 1);
-        let mut col = if let Some(col) = col {
-            Some(col % (max_col + 1))
-        } else {
-            None
-        };
+        let mut col = col.map(|col| col % (max_col + 1));
 
         let mut count = 0;
         {
@@ -1,6 +1,7 @@
 extern crate source_map_mappings;
 
-use source_map_mappings::{parse_mappings, Bias, Mapping, Mappings, OriginalLocation};
+use source_map_mappings::{mappings_equivalent, parse_mappings, Bias, Mapping, Mappings, MappingsBuilder,
+                          MergePolicy, OriginalLocation, QueryHint, QueryOptions};
 
 #[test]
 fn parse_empty_mappings() {
@@ -15,7 +16,7 @@ fn invalid_mappings() {
 }
 
 // From mozilla/source-map's test/util.js `exports.testMap`.
-const TEST_MAPPINGS: &'static [u8] =
+const TEST_MAPPINGS: &[u8] =
     b"CAAC,IAAI,IAAM,SAAUA,GAClB,OAAOC,IAAID;CCDb,IAAI,IAAM,SAAUE,GAClB,OAAOA";
 
 #[test]
@@ -259,7 +260,7 @@ fn test_mapping_back_exactly() {
 
 // From mozilla/source-map's test/test-source-map-consumer.js's "test
 // allGeneratedPositionsFor for line" test case.
-const TEST_MAPPINGS_2: &'static [u8] = b";EAAC,ACAA;EACA,CAAC;EACD";
+const TEST_MAPPINGS_2: &[u8] = b";EAAC,ACAA;EACA,CAAC;EACD";
 
 #[test]
 fn test_all_generated_locations_for_some_line() {
@@ -301,7 +302,7 @@ fn test_all_generated_locations_for_some_line() {
 
 // Taken from mozilla/source-map's test/test-source-map-consumer.js's "test
 // allGeneratedPositionsFor for line fuzzy"
-const TEST_MAPPINGS_3: &'static [u8] = b";EAAC,ACAA;;EAEA";
+const TEST_MAPPINGS_3: &[u8] = b";EAAC,ACAA;;EAEA";
 
 #[test]
 fn test_all_generated_locations_for_line_fuzzy() {
@@ -332,7 +333,7 @@ fn test_all_generated_locations_for_line_fuzzy() {
 
 // Taken from mozilla/source-map's test/test-source-map-consumer.js's "test
 // allGeneratedPositionsFor for column".
-const TEST_MAPPINGS_4: &'static [u8] = b"EAAC,CAAA";
+const TEST_MAPPINGS_4: &[u8] = b"EAAC,CAAA";
 
 #[test]
 fn test_all_generated_locations_for_column() {
@@ -412,7 +413,7 @@ fn test_all_generated_locations_for_column_fuzzy() {
 
 // From mozilla/source-map's test/test-source-map-consumer.js's "test
 // allGeneratedPositionsFor for column on different line fuzzy".
-const TEST_MAPPINGS_5: &'static [u8] = b";EACC,CAAA";
+const TEST_MAPPINGS_5: &[u8] = b";EACC,CAAA";
 
 #[test]
 fn test_all_generated_locations_for_column_on_different_line_fuzzy() {
@@ -425,3 +426,2687 @@ fn test_all_generated_locations_for_column_on_different_line_fuzzy() {
 
     assert!(mappings_on_source_0_line_0_column_0.is_empty());
 }
+
+#[test]
+fn test_all_generated_locations_for_with_bias() {
+    // Generated (0, 0) and (0, 5) map to original (1, 2), generated (1, 0) to
+    // original (1, 6), and generated (2, 0) to original (3, 0).
+    let mut mappings = parse_mappings::<()>(b"AACE,KAAA;AAAI;AAEN").unwrap();
+
+    let mut generated = |line, column, bias| {
+        mappings
+            .all_generated_locations_for_with_bias(0, line, column, bias)
+            .map(|m| (m.generated_line, m.generated_column))
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        generated(2, None, Bias::GreatestLowerBound),
+        vec![(0, 0), (0, 5), (1, 0)]
+    );
+    assert_eq!(generated(2, None, Bias::LeastUpperBound), vec![(2, 0)]);
+    assert_eq!(generated(0, None, Bias::GreatestLowerBound), vec![]);
+
+    assert_eq!(
+        generated(1, Some(4), Bias::GreatestLowerBound),
+        vec![(0, 0), (0, 5)]
+    );
+    assert_eq!(generated(1, Some(4), Bias::LeastUpperBound), vec![(1, 0)]);
+    assert_eq!(generated(1, Some(5), Bias::ClosestMatch), vec![(1, 0)]);
+    // Column fuzzing never slides onto another original line.
+    assert_eq!(generated(1, Some(7), Bias::LeastUpperBound), vec![]);
+}
+
+#[test]
+fn test_sections_original_location_for() {
+    use source_map_mappings::sections::{MappingsSections, SectionMapping};
+
+    let mut sections = MappingsSections::<()>::new();
+    // The second section is pushed first to check that sections get ordered
+    // by their offsets, but keep their push order as their index.
+    assert_eq!(
+        sections.push_section(1, 5, parse_mappings(b"AAAA,EAAE").unwrap()),
+        0
+    );
+    assert_eq!(
+        sections.push_section(0, 0, parse_mappings(b"AAAA;AACA").unwrap()),
+        1
+    );
+
+    assert_eq!(sections.sections()[0].index(), 1);
+    assert_eq!(sections.sections()[1].index(), 0);
+
+    // Within the first section.
+    let found = sections
+        .original_location_for(1, 3, Bias::GreatestLowerBound)
+        .unwrap();
+    assert_eq!(found.section, 1);
+    assert_eq!(found.generated_line(), Some(1));
+    assert_eq!(found.generated_column(), Some(0));
+    assert_eq!(found.mapping.original.as_ref().unwrap().original_line, 1);
+
+    // On the first line of the second section, columns are relative to the
+    // section's column offset.
+    let found = sections
+        .original_location_for(1, 7, Bias::GreatestLowerBound)
+        .unwrap();
+    assert_eq!(found.section, 0);
+    assert_eq!(found.mapping.generated_column, 2);
+    assert_eq!(found.generated_line(), Some(1));
+    assert_eq!(found.generated_column(), Some(7));
+
+    // Fuzzy matching does not slide into the next section.
+    assert!(
+        sections
+            .original_location_for(1, 4, Bias::LeastUpperBound)
+            .is_none()
+    );
+
+    // A mapping that its section's offset pushes past `u32::MAX` has no
+    // absolute location.
+    let mut sections = MappingsSections::<()>::new();
+    sections.push_section(u32::MAX, u32::MAX, parse_mappings(b"AAAA,CAAA;AAAA").unwrap());
+    let found = sections
+        .original_location_for(u32::MAX, u32::MAX, Bias::LeastUpperBound)
+        .unwrap();
+    assert_eq!(found.generated_line(), Some(u32::MAX));
+    assert_eq!(found.generated_column(), Some(u32::MAX));
+    let beyond = SectionMapping {
+        mapping: &sections.sections()[0].mappings().by_generated_location()[1],
+        ..found
+    };
+    assert_eq!(beyond.generated_line(), Some(u32::MAX));
+    assert_eq!(beyond.generated_column(), None);
+    let next_line = SectionMapping {
+        mapping: &sections.sections()[0].mappings().by_generated_location()[2],
+        ..found
+    };
+    assert_eq!(next_line.generated_line(), None);
+    assert_eq!(next_line.generated_column(), Some(0));
+}
+
+#[test]
+fn test_sections_generated_location_for() {
+    use source_map_mappings::sections::MappingsSections;
+
+    let tables = |sources: &[&str]| -> (Vec<String>, Vec<String>) {
+        (sources.iter().map(|s| s.to_string()).collect(), vec![])
+    };
+
+    let mut sections = MappingsSections::<()>::new();
+    // "a.js" line 0 at column 0, and "b.js" line 0 at column 2.
+    let (sources, names) = tables(&["a.js", "b.js"]);
+    sections.push_section(
+        0,
+        0,
+        parse_mappings(b"AAAA,ECAA").unwrap().with_string_tables(sources, names),
+    );
+    // "b.js" line 0 at columns 1 and 3 of the section.
+    let (sources, names) = tables(&["b.js"]);
+    sections.push_section(
+        2,
+        4,
+        parse_mappings(b"CAAA,EAAA").unwrap().with_string_tables(sources, names),
+    );
+
+    let found = sections
+        .generated_location_for("b.js", 0, 0, Bias::GreatestLowerBound)
+        .unwrap();
+    assert_eq!(found.section, 0);
+    assert_eq!((found.generated_line(), found.generated_column()), (Some(0), Some(2)));
+
+    assert!(sections
+        .generated_location_for("c.js", 0, 0, Bias::GreatestLowerBound)
+        .is_none());
+
+    let all: Vec<_> = sections
+        .all_generated_locations_for("b.js", 0, None)
+        .iter()
+        .map(|m| (m.section, m.generated_line().unwrap(), m.generated_column().unwrap()))
+        .collect();
+    assert_eq!(all, vec![(0, 0, 2), (1, 2, 5), (1, 2, 7)]);
+}
+
+#[test]
+fn test_sections_generated_location_for_stays_in_source() {
+    use source_map_mappings::sections::MappingsSections;
+
+    let strings = |strings: &[&str]| strings.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+    let mut sections = MappingsSections::<()>::new();
+    // "a.js" line 0 at column 0, and "b.js" line 5 at column 2.
+    sections.push_section(
+        0,
+        0,
+        parse_mappings(b"AAAA,ECKA")
+            .unwrap()
+            .with_string_tables(strings(&["a.js", "b.js"]), vec![]),
+    );
+    // "a.js" line 5 at column 0.
+    sections.push_section(
+        10,
+        0,
+        parse_mappings(b"AAKA")
+            .unwrap()
+            .with_string_tables(strings(&["a.js"]), vec![]),
+    );
+
+    // The first section's closest mapping is into "b.js", so the second
+    // section's is found instead.
+    let found = sections
+        .generated_location_for("a.js", 3, 0, Bias::LeastUpperBound)
+        .unwrap();
+    assert_eq!(found.section, 1);
+    assert_eq!((found.generated_line(), found.generated_column()), (Some(10), Some(0)));
+    assert_eq!(found.mapping.original.as_ref().unwrap().original_line, 5);
+}
+
+#[test]
+fn test_sections_flatten() {
+    use source_map_mappings::sections::MappingsSections;
+
+    let mut sections = MappingsSections::<()>::new();
+    // Source 1 with name 0 at column 0, and source 0 at column 2.
+    sections.push_section(0, 0, parse_mappings(b"ACAAA,EDAA").unwrap());
+    // Source 0 at column 1 of the section, and on the next line.
+    sections.push_section(1, 4, parse_mappings(b"CAAA;AACA").unwrap());
+
+    let (flattened, remappings) = sections.flatten().unwrap();
+    assert_eq!(remappings[0].sources, vec![0, 1]);
+    assert_eq!(remappings[0].names, vec![0]);
+    assert_eq!(remappings[1].sources, vec![2]);
+    assert!(remappings[1].names.is_empty());
+
+    let expected = parse_mappings::<()>(b"ACAAA,EDAA;KEAA;AACA").unwrap();
+    assert_eq!(flattened.by_generated_location(), expected.by_generated_location());
+
+    // With string tables, shared sources are deduplicated.
+    let strings = |strings: &[&str]| strings.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+    let mut sections = MappingsSections::<()>::new();
+    sections.push_section(
+        0,
+        0,
+        parse_mappings(b"ACAAA,EDAA")
+            .unwrap()
+            .with_string_tables(strings(&["a.js", "b.js"]), strings(&["foo"])),
+    );
+    sections.push_section(
+        1,
+        4,
+        parse_mappings(b"CAAA;AACA")
+            .unwrap()
+            .with_string_tables(strings(&["b.js"]), vec![]),
+    );
+
+    let (flattened, remappings) = sections.flatten().unwrap();
+    assert_eq!(remappings[1].sources, vec![1]);
+    assert_eq!(flattened.sources(), &strings(&["a.js", "b.js"])[..]);
+    assert_eq!(flattened.names(), &strings(&["foo"])[..]);
+    assert_eq!(flattened.to_string(), "ACAAA,EDAA;KCAA;AACA");
+}
+
+#[test]
+fn test_sections_flatten_out_of_table_indices() {
+    use source_map_mappings::sections::MappingsSections;
+    use source_map_mappings::Error;
+
+    // Source 1 and name 1 are past the end of the section's string tables.
+    let strings = |strings: &[&str]| strings.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+    let mut sections = MappingsSections::<()>::new();
+    sections.push_section(
+        0,
+        0,
+        parse_mappings(b"AAAA,CCAAC")
+            .unwrap()
+            .with_string_tables(strings(&["a.js"]), strings(&["foo"])),
+    );
+    sections.push_section(
+        1,
+        0,
+        parse_mappings(b"AAAAA")
+            .unwrap()
+            .with_string_tables(strings(&["a.js"]), strings(&["foo"])),
+    );
+
+    let (flattened, remappings) = sections.flatten().unwrap();
+    assert_eq!(remappings[0].sources, vec![0, 1]);
+    assert_eq!(remappings[0].names, vec![0, 1]);
+    assert_eq!(remappings[1].sources, vec![0]);
+    assert_eq!(remappings[1].names, vec![0]);
+    assert_eq!(flattened.sources(), &strings(&["a.js", ""])[..]);
+    assert_eq!(flattened.names(), &strings(&["foo", ""])[..]);
+    assert_eq!(flattened.to_string(), "AAAA,CCAAC;ADAAD");
+
+    // A source index of `u32::MAX` can't be given a count of sources.
+    let mut sections = MappingsSections::<()>::new();
+    sections.push_section(
+        0,
+        0,
+        Mappings::from_mappings(vec![Mapping {
+            generated_line: 0,
+            generated_column: 0,
+            last_generated_column: None,
+            original: Some(OriginalLocation {
+                source: u32::MAX,
+                original_line: 0,
+                original_column: 0,
+                name: None,
+            }),
+        }])
+        .unwrap(),
+    );
+    assert_eq!(sections.flatten().err(), Some(Error::UnexpectedlyBigSource));
+}
+
+#[test]
+fn test_sections_flatten_offset_overflow() {
+    use source_map_mappings::sections::MappingsSections;
+    use source_map_mappings::Error;
+
+    let mut sections = MappingsSections::<()>::new();
+    sections.push_section(0, u32::MAX, parse_mappings(b"CAAA").unwrap());
+    assert_eq!(sections.flatten().err(), Some(Error::UnexpectedlyBigGeneratedColumn));
+
+    let mut sections = MappingsSections::<()>::new();
+    sections.push_section(u32::MAX, 0, parse_mappings(b"AAAA;AAAA").unwrap());
+    assert_eq!(sections.flatten().err(), Some(Error::UnexpectedlyBigGeneratedLine));
+
+    // Mappings that land exactly on `u32::MAX` are fine.
+    let mut sections = MappingsSections::<()>::new();
+    sections.push_section(u32::MAX, u32::MAX - 1, parse_mappings(b"CAAA").unwrap());
+    let (flattened, _) = sections.flatten().unwrap();
+    let m = &flattened.by_generated_location()[0];
+    assert_eq!((m.generated_line, m.generated_column), (u32::MAX, u32::MAX));
+}
+
+#[test]
+fn test_strip_names() {
+    let mut mappings = parse_mappings::<()>(TEST_MAPPINGS).unwrap();
+    assert!(mappings.by_original_location().any(|m| m.original.as_ref().unwrap().name.is_some()));
+
+    mappings.strip_names();
+
+    assert!(
+        mappings
+            .by_generated_location()
+            .iter()
+            .all(|m| m.original.as_ref().is_none_or(|o| o.name.is_none()))
+    );
+    assert!(mappings.by_original_location().all(|m| m.original.as_ref().unwrap().name.is_none()));
+    assert_eq!(mappings.by_generated_location().len(), 13);
+}
+
+#[test]
+fn test_strip_original() {
+    let mut mappings = parse_mappings::<()>(TEST_MAPPINGS).unwrap();
+    mappings.compute_column_spans();
+    mappings.strip_original();
+
+    assert_eq!(mappings.by_generated_location().len(), 13);
+    assert!(mappings.by_generated_location().iter().all(|m| m.original.is_none()));
+    assert_eq!(mappings.by_original_location().count(), 0);
+    assert_eq!(
+        mappings.original_location_for(0, 5, Bias::GreatestLowerBound),
+        Some(&Mapping {
+            generated_line: 0,
+            generated_column: 5,
+            last_generated_column: Some(9),
+            original: None,
+        })
+    );
+}
+
+#[test]
+fn test_insert_and_remove_at_generated() {
+    let mut mappings = parse_mappings::<()>(b"AAAA,IAAA;AAAA").unwrap();
+    mappings.compute_column_spans();
+
+    mappings.insert(Mapping {
+        generated_line: 0,
+        generated_column: 2,
+        last_generated_column: None,
+        original: Some(OriginalLocation {
+            source: 1,
+            original_line: 0,
+            original_column: 0,
+            name: None,
+        }),
+    });
+
+    let mut expected = parse_mappings::<()>(b"AAAA,ECAA,EDAA;AAAA").unwrap();
+    expected.compute_column_spans();
+    assert_eq!(mappings.by_generated_location(), expected.by_generated_location());
+    assert_eq!(mappings.by_original_source(1).len(), 1);
+
+    let removed = mappings.remove_at_generated(0, 2);
+    assert_eq!(removed.len(), 1);
+    assert!(mappings.remove_at_generated(0, 3).is_empty());
+
+    let mut expected = parse_mappings::<()>(b"AAAA,IAAA;AAAA").unwrap();
+    expected.compute_column_spans();
+    assert_eq!(mappings.by_generated_location(), expected.by_generated_location());
+    assert!(mappings.by_original_source(1).is_empty());
+}
+
+#[test]
+fn test_insert_and_remove_keep_line_index() {
+    // A line long enough to be split into column buckets, between two short
+    // lines.
+    let mut input = b"AAAA;".to_vec();
+    for _ in 0..2000 {
+        input.extend_from_slice(b"CAAA,");
+    }
+    input.extend_from_slice(b"CAAA;;AAAA");
+    let mut mappings = parse_mappings::<()>(&input).unwrap();
+
+    let check = |mappings: &source_map_mappings::Mappings| {
+        let expected = source_map_mappings::Mappings::<()>::from_mappings(mappings.by_generated_location().to_vec())
+            .unwrap();
+        for line in 0..6 {
+            assert_eq!(mappings.mappings_in_generated_line(line), expected.mappings_in_generated_line(line));
+            for column in (0..4010).step_by(3) {
+                for &bias in &[Bias::GreatestLowerBound, Bias::LeastUpperBound] {
+                    assert_eq!(
+                        mappings.original_location_for(line, column, bias),
+                        expected.original_location_for(line, column, bias)
+                    );
+                }
+            }
+        }
+    };
+
+    let at = |line, column| Mapping {
+        generated_line: line,
+        generated_column: column,
+        last_generated_column: None,
+        original: None,
+    };
+
+    mappings.insert(at(1, 1001));
+    check(&mappings);
+    mappings.insert(at(0, 7));
+    check(&mappings);
+    mappings.insert(at(2, 3));
+    check(&mappings);
+    mappings.insert(at(5, 0));
+    check(&mappings);
+
+    mappings.remove_at_generated(1, 1001);
+    check(&mappings);
+    mappings.remove_at_generated(0, 0);
+    check(&mappings);
+    mappings.remove_at_generated(3, 0);
+    check(&mappings);
+
+    // The long line shrinks below the size that gets column buckets.
+    mappings.retain(|m| m.generated_line != 1 || m.generated_column < 1500);
+    for column in 1000..1500 {
+        mappings.remove_at_generated(1, column);
+    }
+    check(&mappings);
+}
+
+#[test]
+fn test_retain() {
+    // Source 0 at columns 0 and 4, source 1 at column 2.
+    let mut mappings = parse_mappings::<()>(b"AAAA,ECAA,EDAA").unwrap();
+    mappings.compute_column_spans();
+    assert_eq!(mappings.by_original_source(1).len(), 1);
+
+    mappings.retain(|m| m.original.as_ref().is_none_or(|o| o.source != 1));
+
+    let mut expected = parse_mappings::<()>(b"AAAA,IAAA").unwrap();
+    expected.compute_column_spans();
+    assert_eq!(mappings.by_generated_location(), expected.by_generated_location());
+    assert!(mappings.by_original_source(1).is_empty());
+}
+
+#[test]
+fn test_compose() {
+    use source_map_mappings::compose::ComposeRemapping;
+
+    // The inner map, from B back to A: B's line 0 column 0 maps to A's line 3
+    // column 1, and B's line 0 column 5 maps to A's line 4 column 0 with
+    // name 0.
+    let inner = parse_mappings::<()>(b"AAGC,KACDA").unwrap();
+
+    // The outer map, from C back to B and another source: C's column 0 maps to
+    // B's column 2, C's column 3 to B's column 7 with name 0, C's column 6 to
+    // the other source, and C's column 9 to B's line 1.
+    let outer = parse_mappings::<()>(b"AAAE,GAAKA,GCAA,GDCA").unwrap();
+
+    let composed = outer.compose(
+        &inner,
+        &ComposeRemapping {
+            source: 0,
+            outer_sources: &[0, 1],
+            outer_names: &[1],
+            inner_sources: &[2],
+            inner_names: &[0],
+        },
+    );
+
+    let originals: Vec<_> = composed
+        .by_generated_location()
+        .iter()
+        .map(|m| {
+            m.original
+                .as_ref()
+                .map(|o| (o.source, o.original_line, o.original_column, o.name))
+        })
+        .collect();
+    assert_eq!(
+        originals,
+        vec![
+            Some((2, 3, 1, None)),
+            Some((2, 4, 0, Some(0))),
+            Some((1, 0, 7, None)),
+            None,
+        ]
+    );
+}
+
+#[test]
+fn test_offset_generated() {
+    let mut mappings = parse_mappings::<()>(b"AAAA,IAAA;CAAA").unwrap();
+    mappings.compute_column_spans();
+
+    mappings.offset_generated(2, 10).unwrap();
+    assert_eq!(mappings.to_string(), ";;UAAA,IAAA;CAAA");
+
+    let spans: Vec<_> = mappings
+        .by_generated_location()
+        .iter()
+        .map(|m| m.last_generated_column)
+        .collect();
+    assert_eq!(spans, vec![Some(14), None, None]);
+
+    let m = mappings.original_location_for(2, 12, Bias::GreatestLowerBound).unwrap();
+    assert_eq!(m.generated_column, 10);
+}
+
+#[test]
+fn test_offset_generated_overflow() {
+    use source_map_mappings::Error;
+
+    let near_max = |line, column| Mapping {
+        generated_line: line,
+        generated_column: column,
+        last_generated_column: None,
+        original: None,
+    };
+    let mut mappings =
+        Mappings::<()>::from_mappings(vec![near_max(0, u32::MAX - 1), near_max(u32::MAX - 1, 0)]).unwrap();
+
+    assert_eq!(mappings.offset_generated(2, 0), Err(Error::UnexpectedlyBigGeneratedLine));
+    assert_eq!(mappings.offset_generated(0, 2), Err(Error::UnexpectedlyBigGeneratedColumn));
+    assert_eq!(mappings.by_generated_location()[0].generated_column, u32::MAX - 1);
+    assert_eq!(mappings.by_generated_location()[1].generated_line, u32::MAX - 1);
+
+    assert_eq!(mappings.offset_generated(1, 1), Ok(()));
+    let locations: Vec<_> = mappings
+        .by_generated_location()
+        .iter()
+        .map(|m| (m.generated_line, m.generated_column))
+        .collect();
+    assert_eq!(locations, vec![(1, u32::MAX), (u32::MAX, 0)]);
+}
+
+#[test]
+fn test_append_with_offset() {
+    let mut mappings = parse_mappings::<()>(b"AAAAA;CAAA").unwrap();
+    let other = parse_mappings::<()>(b"EAAAA;AACA").unwrap();
+
+    mappings.append_with_offset(other, 2, 1, 1).unwrap();
+    assert_eq!(mappings.to_string(), "AAAAA;CAAA;ECAAC;AACA");
+    assert_eq!(mappings.by_original_source(1).len(), 2);
+}
+
+#[test]
+fn test_append_with_offset_overflow() {
+    use source_map_mappings::Error;
+
+    let mut mappings = parse_mappings::<()>(b"AAAAA").unwrap();
+    let other = || parse_mappings::<()>(b"AAAAA;ACAAC").unwrap();
+
+    assert_eq!(
+        mappings.append_with_offset(other(), u32::MAX, 0, 0),
+        Err(Error::UnexpectedlyBigGeneratedLine)
+    );
+    assert_eq!(
+        mappings.append_with_offset(other(), 0, u32::MAX, 0),
+        Err(Error::UnexpectedlyBigSource)
+    );
+    assert_eq!(
+        mappings.append_with_offset(other(), 0, 0, u32::MAX),
+        Err(Error::UnexpectedlyBigName)
+    );
+    assert_eq!(mappings.to_string(), "AAAAA");
+
+    assert_eq!(mappings.append_with_offset(other(), u32::MAX - 1, u32::MAX - 1, 0), Ok(()));
+    assert_eq!(mappings.len(), 3);
+    let last = mappings.by_generated_location()[2].clone();
+    assert_eq!(last.generated_line, u32::MAX);
+    assert_eq!(last.original.unwrap().source, u32::MAX);
+}
+
+#[test]
+fn test_slice_generated() {
+    let mappings = parse_mappings::<()>(b"AAAA;CACA,EAAA;;EACA").unwrap();
+
+    let slice = mappings.slice_generated(1, 3);
+    assert_eq!(slice.to_string(), "CACA,EAAA");
+
+    let slice = mappings.slice_generated(3, 10);
+    assert_eq!(slice.to_string(), "EAEA");
+
+    assert!(mappings.slice_generated(2, 1).by_generated_location().is_empty());
+}
+
+#[test]
+fn test_split_off() {
+    let input = b"AAAA;CACA,EAAA;;EACA";
+
+    let mut mappings = parse_mappings::<()>(input).unwrap();
+    mappings.compute_column_spans();
+    let tail = mappings.split_off(1, true);
+    assert_eq!(mappings.to_string(), "AAAA");
+    assert_eq!(tail.to_string(), "CACA,EAAA;;EACA");
+    assert_eq!(tail.by_generated_location()[0].last_generated_column, Some(3));
+    assert_eq!(mappings.by_original_source(0).len(), 1);
+
+    let mut mappings = parse_mappings::<()>(input).unwrap();
+    let tail = mappings.split_off(3, false);
+    assert_eq!(mappings.to_string(), "AAAA;CACA,EAAA");
+    assert_eq!(tail.to_string(), ";;;EAEA");
+    assert_eq!(mappings.original_location_for(3, 2, Bias::LeastUpperBound), None);
+
+    let mut mappings = parse_mappings::<()>(input).unwrap();
+    assert!(mappings.split_off(10, true).is_empty());
+    assert_eq!(mappings.len(), 4);
+    assert_eq!(mappings.split_off(0, true).len(), 4);
+    assert!(mappings.is_empty());
+}
+
+#[test]
+fn test_split_by_source() {
+    // Source 0 at column 0, generated-only at column 1, source 2 at column 2,
+    // and source 0 on the next line.
+    let mappings = parse_mappings::<()>(b"AAAA,C,CEAA;AFAA").unwrap();
+
+    let split = mappings.split_by_source();
+    let split: Vec<_> = split.iter().map(|&(s, ref m)| (s, m.to_string())).collect();
+    assert_eq!(
+        split,
+        vec![(0, "AAAA;AAAA".to_string()), (2, "EEAA".to_string())]
+    );
+}
+
+#[test]
+fn test_invert() {
+    // Source 0 line 1 column 0 at column 0, source 0 line 0 column 3 at
+    // column 4, and source 1 line 0 column 0 with name 0 on the next line.
+    let mappings = parse_mappings::<()>(b"AACA,IADG;ACAHA").unwrap();
+
+    let inverted = mappings.invert();
+    assert_eq!(inverted.len(), 2);
+
+    let (source, mut inverted_0) = inverted.into_iter().next().unwrap();
+    assert_eq!(source, 0);
+    inverted_0.compute_column_spans();
+    let locations: Vec<_> = inverted_0
+        .by_generated_location()
+        .iter()
+        .map(|m| {
+            let o = m.original.as_ref().unwrap();
+            (m.generated_line, m.generated_column, o.original_line, o.original_column)
+        })
+        .collect();
+    assert_eq!(locations, vec![(0, 3, 0, 4), (1, 0, 0, 0)]);
+
+    let m = inverted_0
+        .original_location_for(0, 5, Bias::GreatestLowerBound)
+        .unwrap();
+    assert_eq!(m.original.as_ref().unwrap().original_column, 4);
+}
+
+#[test]
+fn test_apply_generated_edit() {
+    let mut mappings = parse_mappings::<()>(b"AAAA;CACA;EACA;GACA").unwrap();
+
+    // Replace lines 1 and 2 with three new lines.
+    mappings.apply_generated_edit(1, 2, 3).unwrap();
+    assert_eq!(mappings.to_string(), "AAAA;;;;GAGA");
+
+    // Delete the empty lines again.
+    mappings.apply_generated_edit(1, 3, 0).unwrap();
+    assert_eq!(mappings.to_string(), "AAAA;GAGA");
+
+    // Insert a line at the start.
+    mappings.apply_generated_edit(0, 0, 1).unwrap();
+    assert_eq!(mappings.to_string(), ";AAAA;GAGA");
+}
+
+#[test]
+fn test_apply_generated_edit_overflow() {
+    use source_map_mappings::Error;
+
+    let at = |line| Mapping {
+        generated_line: line,
+        generated_column: 0,
+        last_generated_column: None,
+        original: None,
+    };
+    let mut mappings = Mappings::<()>::from_mappings(vec![at(0), at(5), at(u32::MAX - 1)]).unwrap();
+
+    // Replacing line 3 with three lines would push the last mapping past
+    // `u32::MAX`.
+    assert_eq!(mappings.apply_generated_edit(3, 1, 3), Err(Error::UnexpectedlyBigGeneratedLine));
+    let lines: Vec<_> = mappings.by_generated_location().iter().map(|m| m.generated_line).collect();
+    assert_eq!(lines, vec![0, 5, u32::MAX - 1]);
+
+    assert_eq!(mappings.apply_generated_edit(3, 1, 2), Ok(()));
+    let lines: Vec<_> = mappings.by_generated_location().iter().map(|m| m.generated_line).collect();
+    assert_eq!(lines, vec![0, 6, u32::MAX]);
+
+    // Deleting lines at the very end can't overflow.
+    assert_eq!(mappings.apply_generated_edit(u32::MAX, u32::MAX, 0), Ok(()));
+    assert_eq!(mappings.len(), 2);
+}
+
+#[test]
+fn test_normalize() {
+    // Line 0: a duplicate pair at column 0, and two different mappings at
+    // column 2. Line 1: a single mapping.
+    let mut mappings = parse_mappings::<()>(b"AAAA,AAAA,EAAA,AACA;AAAA").unwrap();
+    mappings.compute_column_spans();
+    mappings.normalize();
+    assert_eq!(mappings.serialize(), "AAAA,EACA;AAAA");
+
+    let spans: Vec<_> = mappings
+        .by_generated_location()
+        .iter()
+        .map(|m| m.last_generated_column)
+        .collect();
+    assert_eq!(spans, vec![Some(2), None, None]);
+}
+
+#[test]
+fn test_dedup_exact() {
+    // Line 0: the same mapping three times at column 0, then two mappings at
+    // column 2 that differ in their original column. Line 1: the same mapping
+    // twice.
+    let mut mappings = parse_mappings::<()>(b"AAAA,AAAA,AAAA,EAAA,AAAC;AAAA,AAAA").unwrap();
+    mappings.compute_column_spans();
+
+    assert_eq!(mappings.exact_duplicates(), 3);
+    assert_eq!(mappings.dedup_exact(), 3);
+    assert_eq!(mappings.exact_duplicates(), 0);
+    assert_eq!(mappings.dedup_exact(), 0);
+
+    let locations: Vec<_> = mappings
+        .by_generated_location()
+        .iter()
+        .map(|m| (m.generated_line, m.generated_column, m.last_generated_column))
+        .collect();
+    assert_eq!(
+        locations,
+        vec![(0, 0, Some(2)), (0, 2, Some(2)), (0, 2, None), (1, 0, None)]
+    );
+}
+
+#[test]
+fn test_shrink_to_fit() {
+    let mut mappings = parse_mappings::<()>(TEST_MAPPINGS).unwrap();
+    let mut expected = parse_mappings::<()>(TEST_MAPPINGS).unwrap();
+    expected.compute_column_spans();
+
+    // Build the indices by original location and by name, then shrink.
+    mappings.by_original_source(0);
+    mappings.generated_locations_for_name(0).count();
+    mappings.retain(|m| m.generated_line == 0);
+    mappings.shrink_to_fit();
+
+    assert!(mappings.by_generated_location().iter().all(|m| m.generated_line == 0));
+    assert_eq!(
+        mappings.original_location_for(0, 10, Bias::GreatestLowerBound),
+        expected.original_location_for(0, 10, Bias::GreatestLowerBound)
+    );
+    assert!(mappings.by_original_location().all(|m| m.generated_line == 0));
+}
+
+#[test]
+fn test_remap_sources_and_names() {
+    // Source 0 with name 0, source 1 with name 1, and source 2.
+    let mut mappings = parse_mappings::<()>(b"AAAAA,CCAAC,CCAA").unwrap();
+    assert_eq!(mappings.by_original_source(0).len(), 1);
+
+    mappings.remap_sources(&[1, 0]);
+    mappings.remap_names(&[5]);
+
+    let originals: Vec<_> = mappings
+        .by_generated_location()
+        .iter()
+        .map(|m| {
+            let o = m.original.as_ref().unwrap();
+            (o.source, o.name)
+        })
+        .collect();
+    assert_eq!(originals, vec![(1, Some(5)), (0, Some(1)), (2, None)]);
+    assert_eq!(mappings.by_original_source(0)[0].generated_column, 1);
+}
+
+#[test]
+fn test_merge() {
+    fn sources_by_column(policy: MergePolicy) -> Vec<(u32, u32, Option<u32>)> {
+        let mut mappings = parse_mappings::<()>(b"AAAA,EAAE").unwrap();
+        mappings.compute_column_spans();
+        mappings.merge(parse_mappings::<()>(b"CCAA,CAAC").unwrap(), policy);
+        mappings
+            .by_generated_location()
+            .iter()
+            .map(|m| {
+                (
+                    m.generated_column,
+                    m.original.as_ref().unwrap().source,
+                    m.last_generated_column,
+                )
+            })
+            .collect()
+    }
+
+    assert_eq!(
+        sources_by_column(MergePolicy::KeepBoth),
+        vec![(0, 0, Some(1)), (1, 1, Some(2)), (2, 0, Some(2)), (2, 1, None)]
+    );
+    assert_eq!(
+        sources_by_column(MergePolicy::PreferSelf),
+        vec![(0, 0, Some(1)), (1, 1, Some(2)), (2, 0, None)]
+    );
+    assert_eq!(
+        sources_by_column(MergePolicy::PreferOther),
+        vec![(0, 0, Some(1)), (1, 1, Some(2)), (2, 1, None)]
+    );
+}
+
+#[test]
+fn test_by_original_source() {
+    let mut mappings = parse_mappings::<()>(TEST_MAPPINGS).unwrap();
+    let mut expected: Vec<_> = mappings
+        .by_generated_location()
+        .iter()
+        .filter(|m| m.original.as_ref().is_some_and(|o| o.source == 0))
+        .cloned()
+        .collect();
+    expected.sort_by_key(|m| {
+        let o = m.original.as_ref().unwrap();
+        (o.original_line, o.original_column, m.generated_line, m.generated_column)
+    });
+
+    mappings.compute_column_spans();
+    let by_original = mappings.by_original_source(0);
+    assert_eq!(by_original.len(), expected.len());
+    assert!(by_original.iter().map(|m| (m.generated_line, m.generated_column)).eq(
+        expected.iter().map(|m| (m.generated_line, m.generated_column))
+    ));
+    assert!(by_original.iter().rev().eq(by_original.iter().collect::<Vec<_>>().into_iter().rev()));
+    assert_eq!(by_original.iter().len(), expected.len());
+    assert_eq!(by_original.first(), Some(&by_original[0]));
+    assert_eq!(by_original.last(), by_original.get(by_original.len() - 1));
+    assert!(by_original.get(by_original.len()).is_none());
+
+    assert!(mappings.by_original_source(1000).is_empty());
+}
+
+#[test]
+fn test_original_tie_break() {
+    use source_map_mappings::OriginalTieBreak;
+
+    // Two mappings at the same original location: the first with name 1, and
+    // the second with name 0.
+    let mut mappings = parse_mappings::<()>(b"AAAAC,CAAAD").unwrap();
+    let order = |mappings: &mut Mappings| -> Vec<_> {
+        mappings.by_original_location().map(|m| m.generated_column).collect()
+    };
+
+    assert_eq!(mappings.original_tie_break(), OriginalTieBreak::NameThenGenerated);
+    assert_eq!(order(&mut mappings), vec![1, 0]);
+    assert_eq!(mappings.all_generated_locations_for(0, 0, Some(0)).next().unwrap().generated_column, 1);
+
+    mappings.set_original_tie_break(OriginalTieBreak::Generated);
+    assert_eq!(order(&mut mappings), vec![0, 1]);
+    assert_eq!(mappings.all_generated_locations_for(0, 0, Some(0)).next().unwrap().generated_column, 0);
+}
+
+#[test]
+fn test_sources_sorted_lazily() {
+    use source_map_mappings::Observer;
+    use std::cell::Cell;
+
+    thread_local! {
+        static SORTS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    // Counts each time it is constructed, which is once per sort.
+    struct CountSortsObserver;
+
+    impl Default for CountSortsObserver {
+        fn default() -> CountSortsObserver {
+            SORTS.with(|sorts| sorts.set(sorts.get() + 1));
+            CountSortsObserver
+        }
+    }
+
+    #[derive(Default)]
+    struct CountSorts;
+
+    impl Observer for CountSorts {
+        type ParseMappings = ();
+        type SortByOriginalLocation = CountSortsObserver;
+        type SortByGeneratedLocation = ();
+        type ComputeColumnSpans = ();
+        type OriginalLocationFor = ();
+        type GeneratedLocationFor = ();
+        type AllGeneratedLocationsFor = ();
+    }
+
+    let sorts = || SORTS.with(Cell::get);
+    let mut mappings = parse_mappings::<CountSorts>(TEST_MAPPINGS).unwrap();
+    assert_eq!(sorts(), 0);
+
+    // Grouping the mappings by source, and then sorting source 0 only.
+    mappings.generated_location_for(0, 1, 1, Bias::GreatestLowerBound);
+    assert_eq!(sorts(), 2);
+    mappings.generated_location_for(0, 2, 1, Bias::GreatestLowerBound);
+    assert_eq!(sorts(), 2);
+
+    // Sorting source 1 on demand too.
+    mappings.generated_location_for(1, 1, 1, Bias::GreatestLowerBound);
+    assert_eq!(sorts(), 3);
+    assert_eq!(mappings.by_original_location().count(), 13);
+    assert_eq!(sorts(), 3);
+}
+
+#[test]
+fn test_original_location_for_with_skew() {
+    // Mappings at columns 1, 5, 9, 18, ... on line 0.
+    let mappings = parse_mappings::<()>(TEST_MAPPINGS).unwrap();
+
+    let (m, distance) = mappings.original_location_for_with_skew(0, 5, 2).unwrap();
+    assert_eq!((m.generated_column, distance), (5, 0));
+
+    let (m, distance) = mappings.original_location_for_with_skew(0, 8, 2).unwrap();
+    assert_eq!((m.generated_column, distance), (9, 1));
+
+    // Equally near to 5 and 9: prefer the mapping before.
+    let (m, distance) = mappings.original_location_for_with_skew(0, 7, 2).unwrap();
+    assert_eq!((m.generated_column, distance), (5, 2));
+
+    assert!(mappings.original_location_for_with_skew(0, 13, 3).is_none());
+
+    // Never slides onto another line.
+    assert!(mappings.original_location_for_with_skew(1, 0, 10).is_some());
+    assert!(mappings.original_location_for_with_skew(2, 0, 10).is_none());
+}
+
+#[test]
+fn test_original_location_for_within_span() {
+    // Line 0: mappings at columns 0 and 4. Line 1: a mapping at column 4.
+    let mut mappings = parse_mappings::<()>(b"AAAA,IAAA;IAAA").unwrap();
+    mappings.compute_column_spans();
+
+    let options = QueryOptions {
+        within_span: true,
+        ..Default::default()
+    };
+
+    let m = mappings.original_location_for_with_options(0, 2, &options).unwrap();
+    assert_eq!((m.generated_line, m.generated_column), (0, 0));
+
+    let m = mappings.original_location_for_with_options(0, 100, &options).unwrap();
+    assert_eq!((m.generated_line, m.generated_column), (0, 4));
+
+    // The start of line 1 is not covered by any mapping.
+    assert!(mappings.original_location_for(1, 2, Bias::GreatestLowerBound).is_some());
+    assert!(mappings.original_location_for_with_options(1, 2, &options).is_none());
+
+    let m = mappings.original_location_for_with_options(1, 4, &options).unwrap();
+    assert_eq!((m.generated_line, m.generated_column), (1, 4));
+
+    // A least upper bound mapping never contains the queried location unless
+    // it is an exact match.
+    let options = QueryOptions {
+        bias: Bias::LeastUpperBound,
+        within_span: true,
+        ..Default::default()
+    };
+    assert!(mappings.original_location_for_with_options(1, 2, &options).is_none());
+}
+
+#[test]
+fn test_nearest_named_mapping() {
+    // Line 0: name 0 at column 0, unnamed at column 4. Line 1: unnamed at
+    // column 0, name 1 at column 6.
+    let mappings = parse_mappings::<()>(b"AAAAA,IAAA;AAAA,MAAAC").unwrap();
+
+    let column = |m: Option<&Mapping>| m.map(|m| (m.generated_line, m.generated_column));
+
+    assert_eq!(
+        column(mappings.nearest_named_mapping(1, 3, Bias::GreatestLowerBound)),
+        Some((0, 0))
+    );
+    assert_eq!(
+        column(mappings.nearest_named_mapping(0, 4, Bias::LeastUpperBound)),
+        Some((1, 6))
+    );
+    assert_eq!(
+        column(mappings.nearest_named_mapping(1, 6, Bias::GreatestLowerBound)),
+        Some((1, 6))
+    );
+    assert_eq!(column(mappings.nearest_named_mapping(1, 7, Bias::LeastUpperBound)), None);
+}
+
+#[test]
+fn test_closest_named_mapping_before() {
+    // Line 0: name 0 at column 0, unnamed at column 4. Line 1: unnamed at
+    // column 0, name 1 at column 6.
+    let mappings = parse_mappings::<()>(b"AAAAA,IAAA;AAAA,MAAAC").unwrap();
+
+    let name = |m: Option<&Mapping>| m.and_then(|m| m.original.as_ref().unwrap().name);
+
+    assert_eq!(name(mappings.closest_named_mapping_before(0, 0)), Some(0));
+    assert_eq!(name(mappings.closest_named_mapping_before(1, 5)), Some(0));
+    assert_eq!(name(mappings.closest_named_mapping_before(1, 6)), Some(1));
+    assert_eq!(name(mappings.closest_named_mapping_before(9, 0)), Some(1));
+    assert_eq!(
+        name(parse_mappings::<()>(b"AAAA").unwrap().closest_named_mapping_before(0, 1)),
+        None
+    );
+}
+
+#[test]
+fn test_generated_locations_for_name() {
+    // Line 0: name 1 at column 0, no name at column 2, name 1 at column 4.
+    // Line 1: name 0 at column 1.
+    let mut mappings = parse_mappings::<()>(b"AAAAC,EAAA,EAAAA;CAAAD").unwrap();
+
+    let locations = |mappings: &mut Mappings, name| {
+        mappings
+            .generated_locations_for_name(name)
+            .map(|m| (m.generated_line, m.generated_column))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(locations(&mut mappings, 0), vec![(1, 1)]);
+    assert_eq!(locations(&mut mappings, 1), vec![(0, 0), (0, 4)]);
+    assert!(locations(&mut mappings, 2).is_empty());
+
+    // The index is rebuilt after the mappings change.
+    mappings.offset_generated(1, 0).unwrap();
+    assert_eq!(locations(&mut mappings, 1), vec![(1, 0), (1, 4)]);
+    mappings.strip_names();
+    assert!(locations(&mut mappings, 1).is_empty());
+}
+
+#[test]
+fn test_check_against_sources() {
+    use source_map_mappings::validation::{InvalidOriginalLocation, OriginalLocationProblem};
+
+    // Source 0 line 0 column 0, source 0 line 2 column 5, source 1 line 0
+    // column 0.
+    let mappings = parse_mappings::<()>(b"AAAA,CAEK,CCFL").unwrap();
+
+    let problems = |found: Vec<InvalidOriginalLocation>| {
+        found
+            .iter()
+            .map(|p| (p.mapping.generated_column, p.problem))
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(problems(mappings.check_against_sources(&[3, 1])), vec![]);
+    assert_eq!(
+        problems(mappings.check_against_sources(&[2])),
+        vec![
+            (1, OriginalLocationProblem::LineOutOfBounds),
+            (2, OriginalLocationProblem::UnknownSource),
+        ]
+    );
+
+    let lines: Vec<Vec<u32>> = vec![vec![10, 10, 4], vec![0]];
+    assert_eq!(
+        problems(mappings.check_against_source_lines(&lines)),
+        vec![(1, OriginalLocationProblem::ColumnOutOfBounds)]
+    );
+}
+
+#[test]
+fn test_indices_used() {
+    // Source 2 with name 3, a generated-only mapping, source 0, and source 2
+    // again with name 1.
+    let mappings = parse_mappings::<()>(b"AEAAG,C,CFAA,CEAAF").unwrap();
+
+    assert_eq!(mappings.source_indices_used(), vec![0, 2]);
+    assert_eq!(mappings.name_indices_used(), vec![1, 3]);
+    assert_eq!(mappings.max_source_index(), Some(2));
+    assert_eq!(mappings.max_name_index(), Some(3));
+
+    let mappings = parse_mappings::<()>(b"A,C").unwrap();
+    assert!(mappings.source_indices_used().is_empty());
+    assert!(mappings.name_indices_used().is_empty());
+    assert_eq!(mappings.max_source_index(), None);
+    assert_eq!(mappings.max_name_index(), None);
+}
+
+#[test]
+fn test_span_anomalies() {
+    use source_map_mappings::validation::SpanAnomalyKind;
+
+    let mapping = |generated_line, generated_column, last_generated_column| Mapping {
+        generated_line,
+        generated_column,
+        last_generated_column,
+        original: None,
+    };
+    let mut mappings = Mappings::<()>::from_mappings(vec![
+        mapping(0, 0, Some(5)),
+        mapping(0, 3, None),
+        mapping(0, 3, None),
+        mapping(1, 2, Some(2)),
+    ]).unwrap();
+
+    let anomalies: Vec<_> = mappings
+        .span_anomalies()
+        .iter()
+        .map(|a| (a.mapping.generated_line, a.mapping.generated_column, a.kind))
+        .collect();
+    assert_eq!(
+        anomalies,
+        vec![
+            (0, 0, SpanAnomalyKind::Overlapping),
+            (0, 3, SpanAnomalyKind::ZeroWidth),
+            (0, 3, SpanAnomalyKind::DuplicateGeneratedLocation),
+            (1, 2, SpanAnomalyKind::ZeroWidth),
+        ]
+    );
+
+    assert!(parse_mappings::<()>(TEST_MAPPINGS).unwrap().span_anomalies().is_empty());
+}
+
+#[test]
+fn test_string_tables() {
+    // "a.js" line 0 column 0 named "foo", then "b.js" line 1 column 0.
+    let mappings = parse_mappings::<()>(b"AAAAA,CCCA").unwrap();
+    let mut mappings = mappings.with_string_tables(
+        vec!["a.js".to_string(), "b.js".to_string()],
+        vec!["foo".to_string()],
+    );
+
+    assert_eq!(mappings.source_index("b.js"), Some(1));
+    assert_eq!(mappings.source_index("c.js"), None);
+
+    let m = mappings.original_location_for(0, 0, Bias::GreatestLowerBound).unwrap();
+    assert_eq!(mappings.source_of(m), Some("a.js"));
+    assert_eq!(mappings.name_of(m), Some("foo"));
+
+    let m = mappings
+        .generated_location_for_source("b.js", 1, 0, Bias::GreatestLowerBound)
+        .unwrap()
+        .clone();
+    assert_eq!(m.generated_column, 1);
+    assert_eq!(mappings.name_of(&m), None);
+
+    // Never slides into another source, unlike queries by index.
+    assert_eq!(
+        mappings.generated_location_for(0, 3, 0, Bias::LeastUpperBound).map(|m| m.generated_column),
+        Some(1)
+    );
+    assert_eq!(mappings.generated_location_for_source("a.js", 3, 0, Bias::LeastUpperBound), None);
+    assert_eq!(mappings.generated_location_for_source("b.js", 0, 0, Bias::GreatestLowerBound), None);
+
+    assert_eq!(mappings.all_generated_locations_for_source("b.js", 1, None).count(), 1);
+    assert_eq!(mappings.all_generated_locations_for_source("c.js", 1, None).count(), 0);
+}
+
+#[test]
+fn test_mappings_in_generated_line() {
+    let mappings = parse_mappings::<()>(b"AAAA,CAAA;;EAAA,CAAA,CAAA;A").unwrap();
+
+    let columns = |line| {
+        mappings
+            .mappings_in_generated_line(line)
+            .iter()
+            .map(|m| m.generated_column)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(columns(0), vec![0, 1]);
+    assert!(columns(1).is_empty());
+    assert_eq!(columns(2), vec![2, 3, 4]);
+    assert_eq!(columns(3), vec![0]);
+    assert!(columns(4).is_empty());
+
+    // Very sparse line numbers aren't indexed, but still work.
+    let mut mappings = parse_mappings::<()>(b"AAAA;CAAA").unwrap();
+    mappings.offset_generated(1_000_000, 0).unwrap();
+    assert_eq!(mappings.mappings_in_generated_line(1_000_001).len(), 1);
+    assert!(mappings.mappings_in_generated_line(3).is_empty());
+    let m = mappings
+        .original_location_for(1_000_001, 5, Bias::GreatestLowerBound)
+        .unwrap();
+    assert_eq!(m.generated_column, 1);
+
+    // So are mappings on the very last generated line.
+    let at = |line| Mapping {
+        generated_line: line,
+        generated_column: 0,
+        last_generated_column: None,
+        original: None,
+    };
+    let mut mappings = Mappings::<()>::from_mappings(vec![at(0), at(u32::MAX)]).unwrap();
+    assert_eq!(mappings.mappings_in_generated_line(u32::MAX).len(), 1);
+    mappings.insert(at(u32::MAX));
+    mappings.insert(at(1));
+    assert_eq!(mappings.mappings_in_generated_line(u32::MAX).len(), 2);
+    assert_eq!(mappings.mappings_in_generated_line(1).len(), 1);
+    assert_eq!(mappings.remove_at_generated(u32::MAX, 0).len(), 2);
+    assert!(mappings.mappings_in_generated_line(u32::MAX).is_empty());
+    assert!(mappings
+        .original_location_for(u32::MAX, 0, Bias::LeastUpperBound)
+        .is_none());
+}
+
+#[test]
+fn test_by_generated_range() {
+    let mappings = parse_mappings::<()>(b"AAAA,CAAA;;EAAA,CAAA,CAAA;A").unwrap();
+
+    let locations = |start_line, start_column, end_line, end_column| {
+        mappings
+            .by_generated_range(start_line, start_column, end_line, end_column)
+            .iter()
+            .map(|m| (m.generated_line, m.generated_column))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(locations(0, 1, 2, 3), vec![(0, 1), (2, 2)]);
+    assert_eq!(locations(1, 0, 3, 0), vec![(2, 2), (2, 3), (2, 4)]);
+    assert_eq!(locations(2, 4, 9, 0), vec![(2, 4), (3, 0)]);
+    assert!(locations(1, 0, 2, 2).is_empty());
+    assert!(locations(3, 0, 0, 0).is_empty());
+}
+
+#[test]
+fn test_line_map() {
+    // Line 0: source 0 original line 0 at column 0, then original line 1.
+    // Line 1: a generated-only mapping, then source 1 original line 3. Line 2
+    // has no mappings. Line 3: only a generated-only mapping.
+    let mappings = parse_mappings::<()>(b"AAAA,CACA;A,CCEA;;A").unwrap();
+
+    assert_eq!(mappings.line_map(), vec![(0, 0, 0), (1, 1, 3)]);
+    assert!(parse_mappings::<()>(b"").unwrap().line_map().is_empty());
+}
+
+#[test]
+fn test_original_location_for_same_line_only() {
+    // Line 0: a mapping at column 4. Line 1: a mapping at column 4.
+    let mappings = parse_mappings::<()>(b"IAAA;IAAA").unwrap();
+
+    let glb = QueryOptions {
+        same_line_only: true,
+        ..Default::default()
+    };
+    assert!(mappings.original_location_for(1, 2, Bias::GreatestLowerBound).is_some());
+    assert!(mappings.original_location_for_with_options(1, 2, &glb).is_none());
+    assert!(mappings.original_location_for_with_options(1, 5, &glb).is_some());
+
+    let lub = QueryOptions {
+        bias: Bias::LeastUpperBound,
+        same_line_only: true,
+        ..Default::default()
+    };
+    assert!(mappings.original_location_for(0, 5, Bias::LeastUpperBound).is_some());
+    assert!(mappings.original_location_for_with_options(0, 5, &lub).is_none());
+    assert!(mappings.original_location_for_with_options(0, 3, &lub).is_some());
+}
+
+#[test]
+fn test_original_location_for_require_original() {
+    // Line 0: a mapping with original info at column 0, then generated-only
+    // mappings at columns 2 and 5.
+    let mappings = parse_mappings::<()>(b"AAAA,E,G").unwrap();
+
+    let glb = QueryOptions {
+        require_original: true,
+        ..Default::default()
+    };
+    let m = mappings.original_location_for(0, 6, Bias::GreatestLowerBound).unwrap();
+    assert!(m.original.is_none());
+    let m = mappings.original_location_for_with_options(0, 6, &glb).unwrap();
+    assert_eq!(m.generated_column, 0);
+    let m = mappings.original_location_for_with_options(0, 2, &glb).unwrap();
+    assert_eq!(m.generated_column, 0);
+
+    let lub = QueryOptions {
+        bias: Bias::LeastUpperBound,
+        require_original: true,
+        ..Default::default()
+    };
+    assert!(mappings.original_location_for(0, 1, Bias::LeastUpperBound).is_some());
+    assert!(mappings.original_location_for_with_options(0, 1, &lub).is_none());
+}
+
+#[test]
+fn test_original_location_for_with_hint() {
+    let mappings = parse_mappings::<()>(TEST_MAPPINGS).unwrap();
+
+    let location = |m: Option<&Mapping>| m.map(|m| (m.generated_line, m.generated_column));
+
+    // Forwards, then backwards, then forwards again, reusing a single hint.
+    let queries = [(0, 0), (0, 3), (0, 10), (1, 0), (2, 0), (0, 5), (0, 9), (3, 0), (99, 0)];
+    for &bias in &[Bias::GreatestLowerBound, Bias::LeastUpperBound] {
+        let mut hint = QueryHint::default();
+        for &(line, column) in &queries {
+            assert_eq!(
+                location(mappings.original_location_for_with_hint(line, column, bias, &mut hint)),
+                location(mappings.original_location_for(line, column, bias))
+            );
+        }
+    }
+}
+
+#[test]
+fn test_query_cache() {
+    let mut cached = parse_mappings::<()>(TEST_MAPPINGS).unwrap().with_query_cache(2);
+    let uncached = parse_mappings::<()>(TEST_MAPPINGS).unwrap();
+
+    // Enough distinct queries to evict from the cache, repeated.
+    let queries = [(0, 3), (1, 0), (0, 3), (2, 5), (1, 0), (0, 3), (99, 0), (2, 5)];
+    for &bias in &[Bias::GreatestLowerBound, Bias::LeastUpperBound] {
+        for &(line, column) in &queries {
+            assert_eq!(
+                cached.original_location_for(line, column, bias),
+                uncached.original_location_for(line, column, bias)
+            );
+        }
+    }
+
+    // Modifying the mappings invalidates the cache.
+    assert!(cached.original_location_for(0, 3, Bias::GreatestLowerBound).is_some());
+    cached.mappings_mut().offset_generated(1, 0).unwrap();
+    assert!(cached.original_location_for(0, 3, Bias::GreatestLowerBound).is_none());
+
+    let mut uncached = uncached.with_query_cache(0);
+    assert!(uncached.original_location_for(0, 3, Bias::GreatestLowerBound).is_some());
+}
+
+#[test]
+fn test_surrounding_mappings() {
+    // Line 0: mappings at columns 2 and 8. Line 2: a mapping at column 0.
+    let mappings = parse_mappings::<()>(b"EAAE,MAAM;;AACR").unwrap();
+
+    let columns = |(lower, upper): (Option<&Mapping>, Option<&Mapping>)| {
+        let column = |m: Option<&Mapping>| m.map(|m| (m.generated_line, m.generated_column));
+        (column(lower), column(upper))
+    };
+
+    assert_eq!(columns(mappings.surrounding_mappings(0, 5)), (Some((0, 2)), Some((0, 8))));
+    assert_eq!(columns(mappings.surrounding_mappings(0, 8)), (Some((0, 8)), Some((0, 8))));
+    assert_eq!(columns(mappings.surrounding_mappings(1, 0)), (Some((0, 8)), Some((2, 0))));
+    assert_eq!(columns(mappings.surrounding_mappings(0, 0)), (None, Some((0, 2))));
+    assert_eq!(columns(mappings.surrounding_mappings(2, 1)), (Some((2, 0)), None));
+}
+
+#[test]
+fn test_original_location_for_exact() {
+    // Line 0: a generated-only mapping and a mapping with original info, both
+    // at column 2, then another mapping at column 5.
+    let mappings = parse_mappings::<()>(b"E,AAAA,GAAA").unwrap();
+
+    let exact = QueryOptions {
+        exact: true,
+        ..Default::default()
+    };
+    assert!(mappings.original_location_for(0, 3, Bias::GreatestLowerBound).is_some());
+    assert!(mappings.original_location_for_with_options(0, 3, &exact).is_none());
+    assert!(mappings.original_location_for_with_options(0, 1, &exact).is_none());
+    let m = mappings.original_location_for_with_options(0, 5, &exact).unwrap();
+    assert_eq!(m.generated_column, 5);
+
+    let exact_original = QueryOptions {
+        exact: true,
+        require_original: true,
+        ..Default::default()
+    };
+    let m = mappings.original_location_for_with_options(0, 2, &exact_original).unwrap();
+    assert!(m.original.is_some());
+}
+
+#[test]
+fn test_closest_match() {
+    // Line 0: mappings at columns 2 and 8 into original line 0 columns 2 and
+    // 8. Line 2: a mapping at column 0 into original line 1 column 0.
+    let mut mappings = parse_mappings::<()>(b"EAAE,MAAM;;AACR").unwrap();
+
+    let column = |m: Option<&Mapping>| m.map(|m| (m.generated_line, m.generated_column));
+
+    assert_eq!(
+        column(mappings.original_location_for(0, 6, Bias::ClosestMatch)),
+        Some((0, 8))
+    );
+    // Equally near: prefer the lower.
+    assert_eq!(
+        column(mappings.original_location_for(0, 5, Bias::ClosestMatch)),
+        Some((0, 2))
+    );
+    // A mapping on the same line is nearer than one on another line.
+    assert_eq!(
+        column(mappings.original_location_for(0, 100, Bias::ClosestMatch)),
+        Some((0, 8))
+    );
+    assert_eq!(
+        column(mappings.original_location_for(1, 0, Bias::ClosestMatch)),
+        Some((0, 8))
+    );
+
+    assert_eq!(
+        column(mappings.generated_location_for(0, 0, 6, Bias::ClosestMatch)),
+        Some((0, 8))
+    );
+    assert_eq!(
+        column(mappings.generated_location_for(0, 0, 0, Bias::ClosestMatch)),
+        Some((0, 2))
+    );
+}
+
+#[test]
+fn test_column_coverage() {
+    // Line 0: a generated-only mapping at 0, then source 0 at 2 and source 1
+    // at 5. Line 1: source 1 at 1. Line 2 has no mappings.
+    let mappings = parse_mappings::<()>(b"A,EAAA,GCAA;CAAA").unwrap();
+
+    let coverage = mappings.column_coverage(&[8, 4, 10]);
+    assert_eq!(coverage.total_columns, 22);
+    assert_eq!(coverage.covered_columns, 3 + 3 + 3);
+    assert_eq!(coverage.covered_columns_by_source, vec![3, 6]);
+    assert_eq!(coverage.fraction(), 9.0 / 22.0);
+    assert_eq!(coverage.source_fraction(1), 6.0 / 22.0);
+    assert_eq!(coverage.source_fraction(2), 0.0);
+
+    // Lines and columns beyond the given lengths are not covered.
+    let coverage = mappings.column_coverage(&[4]);
+    assert_eq!(coverage.covered_columns, 2);
+    assert_eq!(coverage.covered_columns_by_source, vec![2, 0]);
+}
+
+#[test]
+fn test_coverage_stats() {
+    use source_map_mappings::coverage::SourceCoverage;
+
+    // Line 0: source 0 original lines 0 and 1. Line 1: source 0 original line
+    // 4. Line 2: source 1 original lines 4 and 6. Line 4: a generated-only
+    // mapping.
+    let mappings = parse_mappings::<()>(b"AAAA,CACA;AAGA;ACAA,CAEA;;A").unwrap();
+
+    let stats = mappings.coverage_stats();
+    assert_eq!(stats.total_generated_lines, 5);
+    assert_eq!(
+        stats.sources,
+        vec![
+            SourceCoverage {
+                mappings: 3,
+                original_lines: vec![0..2, 4..5],
+                generated_lines: 2,
+            },
+            SourceCoverage {
+                mappings: 2,
+                original_lines: vec![4..5, 6..7],
+                generated_lines: 1,
+            },
+        ]
+    );
+    assert_eq!(stats.generated_line_fraction(0), 0.4);
+    assert_eq!(stats.generated_line_fraction(2), 0.0);
+}
+
+#[test]
+fn test_coverage_stats_at_max_line() {
+    let at_max = |original_line| Mapping {
+        generated_line: u32::MAX,
+        generated_column: 0,
+        last_generated_column: None,
+        original: Some(OriginalLocation {
+            source: 0,
+            original_line,
+            original_column: 0,
+            name: None,
+        }),
+    };
+    let mappings = Mappings::<()>::from_mappings(vec![at_max(u32::MAX - 1), at_max(u32::MAX)]).unwrap();
+
+    let stats = mappings.coverage_stats();
+    assert_eq!(stats.total_generated_lines, u32::MAX);
+    assert_eq!(stats.sources[0].original_lines, vec![u32::MAX - 1..u32::MAX]);
+    assert_eq!(stats.sources[0].generated_lines, 1);
+}
+
+#[test]
+fn test_gaps() {
+    use source_map_mappings::coverage::GeneratedRange;
+
+    // Line 0: mappings at columns 2 and 5. Line 1 has no mappings. Line 2:
+    // a mapping at column 0. Line 3: a mapping at column 3.
+    let mut mappings = parse_mappings::<()>(b"EAAA,GAAA;;AAAA;GAAA").unwrap();
+
+    let range = |generated_line, generated_column, last_generated_column| GeneratedRange {
+        generated_line,
+        generated_column,
+        last_generated_column,
+    };
+    assert_eq!(
+        mappings.gaps().collect::<Vec<_>>(),
+        vec![range(0, 0, Some(2)), range(1, 0, None), range(3, 0, Some(3))]
+    );
+
+    assert_eq!(parse_mappings::<()>(b"").unwrap().gaps().count(), 0);
+
+    // Explicit column spans can leave gaps between mappings, and at the end of
+    // the last line.
+    let mapping = |generated_line, generated_column, last_generated_column| Mapping {
+        generated_line,
+        generated_column,
+        last_generated_column,
+        original: None,
+    };
+    let mut mappings = Mappings::<()>::from_mappings(vec![
+        mapping(0, 0, Some(2)),
+        mapping(0, 5, None),
+        mapping(1, 1, Some(3)),
+    ]).unwrap();
+    assert_eq!(
+        mappings.gaps().collect::<Vec<_>>(),
+        vec![range(0, 2, Some(5)), range(1, 0, Some(1)), range(1, 3, None)]
+    );
+}
+
+#[test]
+fn test_name_usage() {
+    use source_map_mappings::coverage::GeneratedRange;
+
+    // Line 0: name 1 at column 0, no name at column 2, name 1 at column 4.
+    // Line 1: name 0 at column 1.
+    let mut mappings = parse_mappings::<()>(b"AAAAC,EAAA,EAAAA;CAAAD").unwrap();
+
+    let usage = mappings.name_usage();
+    assert_eq!(usage.len(), 2);
+
+    assert_eq!(usage[0].mappings, 1);
+    assert_eq!(
+        usage[0].generated_ranges,
+        vec![GeneratedRange {
+            generated_line: 1,
+            generated_column: 1,
+            last_generated_column: None,
+        }]
+    );
+
+    assert_eq!(usage[1].mappings, 2);
+    assert_eq!(
+        usage[1].generated_ranges,
+        vec![
+            GeneratedRange {
+                generated_line: 0,
+                generated_column: 0,
+                last_generated_column: Some(2),
+            },
+            GeneratedRange {
+                generated_line: 0,
+                generated_column: 4,
+                last_generated_column: None,
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_generated_ranges_for_original_range() {
+    use source_map_mappings::coverage::GeneratedRange;
+
+    // Line 0: column 0 into original (0, 0). Line 1: column 0 into original
+    // (1, 0) and column 2 into original (1, 1). Line 2: column 0 into original
+    // (2, 0).
+    let mut mappings = parse_mappings::<()>(b"AAAA;AACA,EAAC;AACD").unwrap();
+
+    let range = |generated_line, generated_column, last_generated_column| GeneratedRange {
+        generated_line,
+        generated_column,
+        last_generated_column,
+    };
+    assert_eq!(
+        mappings.generated_ranges_for_original_range(0, (1, 0), (2, 0)).collect::<Vec<_>>(),
+        vec![range(1, 0, Some(2)), range(1, 2, None)]
+    );
+    assert_eq!(
+        mappings.generated_ranges_for_original_range(0, (0, 1), (1, 1)).collect::<Vec<_>>(),
+        vec![range(1, 0, Some(2))]
+    );
+    assert_eq!(mappings.generated_ranges_for_original_range(0, (3, 0), (4, 0)).count(), 0);
+    assert_eq!(mappings.generated_ranges_for_original_range(1, (0, 0), (9, 0)).count(), 0);
+}
+
+#[test]
+fn test_attach_payload() {
+    // Sorted by original location, the third mapping comes before the second.
+    let mut mappings = parse_mappings::<()>(b"AAAA,EACA;CADC").unwrap();
+    assert!(mappings.payloads::<u32>().is_none());
+
+    mappings.attach_payload(vec![10u32, 20, 30]);
+    assert!(mappings.payloads::<String>().is_none());
+
+    let m = &mappings.by_generated_location()[1];
+    assert_eq!(mappings.mapping_id(m), Some(1));
+    assert_eq!(mappings.payload::<u32>(m), Some(&20));
+
+    // Mappings found through the original location index have the same ids.
+    let found: Vec<_> = mappings.by_original_location().cloned().collect();
+    let ids: Vec<_> = found.iter().map(|m| mappings.mapping_id(m)).collect();
+    assert_eq!(ids, vec![Some(0), Some(2), Some(1)]);
+
+    mappings.payloads_mut::<u32>().unwrap()[2] += 1;
+    assert_eq!(mappings.detach_payload::<u32>(), Some(vec![10, 20, 31]));
+    assert!(mappings.payloads::<u32>().is_none());
+}
+
+#[test]
+#[should_panic]
+fn test_attach_payload_wrong_length() {
+    let mut mappings = parse_mappings::<()>(b"AAAA,EACA;CAAA").unwrap();
+    mappings.attach_payload(vec![(); 2]);
+}
+
+#[test]
+fn test_payload_follows_edits() {
+    use source_map_mappings::MergePolicy;
+
+    let mut mappings = parse_mappings::<()>(b"AAAA,CAAA,CAAA;AAAA,CAAA").unwrap();
+    mappings.attach_payload(vec![0u32, 1, 2, 3, 4]);
+
+    mappings.insert(Mapping {
+        generated_line: 0,
+        generated_column: 5,
+        last_generated_column: None,
+        original: None,
+    });
+    assert_eq!(mappings.payloads::<u32>(), Some(&[0, 1, 2, 0, 3, 4][..]));
+
+    mappings.remove_at_generated(0, 1);
+    assert_eq!(mappings.payloads::<u32>(), Some(&[0, 2, 0, 3, 4][..]));
+
+    mappings.retain(|m| m.original.is_some());
+    assert_eq!(mappings.payloads::<u32>(), Some(&[0, 2, 3, 4][..]));
+
+    let mut other = parse_mappings::<()>(b"AAAA;EAAA").unwrap();
+    other.attach_payload(vec![10u32, 11]);
+    mappings.merge(other, MergePolicy::PreferSelf);
+    let generated: Vec<_> = mappings
+        .by_generated_location()
+        .iter()
+        .map(|m| (m.generated_line, m.generated_column))
+        .collect();
+    assert_eq!(generated, vec![(0, 0), (0, 2), (1, 0), (1, 1), (1, 2)]);
+    assert_eq!(mappings.payloads::<u32>(), Some(&[0, 2, 3, 4, 11][..]));
+
+    let mut other = parse_mappings::<()>(b"EAAA").unwrap();
+    other.attach_payload(vec![String::from("other")]);
+    mappings.merge(other, MergePolicy::KeepBoth);
+    assert_eq!(mappings.payloads::<u32>(), Some(&[0, 2, 0, 3, 4, 11][..]));
+
+    mappings.dedup_exact();
+    assert_eq!(mappings.payloads::<u32>(), Some(&[0, 2, 3, 4, 11][..]));
+
+    let tail = mappings.split_off(1, true);
+    assert_eq!(mappings.payloads::<u32>(), Some(&[0, 2][..]));
+    assert_eq!(tail.payloads::<u32>(), Some(&[3, 4, 11][..]));
+}
+
+#[test]
+fn test_build_indexes_in_background() {
+    let mut expected = parse_mappings::<()>(TEST_MAPPINGS).unwrap();
+    expected.compute_column_spans();
+    let expected_by_original: Vec<_> = expected.by_original_location().cloned().collect();
+
+    let mappings = parse_mappings::<()>(TEST_MAPPINGS).unwrap();
+    let mut mappings = mappings.build_indexes_in_background().join();
+    assert_eq!(mappings.by_generated_location(), expected.by_generated_location());
+    assert_eq!(
+        mappings.by_original_location().cloned().collect::<Vec<_>>(),
+        expected_by_original
+    );
+}
+
+#[test]
+fn test_serialize() {
+    let mappings = parse_mappings::<()>(TEST_MAPPINGS).unwrap();
+    let serialized = mappings.serialize();
+    assert_eq!(serialized.as_bytes(), TEST_MAPPINGS);
+
+    let mut written = vec![];
+    mappings.write_to(&mut written).unwrap();
+    assert_eq!(written, serialized.as_bytes());
+
+    assert_eq!(format!("{}", mappings), serialized);
+
+    // Segments are sorted within each line, and redundant separators dropped.
+    let mappings = parse_mappings::<()>(b"EACA,,FADA;;").unwrap();
+    assert_eq!(mappings.serialize(), "AAAA,EACA");
+}
+
+#[test]
+fn test_mappings_encoder() {
+    use source_map_mappings::encode::MappingsEncoder;
+
+    let expected = parse_mappings::<()>(TEST_MAPPINGS).unwrap();
+
+    // Reverse the order of the mappings within each line.
+    let mut lines: Vec<Vec<Mapping>> = vec![];
+    for m in expected.by_generated_location() {
+        if lines.last().is_none_or(|l| l[0].generated_line != m.generated_line) {
+            lines.push(vec![]);
+        }
+        lines.last_mut().unwrap().insert(0, m.clone());
+    }
+
+    let mut encoder = MappingsEncoder::new(vec![]);
+    for m in lines.iter().flatten() {
+        encoder.add_mapping(m).unwrap();
+    }
+    assert_eq!(encoder.finish().unwrap(), TEST_MAPPINGS);
+
+    let mut encoder = MappingsEncoder::new(vec![]);
+    let m = |generated_line| Mapping {
+        generated_line,
+        ..Default::default()
+    };
+    encoder.add_mapping(&m(1)).unwrap();
+    encoder.add_mapping(&m(2)).unwrap();
+    assert!(encoder.add_mapping(&m(1)).is_err());
+}
+
+#[test]
+fn test_mappings_builder() {
+    let mut expected = parse_mappings::<()>(TEST_MAPPINGS).unwrap();
+    expected.compute_column_spans();
+
+    let mut builder = MappingsBuilder::<()>::new();
+    for m in expected.by_generated_location().iter().rev() {
+        builder.add_mapping(m.generated_line, m.generated_column, m.original.clone());
+    }
+    assert_eq!(builder.len(), expected.by_generated_location().len());
+
+    let mut mappings = builder.finish();
+    mappings.compute_column_spans();
+    assert_eq!(mappings.by_generated_location(), expected.by_generated_location());
+
+    // Out of order within a line, but in order across lines.
+    let mut builder = MappingsBuilder::<()>::new();
+    builder.add_mapping(0, 5, None);
+    builder.add_mapping(0, 1, None);
+    builder.add_mapping(2, 0, None);
+    let mappings = builder.finish();
+    assert_eq!(mappings.serialize(), "C,I;;A");
+}
+
+#[test]
+fn test_from_mappings() {
+    let expected = parse_mappings::<()>(TEST_MAPPINGS).unwrap();
+    let mut shuffled = expected.by_generated_location().to_vec();
+    shuffled.reverse();
+    let mappings = Mappings::<()>::from_mappings(shuffled).unwrap();
+    assert_eq!(mappings.by_generated_location(), expected.by_generated_location());
+
+    // Explicit column spans are kept.
+    let mut mappings = Mappings::<()>::from_mappings(vec![
+        Mapping {
+            generated_line: 0,
+            generated_column: 5,
+            ..Default::default()
+        },
+        Mapping {
+            generated_line: 0,
+            generated_column: 0,
+            last_generated_column: Some(2),
+            ..Default::default()
+        },
+    ]).unwrap();
+    mappings.compute_column_spans();
+    let spans: Vec<_> = mappings
+        .by_generated_location()
+        .iter()
+        .map(|m| m.last_generated_column)
+        .collect();
+    assert_eq!(spans, vec![Some(2), None]);
+    let options = QueryOptions {
+        within_span: true,
+        ..Default::default()
+    };
+    assert!(mappings.original_location_for_with_options(0, 3, &options).is_none());
+
+    let invalid = Mappings::<()>::from_mappings(vec![Mapping {
+        generated_line: 0,
+        generated_column: 5,
+        last_generated_column: Some(4),
+        original: None,
+    }]);
+    assert!(invalid.is_err());
+}
+
+#[test]
+fn test_equal_mappings_keep_their_order() {
+    // Many mappings at a few locations, told apart only by their spans, which
+    // the comparators ignore.
+    let mappings: Vec<_> = (0..64)
+        .map(|i| Mapping {
+            generated_line: 0,
+            generated_column: 3 - i % 4,
+            last_generated_column: Some(100 + i),
+            original: Some(OriginalLocation {
+                source: 0,
+                original_line: 0,
+                original_column: 0,
+                name: None,
+            }),
+        })
+        .collect();
+    let mut mappings = Mappings::<()>::from_mappings(mappings).unwrap();
+
+    let in_order = |ms: Vec<&Mapping>| {
+        ms.windows(2).all(|pair| {
+            pair[0].generated_column < pair[1].generated_column
+                || pair[0].last_generated_column < pair[1].last_generated_column
+        })
+    };
+    assert!(in_order(mappings.by_generated_location().iter().collect()));
+    assert!(in_order(mappings.by_original_location().collect()));
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn test_parallel_sort() {
+    // Enough mappings for sorting to be split across threads.
+    let mut input = vec![];
+    while input.len() < 2 * 1024 * 1024 {
+        input.extend_from_slice(TEST_MAPPINGS);
+        input.push(b';');
+    }
+
+    let mut expected = parse_mappings::<()>(&input).unwrap();
+    let mut shuffled = expected.by_generated_location().to_vec();
+    shuffled.reverse();
+    let mut mappings = Mappings::<()>::from_mappings(shuffled).unwrap();
+    assert_eq!(mappings.by_generated_location(), expected.by_generated_location());
+
+    expected.compute_column_spans();
+    assert!(mappings.by_original_location().eq(expected.by_original_location()));
+}
+
+#[test]
+fn test_mappings_equivalent() {
+    assert!(mappings_equivalent(TEST_MAPPINGS, TEST_MAPPINGS).unwrap());
+
+    // Redundant separators and trailing lines.
+    assert!(mappings_equivalent(b"AAAA,CAAA;ACAA", b",AAAA,,CAAA;ACAA;;").unwrap());
+
+    // Segments out of order within a line.
+    assert!(mappings_equivalent(b"AAAA,EACA", b"EACA,FADA").unwrap());
+
+    // Non-canonical VLQ encoding of zero.
+    assert!(mappings_equivalent(b"AAAA", b"gAAAA").unwrap());
+
+    assert!(!mappings_equivalent(b"AAAA;AAAA", b"AAAA;CAAA").unwrap());
+    assert!(!mappings_equivalent(b"AAAA;AAAA", b"AAAA").unwrap());
+    assert!(!mappings_equivalent(b"AAAA", b"AAAAA").unwrap());
+
+    assert!(mappings_equivalent(b"AAAA", b"!").is_err());
+}
+
+#[test]
+fn test_parse_in_steps() {
+    use source_map_mappings::MappingsParser;
+
+    let mut parser = MappingsParser::<()>::new(TEST_MAPPINGS);
+    let mut steps = 0;
+    while !parser.parse_step(4).unwrap() {
+        steps += 1;
+    }
+    assert_eq!(steps, 3);
+
+    let mappings = parser.finish().unwrap();
+    assert_eq!(
+        mappings.by_generated_location(),
+        parse_mappings::<()>(TEST_MAPPINGS)
+            .unwrap()
+            .by_generated_location()
+    );
+
+    let mut parser = MappingsParser::<()>::new(b"AAAA,...");
+    assert!(!parser.parse_step(1).unwrap());
+    assert!(parser.parse_step(1).is_err());
+    assert!(parser.parse_step(1).is_err());
+    assert!(parser.finish().is_err());
+}
+
+#[test]
+fn test_parse_mappings_with_options() {
+    use source_map_mappings::{parse_mappings_with_options, MappingsParser, ParseOptions, Recovery};
+
+    let mappings = parse_mappings_with_options::<()>(TEST_MAPPINGS, &ParseOptions::default()).unwrap();
+    assert_eq!(
+        mappings.by_generated_location(),
+        parse_mappings::<()>(TEST_MAPPINGS).unwrap().by_generated_location()
+    );
+    assert!(parse_mappings_with_options::<()>(b"AAAA;!", &ParseOptions::default()).is_err());
+
+    let options = ParseOptions {
+        recovery: Some(Recovery::SkipLine),
+        ..Default::default()
+    };
+    let mappings = parse_mappings_with_options::<()>(b"AAAA;!", &options).unwrap();
+    assert_eq!(mappings.by_generated_location().len(), 1);
+
+    let mut parser = MappingsParser::<()>::with_options(b"AAAA;!", &options);
+    assert!(parser.parse_step(usize::MAX).unwrap());
+    assert_eq!(parser.issues().len(), 1);
+    assert_eq!(parser.issues()[0].skipped, 5..6);
+}
+
+#[test]
+fn test_parse_generated_only() {
+    use source_map_mappings::{parse_mappings_with_options, ParseOptions};
+
+    let options = ParseOptions {
+        generated_only: true,
+        ..Default::default()
+    };
+    let mut mappings = parse_mappings_with_options::<()>(TEST_MAPPINGS, &options).unwrap();
+    let full = parse_mappings::<()>(TEST_MAPPINGS).unwrap();
+
+    let locations = |mappings: &Mappings| -> Vec<_> {
+        mappings
+            .by_generated_location()
+            .iter()
+            .map(|m| (m.generated_line, m.generated_column))
+            .collect()
+    };
+    assert_eq!(locations(&mappings), locations(&full));
+    assert!(mappings.by_generated_location().iter().all(|m| m.original.is_none()));
+    assert_eq!(mappings.by_original_location().count(), 0);
+}
+
+#[test]
+fn test_parse_skip_names() {
+    use source_map_mappings::{parse_mappings_with_options, ParseOptions};
+
+    let options = ParseOptions {
+        skip_names: true,
+        ..Default::default()
+    };
+    // The second mapping's name is relative to the first's, so both must still
+    // be decoded.
+    let mappings = parse_mappings_with_options::<()>(b"AAAAC,CAAAC,CAAA", &options).unwrap();
+    let full = parse_mappings::<()>(b"AAAAC,CAAAC,CAAA").unwrap();
+
+    for (m, full) in mappings.by_generated_location().iter().zip(full.by_generated_location()) {
+        let original = m.original.as_ref().unwrap();
+        let full_original = full.original.as_ref().unwrap();
+        assert_eq!(original.name, None);
+        assert_eq!(
+            (original.source, original.original_line, original.original_column),
+            (full_original.source, full_original.original_line, full_original.original_column)
+        );
+    }
+    assert_eq!(mappings.by_generated_location().len(), 3);
+}
+
+#[test]
+fn test_parse_strict() {
+    use source_map_mappings::{parse_mappings_with_options, Error, ParseOptions};
+
+    let options = ParseOptions {
+        strict: true,
+        ..Default::default()
+    };
+    let parse = |input: &[u8]| parse_mappings_with_options::<()>(input, &options).map(|_| ());
+
+    assert!(parse(TEST_MAPPINGS).is_ok());
+    assert!(parse(b"A,AAAA,AAAAA;A").is_ok());
+    assert_eq!(parse(b"AA"), Err(Error::InvalidSegmentLength));
+    assert_eq!(parse(b"AAA,A"), Err(Error::InvalidSegmentLength));
+    assert_eq!(parse(b"AAAAAA"), Err(Error::InvalidSegmentLength));
+
+    // Without strictness, a sixth field is misread as the start of another
+    // segment.
+    assert_eq!(parse_mappings::<()>(b"AAAAAC").unwrap().by_generated_location().len(), 2);
+}
+
+#[test]
+fn test_parse_unsorted_line() {
+    // Line 0: columns 2, 1, and 5. Line 1: columns 0 and 3, already sorted.
+    let mappings = parse_mappings::<()>(b"EAAA,DAAA,IAAA;AAAA,GAAA").unwrap();
+    let locations: Vec<_> = mappings
+        .by_generated_location()
+        .iter()
+        .map(|m| (m.generated_line, m.generated_column))
+        .collect();
+    assert_eq!(locations, vec![(0, 1), (0, 2), (0, 5), (1, 0), (1, 3)]);
+}
+
+#[test]
+fn test_parse_assume_sorted() {
+    use source_map_mappings::{parse_mappings_with_options, ParseOptions};
+
+    let options = ParseOptions {
+        assume_sorted: true,
+        ..Default::default()
+    };
+    let mappings = parse_mappings_with_options::<()>(TEST_MAPPINGS, &options).unwrap();
+    assert_eq!(
+        mappings.by_generated_location(),
+        parse_mappings::<()>(TEST_MAPPINGS).unwrap().by_generated_location()
+    );
+}
+
+#[test]
+fn test_parse_max_mappings() {
+    use source_map_mappings::{parse_mappings_with_options, Error, ParseOptions, Recovery};
+
+    let options = ParseOptions {
+        max_mappings: Some(3),
+        ..Default::default()
+    };
+    let parse = |input: &[u8], options: &ParseOptions| {
+        parse_mappings_with_options::<()>(input, options).map(|m| m.by_generated_location().len())
+    };
+
+    assert_eq!(parse(b"AAAA,CAAA;AAAA", &options), Ok(3));
+    assert_eq!(parse(b"AAAA,CAAA;AAAA,CAAA", &options), Err(Error::TooManyMappings));
+
+    // Recovering from malformed segments doesn't skip past the limit.
+    let lenient = ParseOptions {
+        recovery: Some(Recovery::SkipSegment),
+        ..options
+    };
+    assert_eq!(parse(b"AAAA,!,CAAA;AAAA", &lenient), Ok(3));
+    assert_eq!(parse(b"AAAA,!,CAAA;AAAA,CAAA", &lenient), Err(Error::TooManyMappings));
+}
+
+#[test]
+fn test_parse_record_segment_indices() {
+    use source_map_mappings::{parse_mappings_with_options, ParseOptions, Recovery};
+
+    let options = ParseOptions {
+        record_segment_indices: true,
+        ..Default::default()
+    };
+
+    // Not recorded by default.
+    assert_eq!(parse_mappings::<()>(b"AAAA").unwrap().segment_indices(), None);
+
+    // Follows out of order segments as their line is sorted.
+    let mut mappings = parse_mappings_with_options::<()>(b"EAAA,FAAA,CAAA;AAAA", &options).unwrap();
+    let columns: Vec<_> = mappings.by_generated_location().iter().map(|m| m.generated_column).collect();
+    assert_eq!(columns, vec![0, 1, 2, 0]);
+    assert_eq!(mappings.segment_indices(), Some(&[1, 2, 0, 3][..]));
+    let last = mappings.by_generated_location()[3].clone();
+    assert_eq!(mappings.segment_index(&last), Some(3));
+
+    // Skipped segments are still counted.
+    let lenient = ParseOptions {
+        recovery: Some(Recovery::SkipLine),
+        ..options
+    };
+    let skipped = parse_mappings_with_options::<()>(b"AAAA,!,CAAA,EAAA;AAAA", &lenient).unwrap();
+    assert_eq!(skipped.segment_indices(), Some(&[0, 4][..]));
+
+    // They move along with their mappings, and added mappings have none.
+    mappings.retain(|m| m.generated_column != 2);
+    assert_eq!(mappings.segment_indices(), Some(&[1, 2, 3][..]));
+    assert_eq!(mappings.segment_index(&last), Some(3));
+
+    let added = Mapping {
+        generated_line: 0,
+        generated_column: 5,
+        last_generated_column: None,
+        original: None,
+    };
+    mappings.insert(added.clone());
+    assert_eq!(mappings.segment_indices(), Some(&[1, 2, usize::MAX, 3][..]));
+    assert_eq!(mappings.segment_index(&added), None);
+    assert_eq!(mappings.segment_index(&last), Some(3));
+
+    mappings.remove_at_generated(0, 1);
+    assert_eq!(mappings.segment_indices(), Some(&[1, usize::MAX, 3][..]));
+}
+
+#[test]
+fn test_parse_record_segment_ranges() {
+    use source_map_mappings::{parse_mappings_with_options, ParseOptions};
+
+    let options = ParseOptions {
+        record_segment_ranges: true,
+        ..Default::default()
+    };
+    let input = b"AAAA,EAAA,DAAA;;CAAC";
+    let mappings = parse_mappings_with_options::<()>(input, &options).unwrap();
+
+    let ranges: Vec<_> = mappings
+        .by_generated_location()
+        .iter()
+        .map(|m| mappings.segment_range(m).unwrap())
+        .collect();
+    assert_eq!(ranges, vec![0..4, 10..14, 5..9, 16..20]);
+    assert_eq!(&input[ranges[1].clone()], b"DAAA");
+
+    assert_eq!(parse_mappings::<()>(input).unwrap().segment_range(&mappings.by_generated_location()[0]), None);
+
+    let mut mappings = mappings;
+    let removed = mappings.remove_at_generated(0, 1);
+    assert_eq!(mappings.segment_range(&mappings.by_generated_location()[1]), Some(5..9));
+    mappings.insert(removed[0].clone());
+    assert_eq!(mappings.segment_range(&mappings.by_generated_location()[1]), None);
+}
+
+#[test]
+fn test_parse_vlq_errors() {
+    use source_map_mappings::Error;
+
+    let parse = |input: &[u8]| parse_mappings::<()>(input).map(|m| m.by_generated_location().len());
+
+    assert_eq!(parse(b"AAAA,gBAAA,g+/BAAA"), Ok(3));
+    assert_eq!(parse(b"AAAA,g"), Err(Error::VlqUnexpectedEof));
+    assert_eq!(parse(b"AAAA,gAA"), Err(Error::VlqUnexpectedEof));
+    assert_eq!(parse(b"AAAA,A!AA"), Err(Error::VlqInvalidBase64));
+    assert_eq!(parse(b"AAAA,g,AA"), Err(Error::VlqInvalidBase64));
+    assert_eq!(parse(b"AAAA,//////////////A"), Err(Error::VlqOverflow));
+    assert_eq!(parse(b"AAAA,ggggggQ"), Err(Error::UnexpectedlyBigGeneratedColumn));
+    assert_eq!(parse(b"AAAA,D"), Err(Error::UnexpectedNegativeGeneratedColumn));
+}
+
+#[test]
+fn test_parse_field_errors() {
+    use source_map_mappings::Error;
+
+    let parse = |input: &[u8]| parse_mappings::<()>(input).map(|m| m.by_generated_location().len());
+
+    assert_eq!(parse(b"AAAA,ADAA"), Err(Error::UnexpectedNegativeSource));
+    assert_eq!(parse(b"AAAA;AADA"), Err(Error::UnexpectedNegativeOriginalLine));
+    assert_eq!(parse(b"AAAA,AAAD"), Err(Error::UnexpectedNegativeOriginalColumn));
+    assert_eq!(parse(b"AAAAA,AAAAD"), Err(Error::UnexpectedNegativeName));
+
+    assert_eq!(parse(b"AggggggQAA"), Err(Error::UnexpectedlyBigSource));
+    assert_eq!(parse(b"AAggggggQA"), Err(Error::UnexpectedlyBigOriginalLine));
+    assert_eq!(parse(b"AAAggggggQ"), Err(Error::UnexpectedlyBigOriginalColumn));
+    assert_eq!(parse(b"AAAAggggggQ"), Err(Error::UnexpectedlyBigName));
+
+    // Only the field that went out of range is blamed, even when a later
+    // field would too.
+    assert_eq!(parse(b"AAAA,ADDD"), Err(Error::UnexpectedNegativeSource));
+}
+
+#[test]
+fn test_parse_error_detail() {
+    use source_map_mappings::{Error, ErrorDetail, MappingsParser, ParseOptions, Recovery, SegmentField};
+
+    let detail = |input: &[u8], options: &ParseOptions| {
+        MappingsParser::<()>::with_options(input, options)
+            .finish_detailed()
+            .unwrap_err()
+    };
+    let options = ParseOptions::default();
+
+    assert_eq!(
+        detail(b"AAAA;CAAA,CA!A", &options),
+        ErrorDetail {
+            error: Error::VlqInvalidBase64,
+            offset: 10,
+            generated_line: 1,
+            segment_index: 2,
+            field: Some(SegmentField::OriginalLine),
+        }
+    );
+    assert_eq!(detail(b"AAAA,D", &options).field, Some(SegmentField::GeneratedColumn));
+    assert_eq!(detail(b"AAAAC,AAAAF", &options).field, Some(SegmentField::Name));
+
+    // Errors that aren't in any one field.
+    let strict = ParseOptions {
+        strict: true,
+        ..Default::default()
+    };
+    let length = detail(b"AAAA;;AA", &strict);
+    assert_eq!(length.error, Error::InvalidSegmentLength);
+    assert_eq!((length.offset, length.generated_line, length.field), (6, 2, None));
+
+    // Skipped segments are counted, and their fields forgotten.
+    let lenient = ParseOptions {
+        recovery: Some(Recovery::SkipSegment),
+        max_mappings: Some(1),
+        ..Default::default()
+    };
+    let too_many = detail(b"A!,AAAA,CAAA", &lenient);
+    assert_eq!(too_many.error, Error::TooManyMappings);
+    assert_eq!((too_many.segment_index, too_many.field), (2, None));
+
+    // Step-wise parsing keeps the detail around.
+    let mut parser = MappingsParser::<()>::new(b"AAAA,!");
+    assert!(parser.error_detail().is_none());
+    assert!(parser.parse_step(10).is_err());
+    assert_eq!(parser.error_detail().unwrap().offset, 5);
+}
+
+#[test]
+fn test_parse_mappings_lenient() {
+    use source_map_mappings::{parse_mappings_lenient, Error, ParseIssue};
+
+    // Line 0: a good segment, then one with an invalid base 64 character. Line
+    // 1: a good segment. Line 2: a VLQ cut off by the end of the line. Line 3:
+    // a good segment. Line 4: a VLQ cut off by the end of the string.
+    let input = b"AAAA,C!AA;AACA;g;AAAA;g";
+
+    let (mappings, issues) = parse_mappings_lenient::<()>(input);
+    let locations: Vec<_> = mappings
+        .by_generated_location()
+        .iter()
+        .map(|m| (m.generated_line, m.generated_column))
+        .collect();
+    assert_eq!(locations, vec![(0, 0), (1, 0), (3, 0)]);
+    assert_eq!(
+        issues,
+        vec![
+            ParseIssue {
+                error: Error::VlqInvalidBase64,
+                skipped: 5..9,
+            },
+            ParseIssue {
+                error: Error::VlqInvalidBase64,
+                skipped: 15..16,
+            },
+            ParseIssue {
+                error: Error::VlqUnexpectedEof,
+                skipped: 22..23,
+            },
+        ]
+    );
+
+    let (mappings, issues) = parse_mappings_lenient::<()>(TEST_MAPPINGS);
+    assert!(issues.is_empty());
+    assert_eq!(
+        mappings.by_generated_location(),
+        parse_mappings::<()>(TEST_MAPPINGS).unwrap().by_generated_location()
+    );
+}
+
+#[test]
+fn test_parse_mappings_with_recovery() {
+    use source_map_mappings::{parse_mappings_with_recovery, Error, ParseIssue, Recovery};
+
+    // Line 0: a good segment, one with an invalid base 64 character, and
+    // another good segment. Line 1: a VLQ cut off by the end of the line. Line
+    // 2: a good segment.
+    let input = b"AAAA,C!AA,CAAA;g;AAAA";
+
+    let locations = |mappings: &Mappings| -> Vec<_> {
+        mappings
+            .by_generated_location()
+            .iter()
+            .map(|m| (m.generated_line, m.generated_column))
+            .collect()
+    };
+
+    let (mappings, issues) = parse_mappings_with_recovery::<()>(input, Recovery::SkipSegment);
+    assert_eq!(locations(&mappings), vec![(0, 0), (0, 2), (2, 0)]);
+    assert_eq!(
+        issues,
+        vec![
+            ParseIssue {
+                error: Error::VlqInvalidBase64,
+                skipped: 5..9,
+            },
+            ParseIssue {
+                error: Error::VlqInvalidBase64,
+                skipped: 15..16,
+            },
+        ]
+    );
+
+    let (mappings, issues) = parse_mappings_with_recovery::<()>(input, Recovery::SkipLine);
+    assert_eq!(locations(&mappings), vec![(0, 0), (2, 0)]);
+    assert_eq!(issues[0].skipped, 5..14);
+
+    // Malformed segments that span several 16 byte blocks.
+    let filler = "A".repeat(40);
+    let input = format!("AAAA,!{},CAAA;!{};AAAA", filler, filler);
+
+    let (mappings, issues) = parse_mappings_with_recovery::<()>(input.as_bytes(), Recovery::SkipSegment);
+    assert_eq!(locations(&mappings), vec![(0, 0), (0, 1), (2, 0)]);
+    assert_eq!(issues[0].skipped, 5..46);
+    assert_eq!(issues[1].skipped, 52..93);
+
+    let (mappings, issues) = parse_mappings_with_recovery::<()>(input.as_bytes(), Recovery::SkipLine);
+    assert_eq!(locations(&mappings), vec![(0, 0), (2, 0)]);
+    assert_eq!(issues[0].skipped, 5..51);
+    assert_eq!(issues[1].skipped, 52..93);
+}
+
+#[test]
+fn test_parse_mappings_chunked() {
+    use source_map_mappings::parse_mappings_chunked;
+
+    let expected = parse_mappings::<()>(TEST_MAPPINGS).unwrap();
+
+    // Every way of splitting the string in two, and into single bytes.
+    for split in 0..TEST_MAPPINGS.len() + 1 {
+        let (a, b) = TEST_MAPPINGS.split_at(split);
+        let mappings = parse_mappings_chunked::<(), _>(vec![a, b]).unwrap();
+        assert_eq!(mappings.by_generated_location(), expected.by_generated_location());
+    }
+    let mappings = parse_mappings_chunked::<(), _>(TEST_MAPPINGS.chunks(1)).unwrap();
+    assert_eq!(mappings.by_generated_location(), expected.by_generated_location());
+
+    assert!(parse_mappings_chunked::<(), _>(vec![&b"AAAA,C"[..], &b"!AA"[..]]).is_err());
+    assert!(parse_mappings_chunked::<(), _>(vec![&b""[..]]).unwrap().by_generated_location().is_empty());
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn test_parse_mappings_parallel() {
+    use source_map_mappings::{parse_mappings_parallel, Error};
+
+    // Big enough to be split into many chunks.
+    let mut input = vec![];
+    while input.len() < 4 * 1024 * 1024 {
+        input.extend_from_slice(TEST_MAPPINGS);
+        input.push(b';');
+    }
+
+    let expected = parse_mappings::<()>(&input).unwrap();
+    let mappings = parse_mappings_parallel::<()>(&input).unwrap();
+    assert_eq!(mappings.by_generated_location(), expected.by_generated_location());
+
+    // The error nearest the start is reported, even when a relative field
+    // only goes out of range in a later chunk.
+    let len = input.len();
+    input[len / 2..len / 2 + 4].copy_from_slice(b"!!!!");
+    input.extend_from_slice(b"ADAA");
+    assert_eq!(parse_mappings_parallel::<()>(&input).err(), Some(Error::VlqInvalidBase64));
+
+    let mut input = input[..len / 2].to_vec();
+    input.extend_from_slice(b";AAAA;AAAA,AFAA;AAAA");
+    assert_eq!(parse_mappings_parallel::<()>(&input).err(), parse_mappings::<()>(&input).err());
+    assert!(parse_mappings_parallel::<()>(&input).is_err());
+
+    // Fields that go out of range in a later chunk are blamed just as they are
+    // when parsing sequentially.
+    let mut input = vec![];
+    while input.len() < 4 * 1024 * 1024 {
+        input.extend_from_slice(b"AAAA,CAAA;");
+    }
+    input.extend_from_slice(b"AAFA");
+    assert_eq!(parse_mappings_parallel::<()>(&input).err(), Some(Error::UnexpectedNegativeOriginalLine));
+    assert_eq!(parse_mappings::<()>(&input).err(), Some(Error::UnexpectedNegativeOriginalLine));
+}
+
+#[test]
+fn test_parse_mappings_into() {
+    use source_map_mappings::{parse_mappings_into, Error};
+
+    let expected = parse_mappings::<()>(TEST_MAPPINGS).unwrap();
+
+    let mut mappings = parse_mappings::<()>(b"AAAA;AAAA").unwrap();
+    mappings.compute_column_spans();
+    parse_mappings_into(&mut mappings, TEST_MAPPINGS).unwrap();
+    assert_eq!(mappings.by_generated_location(), expected.by_generated_location());
+    assert_eq!(
+        mappings.original_location_for(0, 10, Bias::GreatestLowerBound),
+        expected.original_location_for(0, 10, Bias::GreatestLowerBound)
+    );
+
+    assert_eq!(parse_mappings_into(&mut mappings, b"AAAA,!"), Err(Error::VlqInvalidBase64));
+    assert!(mappings.by_generated_location().is_empty());
+
+    parse_mappings_into(&mut mappings, b"AAAA;AACA").unwrap();
+    assert_eq!(mappings.by_generated_location().len(), 2);
+    assert_eq!(mappings.generated_location_for(0, 1, 0, Bias::GreatestLowerBound).unwrap().generated_line, 1);
+}
+
+#[test]
+fn test_parse_mappings_for_positions() {
+    use source_map_mappings::parse_mappings_for_positions;
+
+    let mappings = parse_mappings_for_positions::<()>(TEST_MAPPINGS, &[(0, 10), (1, 0)]).unwrap();
+    let columns: Vec<_> = mappings
+        .by_generated_location()
+        .iter()
+        .map(|m| (m.generated_line, m.generated_column))
+        .collect();
+
+    // The neighbors of column 10 on line 0, the last mapping on line 0 for
+    // sliding back from line 1 column 0, and the first mapping on line 1.
+    assert_eq!(columns, vec![(0, 9), (0, 18), (0, 32), (1, 1)]);
+
+    let found = mappings.original_location_for(0, 10, Bias::GreatestLowerBound).unwrap();
+    assert_eq!(found.original.as_ref().unwrap().original_column, 11);
+    let found = mappings.original_location_for(1, 0, Bias::GreatestLowerBound).unwrap();
+    assert_eq!(found.generated_column, 32);
+    let found = mappings.original_location_for(1, 0, Bias::LeastUpperBound).unwrap();
+    assert_eq!(found.generated_column, 1);
+
+    assert!(
+        parse_mappings_for_positions::<()>(TEST_MAPPINGS, &[])
+            .unwrap()
+            .by_generated_location()
+            .is_empty()
+    );
+}
+
+#[test]
+fn test_original_location_for_on_line_with_many_mappings() {
+    // One huge generated line with uneven gaps between columns, including
+    // duplicate columns, followed by a short second line.
+    let deltas = ["C", "E", "A", "K", "C", "gB"];
+    let mut input = String::from("AAAA");
+    for i in 0..5000 {
+        input.push(',');
+        input.push_str(deltas[i % deltas.len()]);
+    }
+    input.push_str(";AAAA,CAAA");
+
+    let mappings = parse_mappings::<()>(input.as_bytes()).unwrap();
+    let by_generated = mappings.by_generated_location();
+    let max_column = by_generated.iter().map(|m| m.generated_column).max().unwrap();
+
+    for column in 0..max_column + 3 {
+        let glb = mappings
+            .original_location_for(0, column, Bias::GreatestLowerBound)
+            .map(|m| m.generated_column);
+        let expected_glb = by_generated
+            .iter()
+            .rev()
+            .find(|m| m.generated_line == 0 && m.generated_column <= column)
+            .map(|m| m.generated_column);
+        assert_eq!(glb, expected_glb);
+
+        let lub = mappings
+            .original_location_for(0, column, Bias::LeastUpperBound)
+            .map(|m| (m.generated_line, m.generated_column));
+        let expected_lub = by_generated
+            .iter()
+            .find(|m| (m.generated_line, m.generated_column) >= (0, column))
+            .map(|m| (m.generated_line, m.generated_column));
+        assert_eq!(lub, expected_lub);
+    }
+}
+
+#[test]
+fn test_freeze() {
+    use std::sync::Arc;
+    use std::thread;
+
+    let mut mappings = parse_mappings::<()>(TEST_MAPPINGS).unwrap();
+    let frozen = Arc::new(parse_mappings::<()>(TEST_MAPPINGS).unwrap().freeze());
+
+    mappings.compute_column_spans();
+    assert_eq!(frozen.by_generated_location(), mappings.by_generated_location());
+    assert_eq!(
+        frozen.by_original_location().collect::<Vec<_>>(),
+        mappings.by_original_location().collect::<Vec<_>>()
+    );
+    assert_eq!(
+        frozen.generated_locations_for_name(0).collect::<Vec<_>>(),
+        mappings.generated_locations_for_name(0).collect::<Vec<_>>()
+    );
+
+    let queries = || {
+        (0..3).flat_map(|source| {
+            (0..3).flat_map(move |line| {
+                (0..30).flat_map(move |column| {
+                    vec![Bias::GreatestLowerBound, Bias::LeastUpperBound, Bias::ClosestMatch]
+                        .into_iter()
+                        .map(move |bias| (source, line, column, bias))
+                })
+            })
+        })
+    };
+
+    let expected: Vec<_> = queries()
+        .map(|(source, line, column, bias)| {
+            let found = mappings.generated_location_for(source, line, column, bias).cloned();
+            let all: Vec<_> = mappings
+                .all_generated_locations_for_with_bias(source, line, Some(column), bias)
+                .cloned()
+                .collect();
+            (found, all)
+        })
+        .collect();
+
+    let threads: Vec<_> = (0..4)
+        .map(|_| {
+            let frozen = frozen.clone();
+            thread::spawn(move || -> Vec<_> {
+                queries()
+                    .map(|(source, line, column, bias)| {
+                        let found = frozen.generated_location_for(source, line, column, bias).cloned();
+                        let all: Vec<_> = frozen
+                            .all_generated_locations_for_with_bias(source, line, Some(column), bias)
+                            .cloned()
+                            .collect();
+                        (found, all)
+                    })
+                    .collect()
+            })
+        })
+        .collect();
+
+    for thread in threads {
+        assert_eq!(thread.join().unwrap(), expected);
+    }
+
+    let thawed = Arc::try_unwrap(frozen).unwrap().into_inner();
+    assert_eq!(thawed.by_generated_location(), mappings.by_generated_location());
+}
+
+#[test]
+fn test_positions() {
+    use source_map_mappings::position::{GeneratedPosition, OriginalPosition};
+
+    let mut mappings = parse_mappings::<()>(TEST_MAPPINGS).unwrap();
+    mappings.compute_column_spans();
+
+    // Positions are ordered just as the mappings are.
+    let generated: Vec<_> = mappings.by_generated_location().iter().map(Mapping::generated_position).collect();
+    let mut sorted = generated.clone();
+    sorted.sort();
+    assert_eq!(generated, sorted);
+
+    let original: Vec<_> = mappings.by_original_location().map(|m| m.original_position().unwrap()).collect();
+    let mut sorted = original.clone();
+    sorted.sort();
+    assert_eq!(original, sorted);
+
+    let m = &mappings.by_generated_location()[1];
+    assert_eq!(m.generated_position(), GeneratedPosition::from((0, 5)));
+    assert_eq!(m.original_position(), Some(OriginalPosition::from((0, 0, 5))));
+    assert_eq!(m.generated_range().last_generated_column, Some(9));
+
+    assert_eq!(
+        mappings.original_location_at(GeneratedPosition { line: 0, column: 6 }, Bias::GreatestLowerBound),
+        mappings.original_location_for(0, 6, Bias::GreatestLowerBound)
+    );
+
+    let at = mappings
+        .generated_location_at(OriginalPosition { source: 0, line: 1, column: 6 }, Bias::LeastUpperBound)
+        .cloned();
+    assert_eq!(at.as_ref(), mappings.generated_location_for(0, 1, 6, Bias::LeastUpperBound));
+    let frozen = mappings.freeze();
+    assert_eq!(
+        frozen
+            .generated_location_at(OriginalPosition { source: 0, line: 1, column: 6 }, Bias::LeastUpperBound)
+            .cloned(),
+        at
+    );
+
+    let mut builder = MappingsBuilder::<()>::new();
+    builder.add_mapping_at(GeneratedPosition { line: 0, column: 1 }, None);
+    let built = builder.finish();
+    assert_eq!(built.by_generated_location()[0].generated_position(), GeneratedPosition::from((0, 1)));
+}
+
+#[test]
+fn test_into_vec() {
+    let mut mappings = parse_mappings::<()>(TEST_MAPPINGS).unwrap();
+    let expected = mappings.by_generated_location().to_vec();
+
+    assert_eq!((&mappings).into_iter().collect::<Vec<_>>(), expected.iter().collect::<Vec<_>>());
+    assert_eq!(parse_mappings::<()>(TEST_MAPPINGS).unwrap().into_vec(), expected);
+
+    // Rewrite every mapping by value, and rebuild from the results.
+    mappings.compute_column_spans();
+    let with_spans = mappings.by_generated_location().to_vec();
+    let shifted: Vec<_> = mappings
+        .into_iter()
+        .map(|mut m| {
+            m.generated_line += 1;
+            m
+        })
+        .collect();
+    for (m, original) in shifted.iter().zip(with_spans.iter()) {
+        assert_eq!(m.generated_line, original.generated_line + 1);
+        assert_eq!(m.last_generated_column, original.last_generated_column);
+    }
+    let rebuilt = Mappings::<()>::from_mappings(shifted).unwrap();
+    assert_eq!(rebuilt.len(), expected.len());
+}
+
+#[test]
+fn test_introspection() {
+    let mut mappings = parse_mappings::<()>(TEST_MAPPINGS).unwrap();
+    assert_eq!(mappings.len(), 13);
+    assert!(!mappings.is_empty());
+    assert_eq!(mappings.generated_line_count(), 2);
+    assert!(mappings.has_names());
+    mappings.generated_locations_for_name(0);
+    assert!(mappings.has_names());
+
+    let nameless = parse_mappings::<()>(b"AAAA;;CAAC,C").unwrap();
+    assert_eq!(nameless.len(), 3);
+    assert_eq!(nameless.generated_line_count(), 3);
+    assert!(!nameless.has_names());
+
+    let empty = parse_mappings::<()>(b";;").unwrap();
+    assert!(empty.is_empty());
+    assert_eq!(empty.generated_line_count(), 0);
+    assert!(!empty.has_names());
+}
+
+#[test]
+fn test_try_by_original_location() {
+    let mut mappings = parse_mappings::<()>(TEST_MAPPINGS).unwrap();
+    assert!(mappings.try_by_original_location().is_none());
+
+    // Sorting only some sources' mappings isn't enough.
+    mappings.by_original_source(0);
+    assert!(mappings.try_by_original_location().is_none());
+
+    mappings.ensure_original_index();
+    let shared = &mappings;
+    let expected: Vec<_> = shared.try_by_original_location().unwrap().cloned().collect();
+    assert_eq!(expected, mappings.by_original_location().cloned().collect::<Vec<_>>());
+
+    // Modifying the mappings throws the index away.
+    mappings.retain(|m| m.generated_column != 1);
+    assert!(mappings.try_by_original_location().is_none());
+    mappings.by_original_location().count();
+    assert!(mappings.try_by_original_location().is_some());
+}
+
+#[test]
+fn test_original_location_and_span_for() {
+    use source_map_mappings::coverage::GeneratedRange;
+
+    let mut mappings = parse_mappings::<()>(TEST_MAPPINGS).unwrap();
+
+    let (m, span) = mappings
+        .original_location_and_span_for(0, 6, Bias::GreatestLowerBound)
+        .unwrap();
+    assert_eq!(m.generated_column, 5);
+    assert_eq!(
+        span,
+        GeneratedRange {
+            generated_line: 0,
+            generated_column: 5,
+            last_generated_column: Some(9),
+        }
+    );
+
+    // The last mapping on a line spans until the end of the line.
+    let (_, span) = mappings
+        .original_location_and_span_for(0, 1000, Bias::GreatestLowerBound)
+        .unwrap();
+    assert_eq!(span.last_generated_column, None);
+
+    assert!(mappings
+        .original_location_and_span_for(5, 0, Bias::LeastUpperBound)
+        .is_none());
+
+    let frozen = parse_mappings::<()>(TEST_MAPPINGS).unwrap().freeze();
+    assert_eq!(
+        frozen.original_location_and_span_for(0, 6, Bias::GreatestLowerBound),
+        mappings.original_location_and_span_for(0, 6, Bias::GreatestLowerBound)
+    );
+}
+
+#[test]
+fn test_generated_end() {
+    use source_map_mappings::position::GeneratedPosition;
+
+    let mappings = parse_mappings::<()>(b"AAAA,EAAE;;CAAC").unwrap();
+    let by_generated = mappings.by_generated_location();
+
+    // Within a line, the span ends at the next mapping's column.
+    assert_eq!(mappings.generated_end(&by_generated[0]), Some(GeneratedPosition::from((0, 2))));
+
+    // The last mapping on a line spans to the next line's first mapping.
+    let end = mappings.generated_end(&by_generated[1]).unwrap();
+    assert_eq!(end, GeneratedPosition::from((2, 1)));
+    assert!(end.line > by_generated[1].generated_line);
+
+    // The very last mapping spans until the end of the file.
+    assert_eq!(mappings.generated_end(&by_generated[2]), None);
+
+    // Computed column spans are respected.
+    let mut mappings = parse_mappings::<()>(b"AAAA,EAAE;;CAAC").unwrap();
+    mappings.compute_column_spans();
+    let first = mappings.by_generated_location()[0].clone();
+    assert_eq!(mappings.generated_end(&first), Some(GeneratedPosition::from((0, 2))));
+}
+
+#[test]
+fn test_all_generated_locations_for_is_exact_size() {
+    // Three mappings at original line 1, column 2, and one at column 3.
+    let mut mappings = parse_mappings::<()>(b"AACE,CAAA,CAAA,CAAC;AAEA").unwrap();
+
+    let all = mappings.all_generated_locations_for(0, 1, Some(2));
+    assert_eq!(all.len(), 3);
+    let forwards: Vec<_> = all.clone().map(|m| m.generated_column).collect();
+    let mut backwards: Vec<_> = all.rev().map(|m| m.generated_column).collect();
+    backwards.reverse();
+    assert_eq!(forwards, vec![0, 1, 2]);
+    assert_eq!(backwards, forwards);
+
+    let mut all = mappings.all_generated_locations_for(0, 1, None);
+    assert_eq!(all.len(), 4);
+    all.next();
+    all.next_back();
+    assert_eq!(all.len(), 2);
+
+    // Sliding with a column never leaves the given line.
+    assert_eq!(
+        mappings
+            .all_generated_locations_for_with_bias(0, 2, Some(0), Bias::GreatestLowerBound)
+            .len(),
+        0
+    );
+    assert_eq!(mappings.all_generated_locations_for(0, 0, Some(0)).len(), 0);
+}